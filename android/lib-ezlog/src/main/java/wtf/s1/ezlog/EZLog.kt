@@ -28,6 +28,10 @@ object EZLog {
         AES128GCM,
 
         AES128GCMSIV,
+
+        CHACHA20POLY1305,
+
+        XCHACHA20POLY1305,
     }
 
     enum class Compress {
@@ -54,6 +58,8 @@ object EZLog {
     const val Aes256Gcm = 2
     const val Aes128GcmSiv = 3
     const val Aes256GcmSiv = 4
+    const val ChaCha20Poly1305 = 5
+    const val XChaCha20Poly1305 = 6
     const val CompressZlib = 1
     const val CompressDefault = 0
     const val CompressFast = 1
@@ -142,6 +148,30 @@ object EZLog {
         nativeRequestLogFilesForDate(logName, start, end)
     }
 
+    const val FetchFiles = 0
+    const val FetchDecoded = 1
+    const val FetchEncoded = 2
+
+    /**
+     * request the log files in the time range [start, end) with a callback of this request
+     *
+     * @param start unix timestamp in milliseconds
+     * @param end   unix timestamp in milliseconds
+     * @param mode  one of [FetchFiles], [FetchDecoded] and [FetchEncoded]
+     * @return the request id passed to the callback, or 0 if the request is rejected, like the
+     * time range is illegal, [EZLogFetchCallback.onFail] is called with the id 0
+     */
+    @JvmStatic
+    fun requestLogFiles(
+        logName: String,
+        start: Long,
+        end: Long,
+        mode: Int,
+        callback: EZLogFetchCallback
+    ): Long {
+        return nativeRequestLogFiles(logName, start, end, mode, callback)
+    }
+
     @JvmStatic
     @Deprecated("use EZLog.requestLogFilesForDate instead", ReplaceWith("requestLogFilesForDate"))
     fun _requestLogFilesForDate(logName: String, date: Date) {
@@ -198,20 +228,30 @@ object EZLog {
 
     @Volatile
     var isRegister = false
-    private var internalCallback: EZLogCallback? = null
+    private var internalCallback: EZLogFilesCallback? = null
 
     @JvmStatic
     @Synchronized
     fun addCallback(callback: EZLogCallback) {
         if (!isRegister) {
             isRegister = true
-            internalCallback = object : EZLogCallback {
+            internalCallback = object : EZLogFilesCallback {
                 override fun onSuccess(logName: String?, date: String?, logs: Array<String?>?) {
                     for (next in callbacks) {
                         next.onSuccess(logName, date, logs)
                     }
                 }
 
+                override fun onFilesSuccess(logName: String?, date: String?, files: Array<FetchedFile?>?) {
+                    for (next in callbacks) {
+                        if (next is EZLogFilesCallback) {
+                            next.onFilesSuccess(logName, date, files)
+                        } else {
+                            next.onSuccess(logName, date, files?.map { it?.path }?.toTypedArray())
+                        }
+                    }
+                }
+
                 override fun onFail(logName: String?, date: String?, err: String?) {
                     for (next in callbacks) {
                         next.onFail(logName, date, err)
@@ -301,6 +341,21 @@ object EZLog {
      */
     private external fun nativeRequestLogFilesForDate(logName: String, startTimestamp: Long, endTimeStamp: Long)
 
+    /**
+     * @param logName target log name
+     * @param startTimestamp the range start in milliseconds
+     * @param endTimeStamp the range end in milliseconds
+     * @param mode fetch mode
+     * @param callback called once with the result
+     */
+    private external fun nativeRequestLogFiles(
+        logName: String,
+        startTimestamp: Long,
+        endTimeStamp: Long,
+        mode: Int,
+        callback: EZLogFetchCallback
+    ): Long
+
     /**
      * trim log files
      */
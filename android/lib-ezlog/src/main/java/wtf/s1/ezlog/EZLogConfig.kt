@@ -110,6 +110,8 @@ class EZLogConfig(var logName: String, var dirPath: String) {
                 EZLog.Cipher.NONE -> 0
                 EZLog.Cipher.AES256GCM, EZLog.Cipher.AES256GCMSIV -> EZLog.Aes256GcmSiv
                 EZLog.Cipher.AES128GCM, EZLog.Cipher.AES128GCMSIV -> EZLog.Aes128GcmSiv
+                EZLog.Cipher.CHACHA20POLY1305 -> EZLog.ChaCha20Poly1305
+                EZLog.Cipher.XCHACHA20POLY1305 -> EZLog.XChaCha20Poly1305
             }
             return this
         }
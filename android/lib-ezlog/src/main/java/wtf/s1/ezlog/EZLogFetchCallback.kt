@@ -0,0 +1,10 @@
+package wtf.s1.ezlog
+
+/**
+ * Callback of one fetch request, it is called once with the request id, or with the id 0 if the
+ * request is rejected
+ */
+interface EZLogFetchCallback {
+    fun onSuccess(id: Long, logName: String?, date: String?, files: Array<FetchedFile?>?)
+    fun onFail(id: Long, logName: String?, date: String?, err: String?)
+}
@@ -0,0 +1,8 @@
+package wtf.s1.ezlog
+
+/**
+ * Callback with the metadata of the fetched files, the paths are passed to [onSuccess] if it is not implemented
+ */
+interface EZLogFilesCallback : EZLogCallback {
+    fun onFilesSuccess(logName: String?, date: String?, files: Array<FetchedFile?>?)
+}
@@ -0,0 +1,28 @@
+package wtf.s1.ezlog
+
+/**
+ * A log file returned by fetch, the times are unix timestamp in milliseconds
+ *
+ * @param path           file path
+ * @param size           file size in bytes
+ * @param version        file version in header
+ * @param compress       compress kind in header
+ * @param cipher         cipher kind in header
+ * @param keyFingerprint cipher key fingerprint in header
+ * @param createdTime    file creation time in header, no record is written before it
+ * @param rotatedTime    file rotation time, or the modified time of the active file
+ * @param records        count of the records, exclude the extra
+ * @param isActive       whether it is the file the logger is writing
+ */
+class FetchedFile(
+    val path: String,
+    val size: Long,
+    val version: Int,
+    val compress: Int,
+    val cipher: Int,
+    val keyFingerprint: Int,
+    val createdTime: Long,
+    val rotatedTime: Long,
+    val records: Long,
+    val isActive: Boolean,
+)
@@ -1,11 +1,13 @@
 use std::{
-    fs::OpenOptions,
-    io::{
-        BufReader,
-        BufWriter,
-        ErrorKind,
-    },
+    cmp,
+    fs::File,
+    io::ErrorKind,
+    ops::Range,
     path::PathBuf,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
 use time::OffsetDateTime;
@@ -13,6 +15,8 @@ use time::OffsetDateTime;
 use crate::{
     events::event,
     logger::Header,
+    manifest::Manifest,
+    ring,
     *,
 };
 
@@ -29,17 +33,45 @@ pub trait AppenderInner: Write + Send + Sync {
     /// Get the header
     fn header(&self) -> &Header;
 
+    /// Get the mutable header, call [AppenderInner::write_header_to_log] after change
+    fn header_mut(&mut self) -> &mut Header;
+
     /// Write header bytes to log file
     fn write_header_to_log(&mut self) -> std::result::Result<(), std::io::Error>;
 
+    /// The bytes of the log file
+    fn bytes(&self) -> &[u8];
+
     fn append(&mut self, buf: &[u8]) -> std::io::Result<()> {
         self.check_rolling(buf.len()).map_err(io::Error::other)?;
+        if let Some(mut ring) = self.header().ring {
+            let tail = self.header().recorder_position;
+            ring.drop_overwritten(self.bytes(), tail, buf.len())?;
+            self.header_mut().ring = Some(ring);
+        }
         self.write_all(buf)
     }
 
+    /// Wrap the ring buffer to the first record position, the oldest records will be overwritten
+    fn wrap(&mut self) -> std::io::Result<()> {
+        let Some(mut ring) = self.header().ring else {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "log file is not a ring buffer",
+            ));
+        };
+        let start = ring::data_start(self.header(), self.bytes())?;
+        let header = self.header_mut();
+        ring.wrap(start, header.recorder_position);
+        header.ring = Some(ring);
+        header.recorder_position = start;
+        self.write_header_to_log()
+    }
+
     fn check_rolling(&self, buf_size: usize) -> std::result::Result<(), AppenderError> {
         let now = OffsetDateTime::now_utc();
-        if self.is_overtime(now) {
+        // the ring buffer never rotates by time, it keeps the latest records
+        if self.header().ring.is_none() && self.is_overtime(now) {
             let rotate_time = self.header().rotate_time;
             return Err(AppenderError::RotateTimeExceeded {
                 current: now,
@@ -92,6 +124,9 @@ pub struct EZAppender {
 
 impl EZAppender {
     pub fn create_inner(config: &EZLogConfig) -> Result<Box<dyn AppenderInner>> {
+        if let AppenderKind::File { sync } = config.appender_kind() {
+            return Ok(Box::new(FileAppenderInner::new(config, sync)?));
+        }
         event!(Event::MapFile);
         match Self::create_mmap(config) {
             Ok(i) => {
@@ -149,7 +184,12 @@ impl EZAppender {
         drop(old_inner);
 
         // Rename the old log file (now that we've released the lock)
-        EZAppender::rename_current_file(config, &file_path, header_time)?;
+        let rotated = EZAppender::rename_current_file(config, &file_path, header_time)?;
+
+        Manifest::add(config, &rotated)
+            .unwrap_or_else(|e| event!(!Event::RotateFileError, "manifest add error"; &e));
+        // seal and hook on the rotate thread, the logger keeps writing to the new file
+        crate::hook::on_rotate(config, &rotated);
 
         // Create a new inner appender before acquiring any locks
         let new_inner = Self::create_inner(config)?;
@@ -186,8 +226,8 @@ impl EZAppender {
         config: &EZLogConfig,
         file_path: &PathBuf,
         time: OffsetDateTime,
-    ) -> Result<()> {
-        let mut count = 1;
+    ) -> Result<PathBuf> {
+        let mut sequence = config.next_sequence();
         if !file_path.is_file() {
             return Err(errors::LogError::IoError(io::Error::new(
                 ErrorKind::InvalidData,
@@ -197,60 +237,90 @@ impl EZAppender {
 
         let mut rename_count = 0;
         loop {
-            let new_name = config.file_name_with_date(time, count)?;
+            let new_name = config.file_name_with_date(time, sequence)?;
             let new_path = file_path.with_file_name(new_name);
             if !new_path.exists() {
                 match std::fs::rename(file_path, &new_path) {
-                    Ok(_) => return Ok(()),
+                    Ok(_) => return Ok(new_path),
                     Err(e) => {
                         if rename_count >= 3 {
                             return Err(errors::LogError::IoError(e));
                         }
                         rename_count += 1;
                         continue;
-                    } // If rename fails, try the next count
+                    } // If rename fails, try the next sequence
                 }
             }
-            count += 1;
+            sequence += 1;
         }
     }
 }
 
+/// The header in the mmap is updated at most once in this interval when records are written
+const HEADER_WRITE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The appender of the memory mapped log file
+///
+/// the recorder position is kept in memory and written to the header at flush, at drop and at
+/// most once a [HEADER_WRITE_INTERVAL]. the records after the position in the header are recovered
+/// when the file is mapped again, so a crash loses nothing in the page cache.
 pub(crate) struct MmapAppendInner {
     header: Header,
     file_path: PathBuf,
     mmap: MmapMut,
+    /// the range of the records not synced to the file
+    dirty: Option<Range<usize>>,
+    /// the last time the header is written to the mmap
+    header_written: Instant,
 }
 
 impl MmapAppendInner {
     pub(crate) fn new(config: &EZLogConfig) -> Result<Self> {
         let (mut file_path, mut mmap) = config.create_mmap_file()?;
 
-        if mmap.len() < Header::max_length() {
+        if mmap.len() < Header::length_compat(&config.version()) {
             EZAppender::rename_current_file(config, &file_path, OffsetDateTime::now_utc())?;
             (file_path, mmap) = config.create_mmap_file()?;
         }
 
         let mmap_header = &mmap
-            .get(0..Header::max_length())
+            .get(0..cmp::min(mmap.len(), Header::max_length()))
             .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "mmap get header vec error"))?;
         let mut c = Cursor::new(mmap_header);
         let mut header = Header::decode_with_config(&mut c, config)?;
 
         let mut write_init = false;
+        let mut recovered = false;
         if header.is_none() {
             header = Header::create(config);
             write_init = true;
+        } else if header.ring.is_none() && !header.is_empty() {
+            let position = recover_position(&header, &mmap);
+            if position != header.recorder_position {
+                event!(
+                    Event::MapFile,
+                    "recover recorder position {} -> {}",
+                    header.recorder_position,
+                    position
+                );
+                header.recorder_position = position;
+                recovered = true;
+            }
         }
 
         let mut inner = MmapAppendInner {
             header,
             file_path,
             mmap,
+            dirty: None,
+            header_written: Instant::now(),
         };
 
         if write_init {
             inner.write_init(config)?;
+        } else if recovered {
+            // the file is read before the next flush, like the chain tail read by the logger
+            inner.write_header_to_log()?;
         }
 
         Ok(inner)
@@ -269,22 +339,38 @@ impl MmapAppendInner {
                 ),
             )
         })?;
-        let mut c = Cursor::new(m);
-        c.write(buf)
+        let len = Cursor::new(m).write(buf)?;
+        let end = start + len;
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => cmp::min(dirty.start, start)..cmp::max(dirty.end, end),
+            None => start..end,
+        });
+        Ok(len)
     }
 }
 
 impl Write for MmapAppendInner {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         let start = self.header.recorder_position as usize;
-        self.header.recorder_position += buf.len() as u32;
-        self.write_header_to_log()?;
-        self.write_buf(buf, start)
+        let len = self.write_buf(buf, start)?;
+        self.header.recorder_position += len as u32;
+        // the ring state can not be recovered by scanning, it is written with every record
+        if self.header.ring.is_some() || self.header_written.elapsed() >= HEADER_WRITE_INTERVAL {
+            self.write_header_to_log()?;
+        }
+        Ok(len)
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(dirty) = self.dirty.take() {
+            if let Err(e) = self.mmap.flush_range(dirty.start, dirty.len()) {
+                self.dirty = Some(dirty);
+                return Err(e);
+            }
+        }
+        // the header is synced after the records, so it never points to the unsynced bytes
         self.write_header_to_log()?;
-        self.mmap.flush()
+        self.mmap.flush_range(0, self.header.length())
     }
 }
 
@@ -302,6 +388,10 @@ impl AppenderInner for MmapAppendInner {
         &self.header
     }
 
+    fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
     fn write_header_to_log(&mut self) -> std::result::Result<(), std::io::Error> {
         if self.header.is_empty() {
             self.header.init_record_position();
@@ -311,12 +401,18 @@ impl AppenderInner for MmapAppendInner {
         })?;
 
         let mut c = Cursor::new(mmap_header);
-        self.header.encode(&mut c)
+        self.header.encode(&mut c)?;
+        self.header_written = Instant::now();
+        Ok(())
     }
 
     fn file_len(&self) -> usize {
         self.mmap.len()
     }
+
+    fn bytes(&self) -> &[u8] {
+        &self.mmap
+    }
 }
 
 impl Drop for MmapAppendInner {
@@ -325,10 +421,29 @@ impl Drop for MmapAppendInner {
     }
 }
 
+/// The end of the last complete record from the recorder position in the header
+///
+/// the header may fall behind the records written before a crash, see [MmapAppendInner].
+fn recover_position(header: &Header, buf: &[u8]) -> u32 {
+    let mut position = cmp::max(header.recorder_position as usize, header.length());
+    while let Ok(len) = ring::frame_len(buf, position) {
+        position += len;
+    }
+    position as u32
+}
+
+/// The appender used when mmap fails, it keeps the file in memory
+///
+/// only the dirty range of the records and the header are written back to the file at flush.
 struct ByteArrayAppenderInner {
     header: Header,
     file_path: PathBuf,
+    file: File,
     byte_array: Vec<u8>,
+    /// the range of the records not written to the file
+    dirty: Option<Range<usize>>,
+    /// the header is changed since the last flush
+    header_dirty: bool,
 }
 
 impl ByteArrayAppenderInner {
@@ -339,22 +454,34 @@ impl ByteArrayAppenderInner {
             (file, file_path) = config.create_or_open_log_file()?;
         }
         let mut byte_array = vec![0u8; config.max_size() as usize];
-        BufReader::new(&file).read_exact(&mut byte_array)?;
 
-        let mut c = Cursor::new(byte_array.get(0..Header::max_length()).ok_or_else(|| {
-            io::Error::new(ErrorKind::InvalidData, "byte array get header vec error")
-        })?);
+        let header_len = cmp::min(byte_array.len(), Header::max_length());
+        read_at(&file, &mut byte_array[..header_len], 0)?;
+        let mut c = Cursor::new(&byte_array[..header_len]);
         let mut write_init = false;
         let mut header = Header::decode_with_config(&mut c, config)?;
         if header.is_none() {
             header = Header::create(config);
             write_init = true;
+        } else {
+            // only the bytes in use are read, the rest of the file is never read back
+            let used = header
+                .ring
+                .map_or(0, |ring| ring.end)
+                .max(header.recorder_position) as usize;
+            let used = cmp::min(used, byte_array.len());
+            if used > header_len {
+                read_at(&file, &mut byte_array[header_len..used], header_len as u64)?;
+            }
         }
 
         let mut inner = ByteArrayAppenderInner {
             header,
             file_path,
+            file,
             byte_array,
+            dirty: None,
+            header_dirty: false,
         };
 
         if write_init {
@@ -380,7 +507,13 @@ impl ByteArrayAppenderInner {
                     ),
                 )
             })?;
-        Cursor::new(buf_write).write(buf)
+        let len = Cursor::new(buf_write).write(buf)?;
+        let end = start + len;
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => cmp::min(dirty.start, start)..cmp::max(dirty.end, end),
+            None => start..end,
+        });
+        Ok(len)
     }
 }
 
@@ -393,11 +526,22 @@ impl Write for ByteArrayAppenderInner {
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        self.write_header_to_log()?;
-        let file = OpenOptions::new().write(true).open(self.file_path())?;
-        let mut write = BufWriter::new(file);
-        write.write_all(&self.byte_array)?;
-        write.flush()
+        if let Some(dirty) = self.dirty.take() {
+            if let Err(e) = write_at(
+                &self.file,
+                &self.byte_array[dirty.clone()],
+                dirty.start as u64,
+            ) {
+                self.dirty = Some(dirty);
+                return Err(e);
+            }
+        }
+        // the header is written after the records, so it never points to the unwritten bytes
+        if self.header_dirty {
+            write_at(&self.file, &self.byte_array[..self.header.length()], 0)?;
+            self.header_dirty = false;
+        }
+        Ok(())
     }
 }
 
@@ -415,6 +559,10 @@ impl AppenderInner for ByteArrayAppenderInner {
         &self.header
     }
 
+    fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
     fn write_header_to_log(&mut self) -> std::result::Result<(), std::io::Error> {
         if self.header.is_empty() {
             self.header.init_record_position();
@@ -429,12 +577,18 @@ impl AppenderInner for ByteArrayAppenderInner {
                 )
             })?;
         let mut c = Cursor::new(header);
-        self.header.encode(&mut c)
+        self.header.encode(&mut c)?;
+        self.header_dirty = true;
+        Ok(())
     }
 
     fn file_len(&self) -> usize {
         self.byte_array.len()
     }
+
+    fn bytes(&self) -> &[u8] {
+        &self.byte_array
+    }
 }
 
 impl Drop for ByteArrayAppenderInner {
@@ -443,6 +597,161 @@ impl Drop for ByteArrayAppenderInner {
     }
 }
 
+/// The appender writes the records to the end of the file, see [AppenderKind::File]
+///
+/// the file grows with the records, the header is written at flush and drop. the records after
+/// the position in the header are recovered when the file is opened again.
+struct FileAppenderInner {
+    header: Header,
+    file_path: PathBuf,
+    file: File,
+    /// the size to rotate the file
+    max_size: usize,
+    /// sync the file data at flush
+    sync: bool,
+}
+
+impl FileAppenderInner {
+    pub(crate) fn new(config: &EZLogConfig, sync: bool) -> Result<Self> {
+        let (file, file_path) = config.open_log_file()?;
+        let len = file.metadata()?.len() as usize;
+        let mut buf = vec![0u8; cmp::min(len, Header::max_length())];
+        read_at(&file, &mut buf, 0)?;
+        let decoded = match buf.is_empty() {
+            true => None,
+            false => Some(Header::decode_with_config(&mut Cursor::new(&buf), config)?),
+        };
+
+        let mut write_init = false;
+        let mut recovered = false;
+        let header = match decoded.filter(|header| !header.is_none()) {
+            Some(mut header) => {
+                // the bytes after the position are the records written before a crash, or the
+                // unused space of a file written by the mmap appender
+                let position = cmp::max(header.recorder_position as usize, header.length());
+                let mut tail = vec![0u8; len.saturating_sub(position)];
+                read_at(&file, &mut tail, position as u64)?;
+                let mut end = 0;
+                while let Ok(len) = ring::frame_len(&tail, end) {
+                    end += len;
+                }
+                recovered = header.recorder_position != (position + end) as u32;
+                header.recorder_position = (position + end) as u32;
+                file.set_len((position + end) as u64)?;
+                header
+            }
+            None => {
+                file.set_len(0)?;
+                write_init = true;
+                Header::create(config)
+            }
+        };
+
+        let mut inner = FileAppenderInner {
+            header,
+            file_path,
+            file,
+            max_size: config.max_size() as usize,
+            sync,
+        };
+
+        if write_init {
+            inner.write_init(config)?;
+        } else if recovered {
+            // the file is read before the next flush, like the chain tail read by the logger
+            inner.write_header_to_log()?;
+        }
+
+        Ok(inner)
+    }
+}
+
+impl Write for FileAppenderInner {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        write_at(&self.file, buf, self.header.recorder_position.into())?;
+        self.header.recorder_position += buf.len() as u32;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_header_to_log()?;
+        if self.sync {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+impl AppenderInner for FileAppenderInner {
+    fn is_oversize(&self, buf_size: usize) -> bool {
+        self.header.recorder_position as usize + buf_size > self.max_size
+    }
+
+    fn file_path(&self) -> &PathBuf {
+        &self.file_path
+    }
+
+    fn header(&self) -> &Header {
+        &self.header
+    }
+
+    fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
+    fn write_header_to_log(&mut self) -> std::result::Result<(), std::io::Error> {
+        if self.header.is_empty() {
+            self.header.init_record_position();
+        }
+        let mut buf = Vec::with_capacity(self.header.length());
+        self.header.encode(&mut buf)?;
+        write_at(&self.file, &buf, 0)
+    }
+
+    /// The size to rotate the file, the file itself grows with the records
+    fn file_len(&self) -> usize {
+        self.max_size
+    }
+
+    /// The records are not kept in memory, the ring buffer is not supported
+    fn bytes(&self) -> &[u8] {
+        &[]
+    }
+}
+
+impl Drop for FileAppenderInner {
+    fn drop(&mut self) {
+        self.flush().ok();
+    }
+}
+
+#[cfg(unix)]
+fn write_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::write_all_at(file, buf, offset)
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(not(unix))]
+fn write_at(mut file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    use std::io::Seek;
+    file.seek(io::SeekFrom::Start(offset))?;
+    file.write_all(buf)
+}
+
+#[cfg(not(unix))]
+fn read_at(mut file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::io::{
+        Read,
+        Seek,
+    };
+    file.seek(io::SeekFrom::Start(offset))?;
+    file.read_exact(buf)
+}
+
 struct NopInner {
     file_path: PathBuf,
     header: Header,
@@ -474,6 +783,10 @@ impl AppenderInner for NopInner {
         &self.header
     }
 
+    fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
     fn write_header_to_log(&mut self) -> std::result::Result<(), std::io::Error> {
         Ok(())
     }
@@ -481,6 +794,10 @@ impl AppenderInner for NopInner {
     fn file_len(&self) -> usize {
         0
     }
+
+    fn bytes(&self) -> &[u8] {
+        &[]
+    }
 }
 
 impl Write for NopInner {
@@ -522,6 +839,7 @@ mod tests {
     };
     use std::io::{
         BufReader,
+        Read,
         Seek,
         SeekFrom,
     };
@@ -667,6 +985,133 @@ mod tests {
         fs::remove_file(appender.file_path()).unwrap();
     }
 
+    #[test]
+    fn test_mmap_recover_position() {
+        let dir = test_compat::test_path().join("mmap_recover");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let config = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("mmap_recover")
+            .max_size(1024)
+            .build();
+
+        let mut appender = MmapAppendInner::new(&config).unwrap();
+        let initial = appender.header().recorder_position;
+        for content in ["first", "second"] {
+            let record = logger::encode_content(content.as_bytes().to_vec()).unwrap();
+            appender.append(&record).unwrap();
+        }
+        let position = appender.header().recorder_position;
+        assert_eq!(appender.dirty, Some(initial as usize..position as usize));
+        // the header in the file is not written with every record
+        let written = Header::decode(&mut Cursor::new(appender.bytes())).unwrap();
+        assert_eq!(written.recorder_position, initial);
+
+        // crash without flush, the records are recovered from the page cache
+        std::mem::forget(appender);
+        let mut appender = MmapAppendInner::new(&config).unwrap();
+        assert_eq!(appender.header().recorder_position, position);
+        appender.flush().unwrap();
+        assert!(appender.dirty.is_none());
+        let written = Header::decode(&mut Cursor::new(appender.bytes())).unwrap();
+        assert_eq!(written.recorder_position, position);
+        drop(appender);
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_byte_array_incremental_flush() {
+        let dir = test_compat::test_path().join("byte_array_flush");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let config = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("byte_array_flush")
+            .max_size(1024)
+            .build();
+
+        let mut appender = ByteArrayAppenderInner::new(&config).unwrap();
+        appender.write_all(b"first").unwrap();
+        appender.flush().unwrap();
+        assert!(appender.dirty.is_none() && !appender.header_dirty);
+
+        // the bytes out of the dirty range are not written again
+        let path = appender.file_path().clone();
+        let file = current_file(&path).unwrap();
+        write_at(&file, b"untouched", 1000).unwrap();
+        let position = appender.header().recorder_position as usize;
+        appender.write_all(b"second").unwrap();
+        assert_eq!(appender.dirty, Some(position..position + b"second".len()));
+        appender.flush().unwrap();
+        drop(appender);
+
+        let buf = fs::read(&path).unwrap();
+        assert_eq!(&buf[1000..1009], b"untouched");
+        assert_eq!(&buf[position..position + 6], b"second");
+
+        let appender = ByteArrayAppenderInner::new(&config).unwrap();
+        assert_eq!(
+            appender.header().recorder_position as usize,
+            position + b"second".len()
+        );
+        assert_eq!(
+            &appender.bytes()[position - 5..position + 6],
+            b"firstsecond"
+        );
+        assert!(appender.bytes()[1000..1009].iter().all(|b| *b == 0));
+        drop(appender);
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_file_appender() {
+        let dir = test_compat::test_path().join("file_appender");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let config = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("file_appender")
+            .max_size(1024)
+            .appender(AppenderKind::File { sync: true })
+            .build();
+
+        let mut appender = FileAppenderInner::new(&config, true).unwrap();
+        let path = appender.file_path().clone();
+        let record = logger::encode_content(b"file appender".to_vec()).unwrap();
+        appender.append(&record).unwrap();
+        appender.flush().unwrap();
+        let position = appender.header().recorder_position;
+        assert_eq!(fs::metadata(&path).unwrap().len(), position as u64);
+        assert!(appender.is_oversize(1024 - position as usize + 1));
+
+        // crash after a record and a partial one, the complete record is recovered
+        appender.append(&record).unwrap();
+        std::mem::forget(appender);
+        let file = current_file(&path).unwrap();
+        let len = file.metadata().unwrap().len();
+        write_at(&file, &record[..4], len).unwrap();
+        let appender = FileAppenderInner::new(&config, false).unwrap();
+        let recovered = position + record.len() as u32;
+        assert_eq!(appender.header().recorder_position, recovered);
+        assert_eq!(fs::metadata(&path).unwrap().len(), recovered as u64);
+        drop(appender);
+        let header = Header::decode(&mut fs::read(&path).unwrap().as_slice()).unwrap();
+        assert_eq!(header.recorder_position, recovered);
+
+        // rotated by the max size, the file is not preallocated
+        let logger = EZLogger::new(config).unwrap();
+        let content = String::from_utf8(vec![b'a'; 600]).unwrap();
+        logger
+            .append(EZRecord::builder().content(content.clone()).build())
+            .unwrap();
+        let result = logger
+            .append(EZRecord::builder().content(content).build())
+            .unwrap();
+        assert!(matches!(result, AppendSuccess::RotatedAndRetried));
+        logger.flush().unwrap();
+        assert!(fs::metadata(&path).unwrap().len() < 1024);
+        drop(logger);
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
     fn rotate_config(dir: &str) -> EZLogConfigBuilder {
         std::fs::create_dir_all(test_compat::test_path().join(dir)).unwrap();
         EZLogConfigBuilder::new()
@@ -699,15 +1144,24 @@ mod tests {
         }
 
         let mut count = 0;
-        // count files in the rotate directory, which name contains "rorate"
+        // count log files in the rotate directory, which name contains "rorate"
         for entry in fs::read_dir(test_compat::test_path().join("rotate")).unwrap() {
             let entry = entry.unwrap();
-            if entry.file_name().to_str().unwrap().contains("rotate") {
+            let name = entry.file_name().to_str().unwrap().to_string();
+            if name.contains("rotate") && name != "rotate.manifest" {
                 count += 1;
             }
         }
+        let mut sequences: Vec<u64> = fs::read_dir(test_compat::test_path().join("rotate"))
+            .unwrap()
+            .filter_map(|entry| {
+                config.read_file_name_sequence(entry.unwrap().file_name().to_str().unwrap())
+            })
+            .collect();
+        sequences.sort();
         fs::remove_dir_all(test_compat::test_path().join("rotate")).unwrap();
         assert!(count == 10);
+        assert_eq!(sequences, (1..=9).collect::<Vec<u64>>());
     }
 
     #[test]
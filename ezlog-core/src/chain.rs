@@ -0,0 +1,368 @@
+use std::{
+    fmt,
+    fs,
+    io::Cursor,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use hmac::{
+    Hmac,
+    Mac,
+};
+use integer_encoding::VarInt;
+use sha2::Sha256;
+
+use crate::{
+    errors::LogError,
+    Header,
+    Result,
+    RECORD_SIGNATURE_END,
+    RECORD_SIGNATURE_START,
+};
+
+/// Length of the truncated record MAC
+pub const CHAIN_MAC_LEN: usize = 16;
+
+/// Truncated HMAC-SHA256 of a record
+pub type ChainMac = [u8; CHAIN_MAC_LEN];
+
+/// MAC of the record content chained over the previous record's MAC
+///
+/// the content is the encoded record bytes, so the chain can be verified without the cipher key.
+pub fn chain_mac(key: &[u8], prev: &ChainMac, content: &[u8]) -> Result<ChainMac> {
+    let mut hmac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+        .map_err(|e| LogError::Crypto(format!("chain key invalid {}", e)))?;
+    hmac.update(prev);
+    hmac.update(content);
+    let mut mac = ChainMac::default();
+    mac.copy_from_slice(&hmac.finalize().into_bytes()[..CHAIN_MAC_LEN]);
+    Ok(mac)
+}
+
+/// Split the record chunk into the content and the MAC at its end
+pub(crate) fn split_mac(chunk: &[u8]) -> Result<(&[u8], ChainMac)> {
+    let split = chunk
+        .len()
+        .checked_sub(CHAIN_MAC_LEN)
+        .ok_or_else(|| LogError::Parse("record is shorter than the chain mac".to_string()))?;
+    let mut mac = ChainMac::default();
+    mac.copy_from_slice(&chunk[split..]);
+    Ok((&chunk[..split], mac))
+}
+
+/// The records of the log file buffer, `(position, chunk)` without the extra
+fn records<'a>(header: &Header, buf: &'a [u8]) -> Result<Vec<(u64, &'a [u8])>> {
+    let end = std::cmp::min(header.recorder_position as usize, buf.len());
+    let mut position = header.length();
+    let mut records = Vec::new();
+    while position < end {
+        let corrupted = |reason: &str| LogError::Parse(format!("{} at {}", reason, position));
+        if buf[position] != RECORD_SIGNATURE_START {
+            return Err(corrupted("record start signature not found"));
+        }
+        let (size, size_len) = usize::decode_var(&buf[position + 1..end])
+            .ok_or_else(|| corrupted("record size invalid"))?;
+        let start = position + 1 + size_len;
+        let chunk = buf
+            .get(start..start + size)
+            .ok_or_else(|| corrupted("record truncated"))?;
+        if buf.get(start + size) != Some(&RECORD_SIGNATURE_END) {
+            return Err(corrupted("record end signature not found"));
+        }
+        if !header.is_extra_index(position as u64) {
+            records.push((position as u64, chunk));
+        }
+        position = start + size + 1;
+    }
+    Ok(records)
+}
+
+/// The last MAC of the log file, `None` if the file is not chained
+pub(crate) fn last_mac(buf: &[u8]) -> Result<Option<ChainMac>> {
+    let header = Header::decode(&mut Cursor::new(buf))?;
+    let Some(prev) = header.chain_prev else {
+        return Ok(None);
+    };
+    match records(&header, buf)?.last() {
+        Some((_, chunk)) => split_mac(chunk).map(|(_, mac)| Some(mac)),
+        None => Ok(Some(prev)),
+    }
+}
+
+/// The first break of the hash chain found by [verify_chain]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainBreak {
+    /// the log file
+    pub path: PathBuf,
+    /// position of the broken record, `None` if the header does not link to the previous file
+    pub position: Option<u64>,
+    pub reason: String,
+}
+
+impl fmt::Display for ChainBreak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(
+                f,
+                "{} at record {}: {}",
+                self.path.display(),
+                position,
+                self.reason
+            ),
+            None => write!(f, "{} header: {}", self.path.display(), self.reason),
+        }
+    }
+}
+
+/// Result of [verify_chain]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainSummary {
+    /// verified files in chain order
+    pub files: Vec<PathBuf>,
+    /// verified record count
+    pub records: usize,
+    /// the first break, `None` if the whole chain is intact
+    pub first_break: Option<ChainBreak>,
+}
+
+struct ChainedFile {
+    path: PathBuf,
+    header: Header,
+    buf: Vec<u8>,
+    last: ChainMac,
+}
+
+impl ChainedFile {
+    fn chain_break(&self, position: Option<u64>, reason: impl Into<String>) -> ChainBreak {
+        ChainBreak {
+            path: self.path.clone(),
+            position,
+            reason: reason.into(),
+        }
+    }
+
+    /// Verify the records, returns the last MAC and the record count
+    fn verify(&self, key: &[u8]) -> std::result::Result<(ChainMac, usize), ChainBreak> {
+        let records =
+            records(&self.header, &self.buf).map_err(|e| self.chain_break(None, e.to_string()))?;
+        let mut tail = self.header.chain_prev.unwrap_or_default();
+        for (position, chunk) in records.iter() {
+            let (content, mac) =
+                split_mac(chunk).map_err(|e| self.chain_break(Some(*position), e.to_string()))?;
+            let expected = chain_mac(key, &tail, content)
+                .map_err(|e| self.chain_break(Some(*position), e.to_string()))?;
+            if expected != mac {
+                return Err(
+                    self.chain_break(Some(*position), "record mac mismatch, removed or modified")
+                );
+            }
+            tail = mac;
+        }
+        Ok((tail, records.len()))
+    }
+}
+
+/// Verify the hash chain of the log files in the directory
+///
+/// only files written with a chain key are checked, `name` filters the files of one logger.
+/// The files are ordered by the header timestamp and the chain links, then every record MAC
+/// is recomputed and every file header must reference the last MAC of the previous file.
+/// The first file may reference a file which is already trimmed.
+pub fn verify_chain(dir: impl AsRef<Path>, name: Option<&str>, key: &[u8]) -> Result<ChainSummary> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() || !is_logger_file(&path, name) {
+            continue;
+        }
+        let buf = fs::read(&path)?;
+        let Ok(header) = Header::decode(&mut Cursor::new(&buf)) else {
+            continue;
+        };
+        if header.chain_prev.is_none() {
+            continue;
+        }
+        let last = last_mac(&buf).ok().flatten().unwrap_or_default();
+        files.push(ChainedFile {
+            path,
+            header,
+            buf,
+            last,
+        });
+    }
+    files.sort_by(|a, b| (a.header.timestamp, &a.path).cmp(&(b.header.timestamp, &b.path)));
+
+    // files rotated in the same second share the timestamp, follow the links first
+    let mut ordered: Vec<ChainedFile> = Vec::with_capacity(files.len());
+    while !files.is_empty() {
+        let next = match ordered.last() {
+            Some(prev) => files
+                .iter()
+                .position(|f| f.header.chain_prev == Some(prev.last)),
+            // the start is the file not linked to any other file, a new chain is preferred
+            None => {
+                let starts: Vec<usize> = (0..files.len())
+                    .filter(|&i| {
+                        !files
+                            .iter()
+                            .enumerate()
+                            .any(|(j, o)| i != j && files[i].header.chain_prev == Some(o.last))
+                    })
+                    .collect();
+                starts
+                    .iter()
+                    .find(|&&i| files[i].header.chain_prev == Some(ChainMac::default()))
+                    .or(starts.first())
+                    .copied()
+            }
+        }
+        .unwrap_or(0);
+        ordered.push(files.remove(next));
+    }
+
+    let mut summary = ChainSummary::default();
+    let mut prev: Option<ChainMac> = None;
+    for file in ordered {
+        if prev.is_some() && prev != file.header.chain_prev {
+            summary.first_break =
+                Some(file.chain_break(None, "previous file is missing or modified"));
+            break;
+        }
+        match file.verify(key) {
+            Ok((tail, records)) => {
+                prev = Some(tail);
+                summary.records += records;
+                summary.files.push(file.path);
+            }
+            Err(e) => {
+                summary.first_break = Some(e);
+                break;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+fn is_logger_file(path: &Path, name: Option<&str>) -> bool {
+    let Some(name) = name else {
+        return true;
+    };
+    path.file_name()
+        .and_then(|f| f.to_str())
+        .map(|f| f.starts_with(&format!("{}.", name)) || f.starts_with(&format!("{}_", name)))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AppenderKind,
+        EZLogConfigBuilder,
+        EZLogger,
+        EZRecord,
+    };
+
+    fn append(logger: &EZLogger, count: usize) {
+        for i in 0..count {
+            logger
+                .append(EZRecord::builder().content(format!("audit {}", i)).build())
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let dir = test_compat::test_path().join("verify_chain");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let key = b"chain key";
+        let config = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("chain")
+            .extra("audit")
+            .chain_key(key.to_vec())
+            .build();
+        assert!(config.check_valid().is_ok());
+
+        let logger = EZLogger::new(config.clone()).unwrap();
+        append(&logger, 5);
+        logger.rotate_if_not_empty().unwrap();
+        append(&logger, 3);
+        drop(logger);
+        // the tail is recovered from the current file
+        let logger = EZLogger::new(config.clone()).unwrap();
+        append(&logger, 2);
+        logger.rotate_if_not_empty().unwrap();
+        append(&logger, 1);
+        drop(logger);
+
+        let summary = verify_chain(&dir, Some("chain"), key).unwrap();
+        assert_eq!(summary.first_break, None);
+        assert_eq!(summary.files.len(), 3);
+        assert_eq!(summary.records, 11);
+        assert!(summary.files[2].ends_with("chain.mmap"));
+
+        let wrong_key = verify_chain(&dir, None, b"wrong key").unwrap();
+        assert!(wrong_key.first_break.is_some());
+
+        // modify the last byte of the first record content in the second file
+        let second = summary.files[1].clone();
+        let origin = fs::read(&second).unwrap();
+        let header = Header::decode(&mut Cursor::new(&origin)).unwrap();
+        let (position, chunk) = records(&header, &origin).unwrap()[0];
+        let mut tampered = origin.clone();
+        tampered[position as usize + chunk.len()] ^= 0x01;
+        fs::write(&second, &tampered).unwrap();
+        let broken = verify_chain(&dir, Some("chain"), key)
+            .unwrap()
+            .first_break
+            .unwrap();
+        assert_eq!(broken.path, second);
+        assert_eq!(broken.position, Some(position));
+
+        // remove the second file, the last file does not link to the first one
+        fs::remove_file(&second).unwrap();
+        let broken = verify_chain(&dir, Some("chain"), key)
+            .unwrap()
+            .first_break
+            .unwrap();
+        assert_eq!(broken.path, summary.files[2]);
+        assert_eq!(broken.position, None);
+
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_verify_chain_after_crash() {
+        let dir = test_compat::test_path().join("verify_chain_crash");
+        let key = b"chain key";
+        for appender in [AppenderKind::Mmap, AppenderKind::File { sync: false }] {
+            fs::remove_dir_all(&dir).unwrap_or_default();
+            let config = EZLogConfigBuilder::new()
+                .dir_path(&dir)
+                .name("chain_crash")
+                .appender(appender)
+                .chain_key(key.to_vec())
+                .build();
+
+            let logger = EZLogger::new(config.clone()).unwrap();
+            append(&logger, 3);
+            // crash without flush, the header in the file is behind the records
+            std::mem::forget(logger);
+            let logger = EZLogger::new(config.clone()).unwrap();
+            append(&logger, 2);
+            logger.rotate_if_not_empty().unwrap();
+            append(&logger, 1);
+            drop(logger);
+
+            let summary = verify_chain(&dir, Some("chain_crash"), key).unwrap();
+            assert_eq!(summary.first_break, None, "{:?}", appender);
+            assert_eq!(summary.files.len(), 2);
+            assert_eq!(summary.records, 6);
+        }
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+}
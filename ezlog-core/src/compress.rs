@@ -15,6 +15,8 @@ pub enum CompressKind {
     /// ZLIB compression
     /// we use [flate2](https://github.com/rust-lang/flate2-rs) to implement this
     ZLIB,
+    /// User registered compression, see [crate::register_compress]
+    CUSTOM(u8),
     /// No compression
     NONE,
     /// Unknown compression
@@ -26,6 +28,7 @@ impl From<u8> for CompressKind {
         match orig {
             0x00 => CompressKind::NONE,
             0x01 => CompressKind::ZLIB,
+            id if crate::registry::is_custom_id(id) => CompressKind::CUSTOM(id),
             _ => CompressKind::UNKNOWN,
         }
     }
@@ -36,6 +39,7 @@ impl From<CompressKind> for u8 {
         match orig {
             CompressKind::NONE => 0x00,
             CompressKind::ZLIB => 0x01,
+            CompressKind::CUSTOM(id) => id,
             CompressKind::UNKNOWN => 0xff,
         }
     }
@@ -1,6 +1,5 @@
 use std::{
     cmp,
-    collections::hash_map::DefaultHasher,
     fmt,
     fs::{
         self,
@@ -26,6 +25,8 @@ use time::{
     Date,
     Duration,
     OffsetDateTime,
+    Time,
+    UtcOffset,
 };
 
 use crate::events::Event;
@@ -35,9 +36,17 @@ use crate::{
     errors::LogError,
     events::event,
     logger::Header,
+    manifest::{
+        Manifest,
+        MANIFEST_SUFFIX,
+    },
     CipherKind,
     CompressKind,
     CompressLevel,
+    KdfParams,
+    ManifestEntry,
+    RedactRule,
+    SecretBytes,
     Version,
     DEFAULT_LOG_FILE_SUFFIX,
     DEFAULT_LOG_NAME,
@@ -47,6 +56,8 @@ use crate::{
 };
 
 pub const DATE_FORMAT: &str = "[year]_[month]_[day]";
+/// The time of day follows the date in the rotated file name
+pub const TIME_FORMAT: &str = "[hour]_[minute]";
 
 /// A config to set up [EZLogger]
 #[derive(Debug, Clone)]
@@ -79,6 +90,10 @@ pub struct EZLogConfig {
     ///
     /// if log file size is greater than this, logger will rotate the log file
     max_size: u64,
+    /// The maxium total size of the log files
+    ///
+    /// if set, the oldest rotated files are removed after rotation and trim to keep the total size below this
+    max_total_size: Option<u64>,
     /// Log content compress kind.
     // compress kind, default is [CompressKind::NONE]
     compress: CompressKind,
@@ -93,15 +108,57 @@ pub struct EZLogConfig {
     /// Log content cipher key.
     ///
     /// cipher key, default is `None`
-    cipher_key: Option<Vec<u8>>,
+    cipher_key: Option<SecretBytes>,
     /// Log content cipher nonce.
     ///
     /// cipher nonce, default is `None`
-    cipher_nonce: Option<Vec<u8>>,
+    cipher_nonce: Option<SecretBytes>,
+    /// Passphrase to derive the cipher key.
+    ///
+    /// if set, the cipher key is derived by [KdfParams] when the logger is created
+    cipher_passphrase: Option<SecretBytes>,
+    /// Key derivation params.
+    ///
+    /// kdf params, default is `None`, recorded in the log file header
+    kdf: Option<KdfParams>,
+    /// Key of the tamper-evident hash chain.
+    ///
+    /// if set, every record carries a MAC chained over the previous record, see [crate::verify_chain]
+    chain_key: Option<SecretBytes>,
+    /// Ed25519 signing key seed.
+    ///
+    /// if set, the rotated file is sealed with a signature footer, see [crate::verify_signature]
+    signing_key: Option<SecretBytes>,
+    /// Redaction rules.
+    ///
+    /// applied in order to the formatted record before it is compressed and encrypted
+    redact_rules: Vec<RedactRule>,
+    /// Ring buffer mode.
+    ///
+    /// if true, the log file wraps around and overwrites the oldest records instead of rotating
+    ring_buffer: bool,
     /// rotate duration
     ///
     /// the duration after which the log file will be rotated
     rotate_duration: Duration,
+    /// Align the rotation to the wall clock.
+    ///
+    /// if true, the log file is rotated at the multiples of `rotate_duration` since midnight in
+    /// `time_offset`, instead of `rotate_duration` after it is created
+    rotate_aligned: bool,
+    /// The offset of the local time.
+    ///
+    /// used by the dates in the file names, the rotation alignment and the record time, default
+    /// is UTC. it is recorded in the [Version::V3] header
+    time_offset: UtcOffset,
+    /// When the records are synced to the file.
+    ///
+    /// default is [Durability::None]
+    durability: Durability,
+    /// How the log file is written.
+    ///
+    /// default is [AppenderKind::Mmap]
+    appender: AppenderKind,
 
     /// Extra info to be added to log header
     ///
@@ -110,32 +167,51 @@ pub struct EZLogConfig {
 }
 
 impl EZLogConfig {
-    pub(crate) fn file_name(&self) -> crate::Result<String> {
+    /// The name of the current log file, `name.suffix`
+    pub fn file_name(&self) -> crate::Result<String> {
         let str = format!("{}.{}", self.name, self.file_suffix);
         Ok(str)
     }
 
+    /// The rotated file name `name_YYYY_MM_DD_HH_MM.sequence.suffix`
     pub(crate) fn file_name_with_date(
         &self,
         time: OffsetDateTime,
-        count: i32,
+        sequence: u64,
     ) -> crate::Result<String> {
-        let format = time::format_description::parse(DATE_FORMAT).map_err(|e| {
+        let format_str = format!("{}_{}", DATE_FORMAT, TIME_FORMAT);
+        let format = time::format_description::parse(&format_str).map_err(|e| {
             crate::errors::LogError::Parse(format!(
                 "Unable to create a formatter; this is a bug in EZLogConfig#file_name_with_date: {}",
                 e
             ))
         })?;
-        let date = time.format(&format).map_err(|_| {
-            crate::errors::LogError::Parse(
-                "Unable to format date; this is a bug in EZLogConfig#file_name_with_date"
-                    .to_string(),
-            )
-        })?;
-        let new_name = format!("{}_{}.{}.{}", self.name, date, count, self.file_suffix);
+        let date = time
+            .to_offset(self.time_offset)
+            .format(&format)
+            .map_err(|_| {
+                crate::errors::LogError::Parse(
+                    "Unable to format date; this is a bug in EZLogConfig#file_name_with_date"
+                        .to_string(),
+                )
+            })?;
+        let new_name = format!("{}_{}.{}.{}", self.name, date, sequence, self.file_suffix);
         Ok(new_name)
     }
 
+    /// The sequence of the next rotated file, greater than all the rotated files
+    ///
+    /// the last sequence is kept in the manifest, so it does not restart after the files are trimmed
+    pub(crate) fn next_sequence(&self) -> u64 {
+        match Manifest::load(self) {
+            Ok(manifest) => manifest.sequence() + 1,
+            Err(e) => {
+                event!(!Event::RotateFileError, "load manifest"; &e);
+                1
+            }
+        }
+    }
+
     pub fn is_valid(&self) -> bool {
         !self.dir_path.as_os_str().is_empty()
             && !self.name.is_empty()
@@ -148,9 +224,23 @@ impl EZLogConfig {
         Ok((path, mmap))
     }
 
+    /// Create or open the current log file, its length is [Self::max_size] if it is new
     pub(crate) fn create_or_open_log_file(&self) -> crate::Result<(File, PathBuf)> {
-        let file_name = self.file_name()?;
         let max_size = cmp::max(self.max_size, MIN_LOG_SIZE);
+        let (file, path) = self.open_log_file()?;
+        let mut len = file.metadata()?.len();
+        len = if len != max_size && len != 0 {
+            len
+        } else {
+            max_size
+        };
+        file.set_len(len)?;
+        Ok((file, path))
+    }
+
+    /// Create or open the current log file without changing its length
+    pub(crate) fn open_log_file(&self) -> crate::Result<(File, PathBuf)> {
+        let file_name = self.file_name()?;
         let path = Path::new(&self.dir_path).join(file_name);
 
         if let Some(p) = &path.parent() {
@@ -164,28 +254,12 @@ impl EZLogConfig {
             .create(true)
             .truncate(false)
             .open(&path)?;
-        let mut len = file.metadata()?.len();
-        len = if len != max_size && len != 0 {
-            len
-        } else {
-            max_size
-        };
-        file.set_len(len)?;
         Ok((file, path))
     }
 
-    pub(crate) fn is_file_out_of_date(&self, file_name: &str) -> crate::Result<bool> {
-        if file_name == format!("{}.{}", &self.name, &self.file_suffix) {
-            // ignore logging file
-            return Ok(false);
-        }
-        let log_date = self.read_file_name_as_date(file_name)?;
-        let now = OffsetDateTime::now_utc();
-        Ok(self.is_out_of_date(log_date, now))
-    }
-
     pub(crate) fn read_file_name_as_date(&self, file_name: &str) -> crate::Result<OffsetDateTime> {
         const SAMPLE: &str = "2022_02_22";
+        const TIME_SAMPLE: &str = "22_22";
         if file_name == format!("{}.{}", &self.name, &self.file_suffix) {
             return Err(LogError::Illegal("The file is logging file".to_string()));
         }
@@ -206,57 +280,130 @@ impl EZLogConfig {
             date_str,
             "this is a bug in EZLogConfig#read_file_name_as_date:",
         )?;
-        Ok(log_date.midnight().assume_utc())
+        // the legacy name has the date only
+        let time = file_name[self.name.len() + 1 + SAMPLE.len()..]
+            .strip_prefix('_')
+            .and_then(|rest| rest.get(..TIME_SAMPLE.len()))
+            .and_then(|time_str| parse_time_from_str(time_str).ok())
+            .unwrap_or(Time::MIDNIGHT);
+        Ok(log_date.with_time(time).assume_offset(self.time_offset))
     }
 
-    fn is_out_of_date(&self, target: OffsetDateTime, now: OffsetDateTime) -> bool {
+    /// The sequence of the rotated file, `None` if the name has no sequence
+    pub(crate) fn read_file_name_sequence(&self, file_name: &str) -> Option<u64> {
+        let rest = file_name
+            .strip_prefix(&format!("{}_", &self.name))?
+            .strip_suffix(&format!(".{}", &self.file_suffix))?;
+        let (_, sequence) = rest.rsplit_once('.')?;
+        sequence.parse().ok()
+    }
+
+    pub(crate) fn is_out_of_date(&self, target: OffsetDateTime, now: OffsetDateTime) -> bool {
         target + self.trim_duration < now
     }
 
-    pub(crate) fn is_file_same_date(&self, file_name: &str, date: OffsetDateTime) -> bool {
-        if file_name == format!("{}.{}", &self.name, &self.file_suffix) {
-            // ignore logging file
-            return false;
-        }
+    /// The manifest path of the logger, `name.manifest` in the log dir
+    pub(crate) fn manifest_path(&self) -> PathBuf {
+        self.dir_path
+            .join(format!("{}.{}", self.name, MANIFEST_SUFFIX))
+    }
+
+    /// The config to write the records fetched in a time range, see [crate::request_logs_in_range]
+    ///
+    /// the records are encoded as the logger does, except the chain, the ring buffer and the redaction
+    /// which are already applied.
+    #[cfg(feature = "decode")]
+    pub(crate) fn fetch_config(&self, dir_path: PathBuf, name: String, max_size: u64) -> Self {
+        let mut config = self.clone();
+        config.dir_path = dir_path;
+        config.name = name;
+        config.max_size = cmp::max(max_size, self.max_size);
+        config.max_total_size = None;
+        config.chain_key = None;
+        config.signing_key = None;
+        config.redact_rules = Vec::new();
+        config.ring_buffer = false;
+        config
+    }
+
+    /// The rotated log files in manifest, the oldest first
+    pub fn manifest_entries(&self) -> crate::Result<Vec<ManifestEntry>> {
+        Manifest::load(self).map(|manifest| manifest.entries().to_vec())
+    }
+
+    /// Rebuild the manifest from the rotated log files in the log dir
+    ///
+    /// the manifest is trusted when the files are queried, repair it after the files are removed or
+    /// added by others. it is repaired by trim too.
+    pub fn repair_manifest(&self) -> crate::Result<Vec<ManifestEntry>> {
+        Manifest::repair(self).map(|manifest| manifest.entries().to_vec())
+    }
 
-        self.read_file_name_as_date(file_name)
-            .map(|log_date| log_date.date() == date.date())
-            .unwrap_or(false)
+    /// The rotated log files and their sizes, the oldest first
+    pub(crate) fn rotated_log_files(&self) -> crate::Result<Vec<(PathBuf, u64)>> {
+        Ok(self
+            .manifest_entries()?
+            .into_iter()
+            .map(|entry| (self.dir_path.join(entry.file_name()), entry.size()))
+            .collect())
     }
 
+    /// Query the rotated log files created on the date of `date`, in the local offset of the config
     pub fn query_log_files_for_date(&self, date: OffsetDateTime) -> Vec<PathBuf> {
-        let mut logs = Vec::new();
-        match fs::read_dir(&self.dir_path) {
-            Ok(dir) => {
-                for file in dir {
-                    match file {
-                        Ok(file) => {
-                            if let Some(name) = file.file_name().to_str() {
-                                if self.is_file_same_date(name, date) {
-                                    logs.push(file.path());
-                                }
-                            };
-                        }
-                        Err(e) => {
-                            event!(!Event::RequestLogError, "get dir entry in dir"; &e.into());
-                        }
-                    }
-                }
+        match self.manifest_entries() {
+            Ok(entries) => entries
+                .into_iter()
+                .filter(|entry| entry.start().to_offset(self.time_offset).date() == date.date())
+                .map(|entry| self.dir_path.join(entry.file_name()))
+                .filter(|path| path.is_file())
+                .collect(),
+            Err(e) => {
+                event!(!Event::RequestLogError, "load manifest"; &e);
+                Vec::new()
             }
-            Err(e) => event!(!Event::RequestLogError, "read dir"; &e.into()),
         }
-        logs
+    }
+
+    /// Query the rotated log files overlapping `[start, end)`, by the time in header and the rotation time
+    pub fn query_log_files_in_range(
+        &self,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+    ) -> Vec<PathBuf> {
+        match self.manifest_entries() {
+            Ok(entries) => entries
+                .into_iter()
+                .filter(|entry| entry.start() < end && entry.end() >= start)
+                .map(|entry| self.dir_path.join(entry.file_name()))
+                .filter(|path| path.is_file())
+                .collect(),
+            Err(e) => {
+                event!(!Event::RequestLogError, "load manifest"; &e);
+                Vec::new()
+            }
+        }
+    }
+
+    pub(crate) fn time_offset(&self) -> UtcOffset {
+        self.time_offset
     }
 
     pub(crate) fn rotate_time(&self, time: &OffsetDateTime) -> OffsetDateTime {
-        *time + self.rotate_duration
+        if self.rotate_aligned && self.rotate_duration.is_positive() {
+            aligned_rotate_time(*time, self.rotate_duration, self.time_offset)
+        } else {
+            *time + self.rotate_duration
+        }
     }
 
     pub(crate) fn cipher_hash(&self) -> u32 {
-        let mut hasher = DefaultHasher::new();
-        self.cipher.hash(&mut hasher);
-        self.cipher_key.hash(&mut hasher);
-        hasher.finish() as u32
+        crate::key_fingerprint(
+            self.cipher,
+            self.cipher_key
+                .as_ref()
+                .map(|k| k.expose())
+                .unwrap_or_default(),
+        )
     }
 
     pub fn check_valid(&self) -> crate::Result<()> {
@@ -266,6 +413,74 @@ impl EZLogConfig {
                 self
             )));
         }
+        if let CipherKind::CUSTOM(id) = self.cipher {
+            if !crate::is_cryptor_registered(id) {
+                return Err(LogError::Illegal(format!(
+                    "cipher {} is not registered",
+                    self.cipher
+                )));
+            }
+        }
+        if self.cipher_passphrase.is_some() {
+            if Into::<u8>::into(self.version) < Version::V3.into() {
+                return Err(LogError::Illegal(format!(
+                    "cipher passphrase requires version {:?}, but current is {:?}",
+                    Version::V3,
+                    self.version
+                )));
+            }
+            if matches!(self.cipher, CipherKind::NONE | CipherKind::UNKNOWN) {
+                return Err(LogError::Illegal(
+                    "cipher passphrase is set without a cipher".to_string(),
+                ));
+            }
+        }
+        if self.chain_key.is_some() && Into::<u8>::into(self.version) < Version::V3.into() {
+            return Err(LogError::Illegal(format!(
+                "chain key requires version {:?}, but current is {:?}",
+                Version::V3,
+                self.version
+            )));
+        }
+        if self.time_offset != UtcOffset::UTC && Into::<u8>::into(self.version) < Version::V3.into()
+        {
+            return Err(LogError::Illegal(format!(
+                "utc offset requires version {:?}, but current is {:?}",
+                Version::V3,
+                self.version
+            )));
+        }
+        if self.ring_buffer {
+            if Into::<u8>::into(self.version) < Version::V3.into() {
+                return Err(LogError::Illegal(format!(
+                    "ring buffer requires version {:?}, but current is {:?}",
+                    Version::V3,
+                    self.version
+                )));
+            }
+            if self.chain_key.is_some() {
+                return Err(LogError::Illegal(
+                    "ring buffer overwrites the oldest records, it can not be chained".to_string(),
+                ));
+            }
+            if self.appender != AppenderKind::Mmap {
+                return Err(LogError::Illegal(
+                    "ring buffer requires the mmap appender".to_string(),
+                ));
+            }
+        }
+        #[cfg(feature = "sign")]
+        if let Some(signing_key) = &self.signing_key {
+            crate::public_key(signing_key.expose())?;
+        }
+        if let CompressKind::CUSTOM(id) = self.compress {
+            if !crate::is_compress_registered(id) {
+                return Err(LogError::Illegal(format!(
+                    "compress {:?} is not registered",
+                    self.compress
+                )));
+            }
+        }
         Ok(())
     }
 
@@ -277,6 +492,10 @@ impl EZLogConfig {
         self.max_size
     }
 
+    pub(crate) fn max_total_size(&self) -> Option<u64> {
+        self.max_total_size
+    }
+
     pub(crate) fn compress_kind(&self) -> CompressKind {
         self.compress
     }
@@ -287,12 +506,85 @@ impl EZLogConfig {
     pub(crate) fn cipher_kind(&self) -> CipherKind {
         self.cipher
     }
-    pub(crate) fn cipher_key(&self) -> Option<Vec<u8>> {
-        self.cipher_key.clone()
+    pub(crate) fn cipher_key(&self) -> Option<&[u8]> {
+        self.cipher_key.as_ref().map(|k| k.expose())
+    }
+
+    pub(crate) fn cipher_nonce(&self) -> Option<&[u8]> {
+        self.cipher_nonce.as_ref().map(|n| n.expose())
+    }
+
+    /// Replace the cipher key and nonce, the passphrase is dropped
+    pub(crate) fn replace_cipher_key(&mut self, key: SecretBytes, nonce: SecretBytes) {
+        self.cipher_key = Some(key);
+        self.cipher_nonce = Some(nonce);
+        self.cipher_passphrase = None;
+    }
+
+    /// The kdf params to record in header, only if the key is derived from passphrase
+    pub(crate) fn kdf(&self) -> Option<KdfParams> {
+        self.cipher_passphrase.as_ref().and(self.kdf.clone())
+    }
+
+    pub(crate) fn chain_key(&self) -> Option<&[u8]> {
+        self.chain_key.as_ref().map(|k| k.expose())
     }
 
-    pub(crate) fn cipher_nonce(&self) -> Option<Vec<u8>> {
-        self.cipher_nonce.clone()
+    pub(crate) fn redact_rules(&self) -> &[RedactRule] {
+        &self.redact_rules
+    }
+
+    pub(crate) fn is_ring_buffer(&self) -> bool {
+        self.ring_buffer
+    }
+
+    #[cfg(feature = "sign")]
+    pub(crate) fn signing_key(&self) -> Option<&[u8]> {
+        self.signing_key.as_ref().map(|k| k.expose())
+    }
+
+    /// Derive the cipher key from the passphrase
+    ///
+    /// if the kdf salt is empty, reuse the salt in current log file header when the params
+    /// are the same, otherwise generate a random one.
+    #[cfg(feature = "kdf")]
+    pub fn derive_cipher_key(&mut self) -> crate::Result<()> {
+        let passphrase = match &self.cipher_passphrase {
+            Some(passphrase) => passphrase,
+            None => return Ok(()),
+        };
+        let mut kdf = self.kdf.clone().unwrap_or_default();
+        if kdf.salt.is_empty() {
+            kdf.salt = match self.current_header().and_then(|h| h.kdf) {
+                Some(current) if current.is_same_cost(&kdf) && !current.salt.is_empty() => {
+                    current.salt
+                }
+                _ => crate::kdf::random_salt()?,
+            };
+        }
+        self.cipher_key = Some(
+            kdf.derive_key(passphrase.expose(), self.cipher.key_len())?
+                .into(),
+        );
+        self.kdf = Some(kdf);
+        Ok(())
+    }
+
+    /// Read the header of the logging file, if exists
+    #[cfg(feature = "kdf")]
+    fn current_header(&self) -> Option<Header> {
+        use std::io::Read;
+
+        let path = Path::new(&self.dir_path).join(self.file_name().ok()?);
+        let mut buf = Vec::new();
+        File::open(path)
+            .ok()?
+            .take(Header::max_length() as u64)
+            .read_to_end(&mut buf)
+            .ok()?;
+        Header::decode(&mut buf.as_slice())
+            .ok()
+            .filter(|h| !h.is_none())
     }
 
     pub(crate) fn extra(&self) -> Option<String> {
@@ -311,6 +603,14 @@ impl EZLogConfig {
         self.version
     }
 
+    pub(crate) fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    pub(crate) fn appender_kind(&self) -> AppenderKind {
+        self.appender
+    }
+
     pub(crate) fn dir_path(&self) -> &PathBuf {
         &self.dir_path
     }
@@ -331,6 +631,12 @@ impl Hash for EZLogConfig {
         self.cipher.hash(state);
         self.cipher_key.hash(state);
         self.cipher_nonce.hash(state);
+        self.cipher_passphrase.hash(state);
+        self.kdf.hash(state);
+        self.chain_key.hash(state);
+        self.signing_key.hash(state);
+        self.redact_rules.hash(state);
+        self.ring_buffer.hash(state);
         self.extra.hash(state)
     }
 }
@@ -352,12 +658,23 @@ impl EZLogConfigBuilder {
                 file_suffix: DEFAULT_LOG_FILE_SUFFIX.to_string(),
                 trim_duration: Duration::days(7),
                 max_size: DEFAULT_MAX_LOG_SIZE,
+                max_total_size: None,
                 compress: CompressKind::NONE,
                 compress_level: CompressLevel::Default,
                 cipher: CipherKind::NONE,
                 cipher_key: None,
                 cipher_nonce: None,
+                cipher_passphrase: None,
+                kdf: None,
+                chain_key: None,
+                signing_key: None,
+                redact_rules: Vec::new(),
+                ring_buffer: false,
                 rotate_duration: Duration::days(1),
+                rotate_aligned: false,
+                time_offset: UtcOffset::UTC,
+                durability: Durability::None,
+                appender: AppenderKind::Mmap,
                 extra: None,
             },
         }
@@ -405,6 +722,13 @@ impl EZLogConfigBuilder {
         self
     }
 
+    /// Remove the oldest rotated files when the total size of the log files is greater than this
+    #[inline]
+    pub fn max_total_size(mut self, max_total_size: u64) -> Self {
+        self.config.max_total_size = Some(max_total_size);
+        self
+    }
+
     #[inline]
     pub fn compress(mut self, compress: CompressKind) -> Self {
         self.config.compress = compress;
@@ -424,14 +748,76 @@ impl EZLogConfigBuilder {
     }
 
     #[inline]
-    pub fn cipher_key(mut self, cipher_key: Vec<u8>) -> Self {
-        self.config.cipher_key = Some(cipher_key);
+    pub fn cipher_key(mut self, cipher_key: impl Into<SecretBytes>) -> Self {
+        self.config.cipher_key = Some(cipher_key.into());
+        self
+    }
+
+    #[inline]
+    pub fn cipher_nonce(mut self, cipher_nonce: impl Into<SecretBytes>) -> Self {
+        self.config.cipher_nonce = Some(cipher_nonce.into());
+        self
+    }
+
+    /// Derive the cipher key from passphrase, it overrides the [EZLogConfigBuilder::cipher_key]
+    ///
+    /// the log version will be set to [Version::V3] to record the kdf params
+    #[cfg(feature = "kdf")]
+    #[inline]
+    pub fn cipher_passphrase(mut self, passphrase: impl Into<SecretBytes>) -> Self {
+        self.config.cipher_passphrase = Some(passphrase.into());
+        if self.config.kdf.is_none() {
+            self.config.kdf = Some(KdfParams::default());
+        }
+        self.config.version = Version::V3;
+        self
+    }
+
+    /// Key derivation params used by [EZLogConfigBuilder::cipher_passphrase]
+    #[inline]
+    pub fn kdf(mut self, kdf: KdfParams) -> Self {
+        self.config.kdf = Some(kdf);
+        self
+    }
+
+    /// Chain every record with a MAC of this key, to detect removed or altered records
+    ///
+    /// the log version will be set to [Version::V3] to record the previous file's last MAC
+    #[inline]
+    pub fn chain_key(mut self, chain_key: impl Into<SecretBytes>) -> Self {
+        self.config.chain_key = Some(chain_key.into());
+        self.config.version = Version::V3;
         self
     }
 
+    /// Seal every rotated file with an Ed25519 signature of the 32 bytes key seed
+    ///
+    /// the files are sealed on the `ezlog_rotate` thread, the files left unsealed when the process
+    /// exits are sealed when the logger is created again.
+    #[cfg(feature = "sign")]
     #[inline]
-    pub fn cipher_nonce(mut self, cipher_nonce: Vec<u8>) -> Self {
-        self.config.cipher_nonce = Some(cipher_nonce);
+    pub fn signing_key(mut self, signing_key: impl Into<SecretBytes>) -> Self {
+        self.config.signing_key = Some(signing_key.into());
+        self
+    }
+
+    /// Add a redaction rule, the rules are applied in the order they are added
+    #[inline]
+    pub fn redact(mut self, rule: RedactRule) -> Self {
+        self.config.redact_rules.push(rule);
+        self
+    }
+
+    /// Keep a single log file of `max_size`, the oldest records are overwritten when it is full
+    ///
+    /// the log file never rotates by time, the log version will be set to [Version::V3] to record
+    /// the ring offsets
+    #[inline]
+    pub fn ring_buffer(mut self, ring_buffer: bool) -> Self {
+        self.config.ring_buffer = ring_buffer;
+        if ring_buffer {
+            self.config.version = Version::V3;
+        }
         self
     }
 
@@ -440,6 +826,9 @@ impl EZLogConfigBuilder {
         self.config.version = header.version;
         self.config.compress = header.compress;
         self.config.cipher = header.cipher;
+        if header.kdf.is_some() {
+            self.config.kdf = header.kdf.clone();
+        }
         self
     }
 
@@ -449,6 +838,54 @@ impl EZLogConfigBuilder {
         self
     }
 
+    /// Rotate at the wall clock boundaries, like the top of the hour or midnight
+    ///
+    /// the rotation time is the next multiple of `rotate_duration` since midnight in the
+    /// [Self::utc_offset], and the midnight if the duration does not divide a day.
+    #[inline]
+    pub fn rotate_aligned(mut self, aligned: bool) -> Self {
+        self.config.rotate_aligned = aligned;
+        self
+    }
+
+    /// Set the offset of the local time, default is UTC
+    ///
+    /// the dates in the rotated file names, the aligned rotation and the record time are in the
+    /// offset. an offset other than UTC requires [Version::V3] to record it in the header, so the
+    /// decoder shows the local time.
+    #[inline]
+    pub fn utc_offset(mut self, offset: UtcOffset) -> Self {
+        self.config.time_offset = offset;
+        self
+    }
+
+    /// Use the current local offset, see [Self::utc_offset]
+    ///
+    /// the offset is read once, it is UTC if the local offset can not be determined, like in a
+    /// multi-threaded process on some unix platforms. the daylight saving change is not followed.
+    #[inline]
+    pub fn local_time(self) -> Self {
+        let offset = UtcOffset::current_local_offset().unwrap_or_else(|e| {
+            event!(Event::Init, "local offset is indeterminate, use UTC: {}", e);
+            UtcOffset::UTC
+        });
+        self.utc_offset(offset)
+    }
+
+    /// Set when the records are synced to the file, see [Durability]
+    #[inline]
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.config.durability = durability;
+        self
+    }
+
+    /// Set how the log file is written, see [AppenderKind]
+    #[inline]
+    pub fn appender(mut self, appender: AppenderKind) -> Self {
+        self.config.appender = appender;
+        self
+    }
+
     #[inline]
     pub fn extra(mut self, extra: impl AsRef<str>) -> Self {
         self.config.extra = Some(extra.as_ref().to_owned());
@@ -475,6 +912,43 @@ pub(crate) fn parse_date_from_str(date_str: &str, case: &str) -> crate::Result<D
     Ok(date)
 }
 
+pub(crate) fn parse_time_from_str(time_str: &str) -> crate::Result<Time> {
+    let format = format_description::parse(TIME_FORMAT)
+        .map_err(|e| crate::errors::LogError::Parse(format!("{} {}", time_str, e)))?;
+    Time::parse(time_str, &format)
+        .map_err(|e| crate::errors::LogError::Parse(format!("{} {}", time_str, e)))
+}
+
+/// When the records are synced to the file
+///
+/// the records in the mmap survive a process crash without a sync, the sync protects them from
+/// a system crash or a power loss at the cost of the disk writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// Rely on the page cache, the records are synced at flush, rotation and drop
+    #[default]
+    None,
+    /// Sync the records at most the duration after they are written, on the worker thread
+    Interval(Duration),
+    /// Sync right after a record at the level or more severe
+    OnLevel(Level),
+    /// Sync after every record
+    EveryRecord,
+}
+
+/// How the log file is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AppenderKind {
+    /// Map the file of `max_size` to memory, it falls back to a buffer in memory if mmap fails
+    #[default]
+    Mmap,
+    /// Write the records to the end of the file by positioned writes, without preallocation
+    ///
+    /// for the file systems where the mmap of a shared file behaves badly, like the network
+    /// mounts or FUSE. if `sync`, the file data is synced by `fdatasync` at flush.
+    File { sync: bool },
+}
+
 /// Log level, used to filter log records
 #[repr(usize)]
 #[derive(Copy, Eq, Debug)]
@@ -629,6 +1103,25 @@ impl fmt::Display for Level {
     }
 }
 
+/// The next boundary after `time`, at the multiples of `duration` since midnight in `offset`
+fn aligned_rotate_time(
+    time: OffsetDateTime,
+    duration: Duration,
+    offset: UtcOffset,
+) -> OffsetDateTime {
+    let local = time.to_offset(offset);
+    let midnight = local.replace_time(Time::MIDNIGHT);
+    let next_midnight = midnight + Duration::days(1);
+    if duration >= Duration::days(1) {
+        return (midnight + duration).to_offset(time.offset());
+    }
+    // both are less than a day in nanoseconds, far from the i64 limit
+    let elapsed = (local - midnight).whole_nanoseconds() as i64;
+    let step = duration.whole_nanoseconds() as i64;
+    let next = midnight + Duration::nanoseconds((elapsed / step + 1) * step);
+    cmp::min(next, next_midnight).to_offset(time.offset())
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -639,9 +1132,13 @@ mod tests {
 
     use test_compat::test_path;
     use time::{
-        macros::datetime,
+        macros::{
+            datetime,
+            offset,
+        },
         Duration,
         OffsetDateTime,
+        UtcOffset,
     };
 
     use crate::{
@@ -662,6 +1159,31 @@ mod tests {
             .file_suffix("log")
             .build();
         assert!(config.is_valid());
+
+        let config = EZLogConfigBuilder::default()
+            .dir_path(test_path().join("ezlog"))
+            .version(crate::Version::V3)
+            .ring_buffer(true)
+            .appender(super::AppenderKind::File { sync: false })
+            .build();
+        assert!(config.check_valid().is_err());
+
+        let builder = EZLogConfigBuilder::default()
+            .dir_path(test_path().join("ezlog"))
+            .name("test")
+            .utc_offset(offset!(+8));
+        assert!(builder.clone().build().check_valid().is_err());
+        assert!(builder
+            .clone()
+            .version(crate::Version::V2)
+            .build()
+            .check_valid()
+            .is_err());
+        assert!(builder
+            .version(crate::Version::V3)
+            .build()
+            .check_valid()
+            .is_ok());
     }
 
     #[test]
@@ -675,6 +1197,86 @@ mod tests {
         assert_eq!(rotate_time, now + Duration::days(1));
     }
 
+    #[test]
+    fn test_config_rotate_aligned() {
+        let time = datetime!(2023-10-14 10:37:12 +8).to_offset(UtcOffset::UTC);
+        let rotate_time = |duration: Duration, offset: UtcOffset| {
+            EZLogConfigBuilder::new()
+                .rotate_duration(duration)
+                .rotate_aligned(true)
+                .utc_offset(offset)
+                .build()
+                .rotate_time(&time)
+        };
+        let offset = offset!(+8);
+
+        assert_eq!(
+            rotate_time(Duration::hours(1), offset),
+            datetime!(2023-10-14 11:00 +8)
+        );
+        assert_eq!(
+            rotate_time(Duration::minutes(15), offset),
+            datetime!(2023-10-14 10:45 +8)
+        );
+        assert_eq!(
+            rotate_time(Duration::days(1), offset),
+            datetime!(2023-10-15 00:00 +8)
+        );
+        // 7 hours does not divide a day, the last file of the day ends at midnight
+        assert_eq!(
+            rotate_time(Duration::hours(7), offset),
+            datetime!(2023-10-14 14:00 +8)
+        );
+        assert_eq!(
+            rotate_time(Duration::days(1), UtcOffset::UTC),
+            datetime!(2023-10-15 00:00 UTC)
+        );
+        assert_eq!(
+            rotate_time(Duration::days(1), offset).offset(),
+            UtcOffset::UTC
+        );
+        // sub-millisecond durations do not overflow
+        assert_eq!(
+            rotate_time(Duration::microseconds(1), offset),
+            time + Duration::microseconds(1)
+        );
+
+        let late = datetime!(2023-10-14 22:30 +8).to_offset(UtcOffset::UTC);
+        let config = EZLogConfigBuilder::new()
+            .rotate_duration(Duration::hours(7))
+            .rotate_aligned(true)
+            .utc_offset(offset)
+            .build();
+        assert_eq!(config.rotate_time(&late), datetime!(2023-10-15 00:00 +8));
+        assert_eq!(config.rotate_time(&late).offset(), UtcOffset::UTC);
+    }
+
+    #[test]
+    fn test_config_utc_offset_file_name() {
+        let config = EZLogConfigBuilder::new()
+            .name("offset")
+            .utc_offset(offset!(+8))
+            .build();
+        // 20:30 UTC is the next morning in UTC+8
+        let time = datetime!(2023-10-14 20:30 UTC);
+        let name = config.file_name_with_date(time, 1).unwrap();
+        assert_eq!(name, "offset_2023_10_15_04_30.1.mmap");
+        assert_eq!(config.read_file_name_as_date(&name).unwrap(), time);
+    }
+
+    #[test]
+    fn test_config_debug_redact_secrets() {
+        let config = EZLogConfigBuilder::new()
+            .cipher(CipherKind::AES256GCMSIV)
+            .cipher_key(b"an example very very secret key.".to_vec())
+            .cipher_nonce(b"unique nonce".to_vec())
+            .build();
+        let debug = format!("{:?}", config);
+        assert!(!debug.contains("secret key"));
+        assert!(!debug.contains("unique nonce"));
+        assert!(!debug.contains(&format!("{:?}", b"an example very very secret key.")));
+    }
+
     #[test]
     fn test_config_cipher_hash() {
         let config_builder = EZLogConfigBuilder::default();
@@ -743,6 +1345,34 @@ mod tests {
         assert!(config
             .read_file_name_as_date("test_2019_06_13.123.mmap")
             .is_ok());
+        assert_eq!(
+            config
+                .read_file_name_as_date("test_2019_06_13_08_30.7.mmap")
+                .unwrap(),
+            datetime!(2019-06-13 8:30 UTC)
+        );
+        assert_eq!(
+            config
+                .read_file_name_as_date("test_2019_06_13.1.mmap")
+                .unwrap(),
+            datetime!(2019-06-13 0:00 UTC)
+        );
+
+        let name = config
+            .file_name_with_date(datetime!(2019-06-13 23:05:59 UTC), 42)
+            .unwrap();
+        assert_eq!(name, "test_2019_06_13_23_05.42.mmap");
+        assert_eq!(
+            config.read_file_name_as_date(&name).unwrap(),
+            datetime!(2019-06-13 23:05 UTC)
+        );
+        assert_eq!(config.read_file_name_sequence(&name), Some(42));
+        assert_eq!(
+            config.read_file_name_sequence("test_2019_06_13.3.mmap"),
+            Some(3)
+        );
+        assert_eq!(config.read_file_name_sequence("test_2019_06_13.mmap"), None);
+        assert_eq!(config.read_file_name_sequence("test.mmap"), None);
     }
 
     #[test]
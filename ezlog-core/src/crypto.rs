@@ -20,6 +20,10 @@ pub enum CipherKind {
     AES256GCM,
     AES128GCMSIV,
     AES256GCMSIV,
+    CHACHA20POLY1305,
+    XCHACHA20POLY1305,
+    /// user registered cipher, see [crate::register_cryptor]
+    CUSTOM(u8),
     NONE,
     UNKNOWN,
 }
@@ -33,6 +37,9 @@ impl From<u8> for CipherKind {
             0x02 => CipherKind::AES256GCM,
             0x03 => CipherKind::AES128GCMSIV,
             0x04 => CipherKind::AES256GCMSIV,
+            0x05 => CipherKind::CHACHA20POLY1305,
+            0x06 => CipherKind::XCHACHA20POLY1305,
+            id if crate::registry::is_custom_id(id) => CipherKind::CUSTOM(id),
             _ => CipherKind::UNKNOWN,
         }
     }
@@ -47,6 +54,9 @@ impl From<CipherKind> for u8 {
             CipherKind::AES256GCM => 0x02,
             CipherKind::AES128GCMSIV => 0x03,
             CipherKind::AES256GCMSIV => 0x04,
+            CipherKind::CHACHA20POLY1305 => 0x05,
+            CipherKind::XCHACHA20POLY1305 => 0x06,
+            CipherKind::CUSTOM(id) => id,
             CipherKind::UNKNOWN => 0xff,
         }
     }
@@ -60,6 +70,9 @@ impl core::fmt::Display for CipherKind {
             CipherKind::AES256GCM => write!(f, "AEAD_AES_256_GCM"),
             CipherKind::AES128GCMSIV => write!(f, "AEAD_AES_128_GCM_SIV"),
             CipherKind::AES256GCMSIV => write!(f, "AEAD_AES_128_GCM_SIV"),
+            CipherKind::CHACHA20POLY1305 => write!(f, "AEAD_CHACHA20_POLY1305"),
+            CipherKind::XCHACHA20POLY1305 => write!(f, "AEAD_XCHACHA20_POLY1305"),
+            CipherKind::CUSTOM(id) => write!(f, "CUSTOM_{}", id),
             CipherKind::NONE => write!(f, "NONE"),
             _ => write!(f, "UNKNOWN"),
         }
@@ -76,14 +89,44 @@ impl std::str::FromStr for CipherKind {
             "AEAD_AES_256_GCM" => Ok(CipherKind::AES256GCM),
             "AEAD_AES_128_GCM_SIV" => Ok(CipherKind::AES128GCMSIV),
             "AEAD_AES_256_GCM_SIV" => Ok(CipherKind::AES256GCMSIV),
+            "AEAD_CHACHA20_POLY1305" => Ok(CipherKind::CHACHA20POLY1305),
+            "AEAD_XCHACHA20_POLY1305" => Ok(CipherKind::XCHACHA20POLY1305),
             "NONE" => Ok(CipherKind::NONE),
-            _ => Err(crate::errors::LogError::Parse(
-                "unknown cipher kind".to_string(),
-            )),
+            _ => s
+                .strip_prefix("CUSTOM_")
+                .and_then(|id| id.parse::<u8>().ok())
+                .filter(|id| crate::registry::is_custom_id(*id))
+                .map(CipherKind::CUSTOM)
+                .ok_or_else(|| crate::errors::LogError::Parse("unknown cipher kind".to_string())),
         }
     }
 }
 
+#[allow(deprecated)]
+impl CipherKind {
+    /// The key length in bytes, used to derive the key from passphrase
+    pub fn key_len(&self) -> usize {
+        match self {
+            CipherKind::AES128GCM | CipherKind::AES128GCMSIV => 16,
+            _ => 32,
+        }
+    }
+}
+
+/// A stable fingerprint of the cipher and key
+///
+/// the first 4 bytes of `SHA-256(cipher || key)` in big-endian, it is written to the
+/// log file header to pick the right key when decoding.
+pub fn key_fingerprint(cipher: CipherKind, key: &[u8]) -> u32 {
+    use sha2::Digest;
+
+    let mut hasher = sha2::Sha256::new();
+    hasher.update([u8::from(cipher)]);
+    hasher.update(key);
+    let digest = hasher.finalize();
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]])
+}
+
 pub struct Aes256GcmSiv {
     // 96-bits; unique per message
     nonce: Vec<u8>,
@@ -284,3 +327,174 @@ impl Decryptor for Aes128Gcm {
             .map_err(|e| LogError::Crypto(format!("{e:?}")))
     }
 }
+
+pub struct ChaCha20Poly1305 {
+    // 96-bits; unique per message
+    nonce: Vec<u8>,
+    // chacha20poly1305
+    cipher: chacha20poly1305::ChaCha20Poly1305,
+}
+
+impl ChaCha20Poly1305 {
+    pub fn new(key: &[u8], nonce: &[u8]) -> crate::Result<Self> {
+        if nonce.len() != 12 {
+            return Err(LogError::Illegal(format!(
+                "nonce must be 12 bytes, but current is {}",
+                nonce.len()
+            )));
+        }
+        match chacha20poly1305::ChaCha20Poly1305::new_from_slice(key) {
+            Ok(cipher) => Ok(ChaCha20Poly1305 {
+                nonce: nonce.to_owned(),
+                cipher,
+            }),
+            Err(e) => Err(LogError::Illegal(format!("key length invalid {}", e))),
+        }
+    }
+
+    /// ChaCha20-Poly1305 is not nonce-misuse-resistant, so the record position
+    /// must take part in the nonce. The generator only covers the first 12 bytes
+    /// with the timestamp, so pad the nonce to 16 bytes and fold the position
+    /// bytes back into the tail.
+    fn record_nonce(&self, op: &dyn Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+        let mut padded = self.nonce.clone();
+        padded.resize(16, 0);
+        let mixed = op(&padded);
+        let (head, tail) = mixed.split_at(12);
+        let mut nonce = head.to_vec();
+        for (i, byte) in tail.iter().enumerate() {
+            nonce[8 + i] ^= byte;
+        }
+        nonce
+    }
+}
+
+impl Encryptor for ChaCha20Poly1305 {
+    fn encrypt(&self, data: &[u8], op: Box<dyn Fn(&[u8]) -> Vec<u8>>) -> Result<Vec<u8>, LogError> {
+        let new_nonce = self.record_nonce(op.as_ref());
+        let nonce = chacha20poly1305::Nonce::from_slice(&new_nonce);
+        self.cipher
+            .encrypt(nonce, data)
+            .map_err(|e| LogError::Crypto(format!("{e:?}")))
+    }
+}
+
+impl Decryptor for ChaCha20Poly1305 {
+    fn decrypt(&self, data: &[u8], op: Box<dyn Fn(&[u8]) -> Vec<u8>>) -> Result<Vec<u8>, LogError> {
+        let new_nonce = self.record_nonce(op.as_ref());
+        let nonce = chacha20poly1305::Nonce::from_slice(&new_nonce);
+        self.cipher
+            .decrypt(nonce, data)
+            .map_err(|e| LogError::Crypto(format!("{e:?}")))
+    }
+}
+
+pub struct XChaCha20Poly1305 {
+    // 192-bits; unique per message
+    nonce: Vec<u8>,
+    // xchacha20poly1305
+    cipher: chacha20poly1305::XChaCha20Poly1305,
+}
+
+impl XChaCha20Poly1305 {
+    pub fn new(key: &[u8], nonce: &[u8]) -> crate::Result<Self> {
+        if nonce.len() != 24 {
+            return Err(LogError::Illegal(format!(
+                "nonce must be 24 bytes, but current is {}",
+                nonce.len()
+            )));
+        }
+        match chacha20poly1305::XChaCha20Poly1305::new_from_slice(key) {
+            Ok(cipher) => Ok(XChaCha20Poly1305 {
+                nonce: nonce.to_owned(),
+                cipher,
+            }),
+            Err(e) => Err(LogError::Illegal(format!("key length invalid {}", e))),
+        }
+    }
+}
+
+impl Encryptor for XChaCha20Poly1305 {
+    fn encrypt(&self, data: &[u8], op: Box<dyn Fn(&[u8]) -> Vec<u8>>) -> Result<Vec<u8>, LogError> {
+        // the whole timestamp and position fit in the 192-bits nonce
+        let new_nonce = op(&self.nonce);
+        let nonce = chacha20poly1305::XNonce::from_slice(&new_nonce);
+        self.cipher
+            .encrypt(nonce, data)
+            .map_err(|e| LogError::Crypto(format!("{e:?}")))
+    }
+}
+
+impl Decryptor for XChaCha20Poly1305 {
+    fn decrypt(&self, data: &[u8], op: Box<dyn Fn(&[u8]) -> Vec<u8>>) -> Result<Vec<u8>, LogError> {
+        let new_nonce = op(&self.nonce);
+        let nonce = chacha20poly1305::XNonce::from_slice(&new_nonce);
+        self.cipher
+            .decrypt(nonce, data)
+            .map_err(|e| LogError::Crypto(format!("{e:?}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::logger::{
+        combine_time_position,
+        xor_slice,
+    };
+
+    const KEY: &[u8; 32] = b"an example very very secret key.";
+
+    #[test]
+    fn test_key_fingerprint_stable() {
+        assert_eq!(key_fingerprint(CipherKind::AES256GCMSIV, KEY), 0xa446df8d);
+        assert_ne!(
+            key_fingerprint(CipherKind::AES128GCMSIV, KEY),
+            key_fingerprint(CipherKind::AES256GCMSIV, KEY)
+        );
+    }
+
+    fn nonce_fn(timestamp: i64, position: u64) -> Box<dyn Fn(&[u8]) -> Vec<u8>> {
+        let combine = combine_time_position(timestamp, position);
+        Box::new(move |input| xor_slice(input, &combine))
+    }
+
+    #[test]
+    fn test_cipher_kind_convert() {
+        for kind in [
+            CipherKind::CHACHA20POLY1305,
+            CipherKind::XCHACHA20POLY1305,
+            CipherKind::CUSTOM(0x80),
+        ] {
+            assert_eq!(kind, CipherKind::from(u8::from(kind)));
+            assert_eq!(kind, CipherKind::from_str(&kind.to_string()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_chacha20poly1305() {
+        let cipher = ChaCha20Poly1305::new(KEY, b"unique nonce").unwrap();
+        let encrypted = cipher.encrypt(b"hello ezlog", nonce_fn(1, 22)).unwrap();
+        let decrypted = cipher.decrypt(&encrypted, nonce_fn(1, 22)).unwrap();
+        assert_eq!(decrypted, b"hello ezlog");
+
+        // the record position must change the keystream
+        let other = cipher.encrypt(b"hello ezlog", nonce_fn(1, 23)).unwrap();
+        assert_ne!(encrypted, other);
+        assert!(cipher.decrypt(&encrypted, nonce_fn(1, 23)).is_err());
+    }
+
+    #[test]
+    fn test_xchacha20poly1305() {
+        assert!(XChaCha20Poly1305::new(KEY, b"unique nonce").is_err());
+        let cipher = XChaCha20Poly1305::new(KEY, b"unique nonce, 192 bits!!").unwrap();
+        let encrypted = cipher.encrypt(b"hello ezlog", nonce_fn(1, 22)).unwrap();
+        let decrypted = cipher.decrypt(&encrypted, nonce_fn(1, 22)).unwrap();
+        assert_eq!(decrypted, b"hello ezlog");
+
+        let other = cipher.encrypt(b"hello ezlog", nonce_fn(1, 23)).unwrap();
+        assert_ne!(encrypted, other);
+    }
+}
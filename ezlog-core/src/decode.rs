@@ -32,6 +32,7 @@ use crate::{
     Level,
     NonceGenFn,
     Result,
+    RingState,
     Version,
     RECORD_SIGNATURE_START,
 };
@@ -109,13 +110,22 @@ pub(crate) fn decode_record_from_read(
     cryptor: &Option<Box<dyn Cryptor + Send + Sync>>,
     header: &Header,
     position: u64,
+    lap: u32,
 ) -> Result<Vec<u8>> {
     let chunk = decode_record_to_content(reader, &header.version)?;
-    let combine = crate::logger::combine_time_position(header.timestamp.unix_timestamp(), position);
+    let combine = crate::ring::nonce_combine(
+        header.timestamp.unix_timestamp(),
+        position,
+        header.ring.map(|_| lap),
+    );
 
     let op = Box::new(move |input: &[u8]| crate::logger::xor_slice(input, &combine));
     if header.has_record() && !header.is_extra_index(position) {
-        decode_record_content(&header.version, &chunk, compression, cryptor, op)
+        let chunk = match header.chain_prev {
+            Some(_) => crate::chain::split_mac(&chunk)?.0,
+            None => &chunk,
+        };
+        decode_record_content(&header.version, chunk, compression, cryptor, op)
     } else {
         Ok(chunk)
     }
@@ -154,7 +164,7 @@ pub(crate) fn decode_record_size(mut reader: &mut dyn BufRead, version: &Version
             };
             Ok(content_size)
         }
-        Version::V2 => {
+        Version::V2 | Version::V3 => {
             let size: usize = reader.read_varint()?;
             Ok(size)
         }
@@ -202,9 +212,17 @@ pub fn decode_with_fn<F>(
 ) where
     F: for<'a> FnMut(&'a Vec<u8>, bool) -> Option<u64>,
 {
+    if let Some(ring) = header.ring.filter(|ring| ring.lap > 0) {
+        return decode_ring_with_fn(reader, compression, cryptor, header, &ring, op);
+    }
     loop {
         let position: u64 = reader.position();
-        match decode_record_from_read(reader, compression, cryptor, header, position) {
+        // the signature footer follows the records of a sealed file
+        if header.is_sealed() && position >= header.recorder_position as u64 {
+            op(&vec![], true);
+            break;
+        }
+        match decode_record_from_read(reader, compression, cryptor, header, position, 0) {
             Ok(buf) => match op(&buf, buf.is_empty()) {
                 Some(skip) => {
                     if skip > 0 {
@@ -232,6 +250,42 @@ pub fn decode_with_fn<F>(
     }
 }
 
+/// Decode the wrapped ring buffer, the extra first, then the last lap and the current lap
+fn decode_ring_with_fn<F>(
+    reader: &mut Cursor<Vec<u8>>,
+    compression: &Option<Box<dyn Compress + Send + Sync>>,
+    cryptor: &Option<Box<dyn Cryptor + Send + Sync>>,
+    header: &Header,
+    ring: &RingState,
+    mut op: F,
+) where
+    F: for<'a> FnMut(&'a Vec<u8>, bool) -> Option<u64>,
+{
+    let mut segments = ring.segments(header.recorder_position);
+    if header.has_extra() {
+        segments.insert(0, (header.length() as u64, ring.start.into(), 0));
+    }
+    for (from, to, lap) in segments {
+        reader.set_position(from);
+        while reader.position() < to {
+            let position = reader.position();
+            match decode_record_from_read(reader, compression, cryptor, header, position, lap) {
+                Ok(buf) => match op(&buf, false) {
+                    Some(skip) => {
+                        if skip > 0 {
+                            reader.set_position(reader.position() + skip);
+                        }
+                    }
+                    None => return,
+                },
+                Err(LogError::IoError(_)) | Err(LogError::Illegal(_)) => break,
+                Err(e) => error!(target: "ezlog_decode", "{}", e),
+            }
+        }
+    }
+    op(&vec![], true);
+}
+
 pub fn decode_with_writer(
     cursor: &mut Cursor<Vec<u8>>,
     writer: &mut io::BufWriter<std::fs::File>,
@@ -263,6 +317,28 @@ pub fn decode_with_writer(
         .map_err(|e| LogError::Parse(format!("{}", e)))
 }
 
+/// Decode the first record after the extra, the reader position is restored.
+///
+/// it can be used to check whether the cryptor is able to decrypt the log file.
+pub fn decode_first_record(
+    reader: &mut Cursor<Vec<u8>>,
+    compression: &Option<Box<dyn Compress + Send + Sync>>,
+    cryptor: &Option<Box<dyn Cryptor + Send + Sync>>,
+    header: &Header,
+) -> Result<Vec<u8>> {
+    let start = reader.position();
+    let lap = header.ring.map(|ring| ring.lap).unwrap_or(0);
+    let result = loop {
+        let position = reader.position();
+        match decode_record_from_read(reader, compression, cryptor, header, position, lap) {
+            Ok(_) if header.is_extra_index(position) => continue,
+            other => break other,
+        }
+    };
+    reader.set_position(start);
+    result
+}
+
 pub fn decode_header_and_extra(
     cursor: &mut Cursor<Vec<u8>>,
 ) -> Result<(Header, Option<(String, &str)>)> {
@@ -426,6 +502,129 @@ mod tests {
         fs::remove_dir_all(&config.dir_path()).unwrap_or_default();
     }
 
+    #[cfg(feature = "kdf")]
+    #[test]
+    fn test_encode_decode_with_passphrase() {
+        let dir = test_compat::test_path().join("test_passphrase");
+        let config = crate::EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("passphrase")
+            .compress(crate::CompressKind::ZLIB)
+            .cipher(crate::CipherKind::AES256GCMSIV)
+            .cipher_nonce(b"unique nonce".to_vec())
+            .kdf(crate::KdfParams::pbkdf2_sha256(1000))
+            .cipher_passphrase("correct horse battery staple")
+            .build();
+        assert!(config.check_valid().is_ok());
+
+        let logger = EZLogger::new(config.clone()).unwrap();
+        let key = logger.config.cipher_key().map(|k| k.to_vec());
+        assert_eq!(key.as_ref().map(|k| k.len()), Some(32));
+        for i in 0..10 {
+            logger
+                .append(
+                    EZRecordBuilder::default()
+                        .content(format!("hello world {}", i))
+                        .build(),
+                )
+                .unwrap();
+        }
+        logger.flush().unwrap();
+        drop(logger);
+
+        // reopen the logger, the salt in the header is reused
+        let logger = EZLogger::new(config.clone()).unwrap();
+        assert_eq!(logger.config.cipher_key(), key.as_deref());
+        drop(logger);
+
+        let (path, _mmap) = &config.create_mmap_file().unwrap();
+        let buf = fs::read(path).unwrap();
+        let mut cursor = Cursor::new(buf);
+        let header = Header::decode(&mut cursor).unwrap();
+        assert_eq!(header.version, crate::Version::V3);
+        assert!(header.kdf.is_some());
+
+        let mut decode_config = crate::EZLogConfigBuilder::new()
+            .from_header(&header)
+            .cipher_nonce(b"unique nonce".to_vec())
+            .cipher_passphrase("correct horse battery staple")
+            .build();
+        decode_config.derive_cipher_key().unwrap();
+        assert_eq!(decode_config.cipher_key(), key.as_deref());
+
+        let compression = crate::create_compress(&decode_config);
+        let cryptor = crate::create_cryptor(&decode_config).unwrap();
+        let (tx, rx) = channel();
+        let mut contents = Vec::new();
+        let closure = |data: &Vec<u8>, is_end: bool| {
+            if !data.is_empty() {
+                contents.push(String::from_utf8_lossy(data).to_string());
+            }
+            if is_end {
+                tx.send(()).expect("Could not send signal on channel.");
+                return None;
+            }
+            Some(0)
+        };
+        decode::decode_with_fn(&mut cursor, &compression, &cryptor, &header, closure);
+        rx.recv().expect("Could not receive from channel.");
+        assert_eq!(contents.len(), 10);
+        assert!(contents[9].contains("hello world 9"));
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_encode_decode_chained() {
+        let dir = test_compat::test_path().join("test_chained");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let config = crate::EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("chained")
+            .compress(crate::CompressKind::ZLIB)
+            .cipher(crate::CipherKind::AES256GCMSIV)
+            .cipher_key(b"an example very very secret key.".to_vec())
+            .cipher_nonce(b"unique nonce".to_vec())
+            .chain_key("chain key")
+            .build();
+
+        let logger = EZLogger::new(config.clone()).unwrap();
+        for i in 0..10 {
+            logger
+                .append(
+                    EZRecordBuilder::default()
+                        .content(format!("hello world {}", i))
+                        .build(),
+                )
+                .unwrap();
+        }
+        drop(logger);
+
+        let (path, _mmap) = &config.create_mmap_file().unwrap();
+        let mut cursor = Cursor::new(fs::read(path).unwrap());
+        let header = Header::decode(&mut cursor).unwrap();
+        assert!(header.chain_prev().is_some());
+
+        let compression = crate::create_compress(&config);
+        let cryptor = crate::create_cryptor(&config).unwrap();
+        let (tx, rx) = channel();
+        let mut contents = Vec::new();
+        let closure = |data: &Vec<u8>, is_end: bool| {
+            if !data.is_empty() {
+                contents.push(String::from_utf8_lossy(data).to_string());
+            }
+            if is_end {
+                tx.send(()).expect("Could not send signal on channel.");
+                return None;
+            }
+            Some(0)
+        };
+        decode::decode_with_fn(&mut cursor, &compression, &cryptor, &header, closure);
+        rx.recv().expect("Could not receive from channel.");
+        assert_eq!(contents.len(), 10);
+        assert!(contents[9].contains("hello world 9"));
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
     #[inline]
     fn decode_array_record(
         logger: &mut EZLogger,
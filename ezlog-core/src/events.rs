@@ -27,6 +27,7 @@ pub enum Event {
     RecordError,
     RecordEnd,
     RecordFilterOut,
+    Redact,
 
     // Compression events
     Compress,
@@ -50,9 +51,22 @@ pub enum Event {
     MapFileEnd,
     RotateFile,
     RotateFileError,
+    WrapRing,
+    RotateHook,
+    RotateHookError,
+    RotateHookEnd,
+    RotateKey,
+    RotateKeyError,
+    RotateKeyEnd,
     Trim,
     TrimError,
     TrimEnd,
+    Upload,
+    UploadError,
+    UploadEnd,
+    Export,
+    ExportError,
+    ExportEnd,
 
     // Other Error events
     FFIError,
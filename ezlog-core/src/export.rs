@@ -0,0 +1,410 @@
+use std::{
+    fs,
+    io::Cursor,
+    path::Path,
+};
+
+use serde::{
+    Serialize,
+    Serializer,
+};
+use time::OffsetDateTime;
+
+use crate::{
+    decode,
+    events::event,
+    fetch,
+    redact,
+    EZLogConfig,
+    Event,
+    FetchedFile,
+    Header,
+    LogError,
+    ManifestEntry,
+    Result,
+};
+
+/// The manifest is the last entry of the bundle
+pub(crate) const EXPORT_MANIFEST: &str = "manifest.json";
+/// The log files are in this dir of the bundle
+const EXPORT_LOG_DIR: &str = "logs";
+/// Suffix of the decoded log file in the bundle
+const DECODED_SUFFIX: &str = "log";
+
+/// The manifest of an exported bundle, saved as `manifest.json` in the tar file
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportManifest {
+    /// the logger name
+    pub(crate) name: String,
+    #[serde(serialize_with = "crate::serialize_time")]
+    pub(crate) start: OffsetDateTime,
+    #[serde(serialize_with = "crate::serialize_time")]
+    pub(crate) end: OffsetDateTime,
+    /// whether the files are decoded and redacted
+    pub(crate) decoded: bool,
+    pub(crate) files: Vec<ExportedFile>,
+}
+
+impl ExportManifest {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn start(&self) -> OffsetDateTime {
+        self.start
+    }
+
+    pub fn end(&self) -> OffsetDateTime {
+        self.end
+    }
+
+    pub fn decoded(&self) -> bool {
+        self.decoded
+    }
+
+    pub fn files(&self) -> &[ExportedFile] {
+        &self.files
+    }
+}
+
+/// A log file in the bundle
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedFile {
+    /// the path in the bundle
+    pub(crate) entry: String,
+    /// the file name in the log dir
+    pub(crate) file_name: String,
+    /// size of the entry in bytes
+    pub(crate) size: u64,
+    /// whether it is the file the logger is writing
+    pub(crate) is_active: bool,
+    /// cipher key fingerprint in header, see [crate::key_fingerprint]
+    #[serde(serialize_with = "serialize_fingerprint")]
+    pub(crate) key_fingerprint: u32,
+    #[serde(serialize_with = "crate::serialize_time")]
+    pub(crate) first_record_time: OffsetDateTime,
+    #[serde(serialize_with = "crate::serialize_time")]
+    pub(crate) last_record_time: OffsetDateTime,
+    /// count of the records in the entry, exclude the extra
+    pub(crate) records: u64,
+    pub(crate) extra: Option<String>,
+    /// `None` if the header is broken
+    pub(crate) header: Option<Header>,
+}
+
+impl ExportedFile {
+    pub fn entry(&self) -> &str {
+        &self.entry
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.is_active
+    }
+
+    pub fn key_fingerprint(&self) -> u32 {
+        self.key_fingerprint
+    }
+
+    pub fn first_record_time(&self) -> OffsetDateTime {
+        self.first_record_time
+    }
+
+    pub fn last_record_time(&self) -> OffsetDateTime {
+        self.last_record_time
+    }
+
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    pub fn extra(&self) -> Option<&str> {
+        self.extra.as_deref()
+    }
+
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+}
+
+fn serialize_fingerprint<S>(
+    fingerprint: &u32,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format!("{:08x}", fingerprint))
+}
+
+/// Bundle the rotated files overlapping `[start, end)` and a copy of the current file to a tar file
+///
+/// if `decoded`, only the records in the range are kept, decoded and redacted by the rules of the
+/// config, the file encrypted by another key is skipped.
+pub(crate) fn export(
+    config: &EZLogConfig,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    decoded: bool,
+    dest: &Path,
+) -> Result<ExportManifest> {
+    event!(Event::Export, "{} to {:?}", config.name(), dest);
+    let mut paths: Vec<_> = config
+        .query_log_files_in_range(start, end)
+        .into_iter()
+        .map(|path| (path, false))
+        .collect();
+    let active = config.dir_path().join(config.file_name()?);
+    let active_time = fs::read(&active)
+        .ok()
+        .and_then(|buf| Header::decode(&mut Cursor::new(buf)).ok())
+        .map(|header| header.timestamp);
+    if active_time.is_some_and(|created| created < end) {
+        paths.push((active, true));
+    }
+    let entries = config.manifest_entries().unwrap_or_default();
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut builder = tar::Builder::new(fs::File::create(dest)?);
+    let mut manifest = ExportManifest {
+        name: config.name().to_string(),
+        start,
+        end,
+        decoded,
+        files: Vec::new(),
+    };
+    for (path, is_active) in paths {
+        let entry = entries
+            .iter()
+            .find(|e| path.file_name().and_then(|n| n.to_str()) == Some(e.file_name()));
+        let file = if decoded {
+            append_decoded(&mut builder, config, &path, entry, is_active, start, end)
+        } else {
+            append_file(&mut builder, config, &path, entry, is_active)
+        };
+        match file {
+            Ok(Some(file)) => manifest.files.push(file),
+            Ok(None) => {}
+            Err(e) => event!(!Event::ExportError, "export {:?}", path; &e),
+        }
+    }
+
+    let json = serde_json::to_vec_pretty(&manifest).map_err(|e| LogError::Parse(e.to_string()))?;
+    append_data(&mut builder, EXPORT_MANIFEST, &json)?;
+    builder.into_inner()?.sync_all()?;
+    event!(Event::ExportEnd, "{} files", manifest.files.len());
+    Ok(manifest)
+}
+
+fn append_file(
+    builder: &mut tar::Builder<fs::File>,
+    config: &EZLogConfig,
+    path: &Path,
+    entry: Option<&ManifestEntry>,
+    is_active: bool,
+) -> Result<Option<ExportedFile>> {
+    let file = exported_file(config, path, entry, is_active)?;
+    builder.append_path_with_name(path, &file.entry)?;
+    Ok(Some(file))
+}
+
+/// Append the records in the range, returns `None` if there is no record
+fn append_decoded(
+    builder: &mut tar::Builder<fs::File>,
+    config: &EZLogConfig,
+    path: &Path,
+    entry: Option<&ManifestEntry>,
+    is_active: bool,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) -> Result<Option<ExportedFile>> {
+    let mut content = String::new();
+    let mut times: Option<(OffsetDateTime, OffsetDateTime)> = None;
+    let mut records = 0;
+    fetch::for_each_record(config, path, |time, buf| {
+        if start <= time && time < end {
+            let (text, _) = redact::redact(config.redact_rules(), &String::from_utf8_lossy(buf));
+            content.push_str(&text);
+            content.push('\n');
+            times = Some(times.map_or((time, time), |(first, _)| (first, time)));
+            records += 1;
+        }
+    })?;
+    let Some((first, last)) = times else {
+        return Ok(None);
+    };
+    let mut file = exported_file(config, path, entry, is_active)?;
+    file.entry = format!("{}.{}", file.entry, DECODED_SUFFIX);
+    file.size = content.len() as u64;
+    file.first_record_time = first;
+    file.last_record_time = last;
+    file.records = records;
+    append_data(builder, &file.entry, content.as_bytes())?;
+    Ok(Some(file))
+}
+
+fn exported_file(
+    config: &EZLogConfig,
+    path: &Path,
+    entry: Option<&ManifestEntry>,
+    is_active: bool,
+) -> Result<ExportedFile> {
+    let fetched = FetchedFile::from_file(path, entry, is_active)?;
+    // the time span of the records is in the export manifest
+    let (first_record_time, last_record_time) = fetched
+        .record_times(config)
+        .unwrap_or((fetched.created_time(), fetched.rotated_time()));
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(String::from)
+        .unwrap_or_default();
+    let (header, extra) = match decode::decode_header_and_extra(&mut Cursor::new(fs::read(path)?)) {
+        Ok((header, extra)) => (Some(header), extra.map(|(extra, _)| extra)),
+        Err(_) => (None, None),
+    };
+    Ok(ExportedFile {
+        entry: format!("{}/{}", EXPORT_LOG_DIR, file_name),
+        file_name,
+        size: fetched.size(),
+        is_active,
+        key_fingerprint: fetched.key_fingerprint(),
+        first_record_time,
+        last_record_time,
+        records: fetched.records(),
+        extra,
+        header,
+    })
+}
+
+fn append_data(builder: &mut tar::Builder<fs::File>, name: &str, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(OffsetDateTime::now_utc().unix_timestamp().max(0) as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, name, data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use time::Duration;
+
+    use super::*;
+    use crate::{
+        EZLogConfigBuilder,
+        EZLogger,
+        EZRecord,
+        RedactRule,
+    };
+
+    #[test]
+    fn test_export() {
+        let dir = test_compat::test_path().join("export");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let config = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("export")
+            .extra("export extra")
+            .build();
+        let logger = EZLogger::new(config.clone()).unwrap();
+        logger
+            .append(EZRecord::builder().content("sealed token=1").build())
+            .unwrap();
+        logger.rotate_if_not_empty().unwrap();
+        logger
+            .append(EZRecord::builder().content("active token=2").build())
+            .unwrap();
+        logger.flush().unwrap();
+
+        let now = OffsetDateTime::now_utc();
+        let (start, end) = (now - Duration::minutes(1), now + Duration::minutes(1));
+        let dest = dir.join("bundle").join("export.tar");
+        let manifest = export(&config, start, end, false, &dest).unwrap();
+        assert_eq!(manifest.files().len(), 2);
+        assert!(!manifest.files()[0].is_active());
+        assert!(manifest.files()[1].is_active());
+        assert!(manifest
+            .files()
+            .iter()
+            .all(|file| file.extra() == Some("export extra") && file.records() == 1));
+
+        let mut archive = tar::Archive::new(fs::File::open(&dest).unwrap());
+        let mut names = Vec::new();
+        let mut json = String::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_string_lossy().to_string();
+            if name == EXPORT_MANIFEST {
+                entry.read_to_string(&mut json).unwrap();
+            }
+            names.push(name);
+        }
+        assert_eq!(
+            names,
+            vec![
+                manifest.files()[0].entry().to_string(),
+                manifest.files()[1].entry().to_string(),
+                EXPORT_MANIFEST.to_string()
+            ]
+        );
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["name"], "export");
+        assert_eq!(value["files"][1]["is_active"], true);
+        assert_eq!(
+            value["files"][0]["key_fingerprint"],
+            format!("{:08x}", config.cipher_hash())
+        );
+
+        // decode and redact
+        let config = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("export")
+            .redact(RedactRule::literal("token=").mask())
+            .build();
+        let dest = dir.join("bundle").join("decoded.tar");
+        let manifest = export(&config, start, end, true, &dest).unwrap();
+        assert!(manifest.decoded());
+        let mut archive = tar::Archive::new(fs::File::open(&dest).unwrap());
+        let mut content = String::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            if entry
+                .path()
+                .unwrap()
+                .extension()
+                .is_some_and(|ext| ext == DECODED_SUFFIX)
+            {
+                entry.read_to_string(&mut content).unwrap();
+            }
+        }
+        assert!(content.contains("sealed ***1"));
+        assert!(content.contains("active ***2"));
+        assert!(!content.contains("token="));
+
+        assert!(export(
+            &config,
+            start - Duration::days(2),
+            start - Duration::days(1),
+            false,
+            &dest
+        )
+        .unwrap()
+        .files()
+        .is_empty());
+        drop(logger);
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+}
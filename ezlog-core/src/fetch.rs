@@ -0,0 +1,407 @@
+use std::{
+    fs,
+    io::{
+        Cursor,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use time::{
+    format_description::well_known::Rfc3339,
+    OffsetDateTime,
+};
+
+use crate::{
+    create_compress,
+    create_cryptor,
+    decode,
+    events::event,
+    EZLogConfig,
+    EZLogger,
+    Event,
+    FetchMode,
+    Header,
+    LogError,
+    Result,
+};
+
+/// The records fetched in a time range are written in this dir of the log dir
+pub(crate) const FETCH_DIR: &str = "fetch";
+/// Suffix of the decoded records file
+const DECODED_SUFFIX: &str = "log";
+
+/// Extract the records in `[start, end)` of the log files to one file in [FETCH_DIR]
+///
+/// the record time is read from the `[time ...]` prefix of the default formatter, a record without
+/// it inherits the time of the previous record, or the creation time of the file.
+/// the file encrypted by another key or not readable is pushed to `skipped`.
+/// returns `None` if there is no record in the range, or the mode is [FetchMode::Files].
+pub(crate) fn extract_records(
+    config: &EZLogConfig,
+    files: &[PathBuf],
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    mode: FetchMode,
+    skipped: &mut Vec<PathBuf>,
+) -> Result<Option<PathBuf>> {
+    if mode == FetchMode::Files {
+        return Ok(None);
+    }
+    let mut records = Vec::new();
+    let mut size = 0;
+    for path in files {
+        if let Err(e) = read_records(config, path, start, end, &mut records) {
+            event!(!Event::RequestLogError, "extract records from {:?}", path; &e);
+            skipped.push(path.clone());
+        }
+        size += fs::metadata(path).map(|m| m.len()).unwrap_or_default();
+    }
+    if records.is_empty() {
+        return Ok(None);
+    }
+
+    let dir = config.dir_path().join(FETCH_DIR);
+    fs::create_dir_all(&dir)?;
+    let name = format!(
+        "{}_{}_{}",
+        config.name(),
+        start.unix_timestamp(),
+        end.unix_timestamp()
+    );
+    match mode {
+        FetchMode::Files => Ok(None),
+        FetchMode::Decoded => {
+            let path = dir.join(format!("{}.{}", name, DECODED_SUFFIX));
+            let mut file = fs::File::create(&path)?;
+            for record in &records {
+                file.write_all(record)?;
+                file.write_all(b"\n")?;
+            }
+            Ok(Some(path))
+        }
+        FetchMode::Encoded => {
+            let fetch_config = config.fetch_config(dir, name, size);
+            let path = fetch_config.dir_path().join(fetch_config.file_name()?);
+            fs::remove_file(&path).unwrap_or_default();
+            let logger = EZLogger::new(fetch_config)?;
+            for (index, record) in records.into_iter().enumerate() {
+                logger.append_formatted(&index.to_string(), record)?;
+            }
+            logger.flush()?;
+            Ok(Some(path))
+        }
+    }
+}
+
+/// The records files fetched by the logger in [FETCH_DIR] with their size, the oldest first
+///
+/// they are the copies of the log files, removed by the trim and counted in the max total size.
+pub(crate) fn fetched_outputs(config: &EZLogConfig) -> Vec<(PathBuf, u64, OffsetDateTime)> {
+    let Ok(dir) = fs::read_dir(config.dir_path().join(FETCH_DIR)) else {
+        return Vec::new();
+    };
+    let mut outputs: Vec<(PathBuf, u64, OffsetDateTime)> = dir
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| is_fetched_output(config.name(), name))
+        })
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok().filter(|meta| meta.is_file())?;
+            let modified = meta.modified().map(OffsetDateTime::from).ok()?;
+            Some((entry.path(), meta.len(), modified))
+        })
+        .collect();
+    outputs.sort_by_key(|(_, _, modified)| *modified);
+    outputs
+}
+
+/// The file name is `{name}_{start}_{end}.{suffix}` written by [extract_records]
+fn is_fetched_output(name: &str, file_name: &str) -> bool {
+    let Some(range) = file_name
+        .strip_prefix(name)
+        .and_then(|rest| rest.strip_prefix('_'))
+        .and_then(|rest| rest.split_once('.'))
+        .map(|(range, _)| range)
+    else {
+        return false;
+    };
+    range
+        .split_once('_')
+        .is_some_and(|(start, end)| start.parse::<i64>().is_ok() && end.parse::<i64>().is_ok())
+}
+
+fn read_records(
+    config: &EZLogConfig,
+    path: &Path,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    records: &mut Vec<Vec<u8>>,
+) -> Result<()> {
+    for_each_record(config, path, |time, buf| {
+        if start <= time && time < end {
+            records.push(buf.clone());
+        }
+    })
+}
+
+/// The time of the first and the last record of the log file, or the decoded records
+pub(crate) fn record_times(
+    config: &EZLogConfig,
+    path: &Path,
+) -> Option<(OffsetDateTime, OffsetDateTime)> {
+    let mut times: Option<(OffsetDateTime, OffsetDateTime)> = None;
+    let mut update = |time| {
+        times = Some(times.map_or((time, time), |(first, _)| (first, time)));
+    };
+    if path.extension().is_some_and(|ext| ext == DECODED_SUFFIX) {
+        let content = fs::read(path).ok()?;
+        content
+            .split(|b| *b == b'\n')
+            .filter_map(record_time)
+            .for_each(update);
+    } else {
+        for_each_record(config, path, |time, _| update(time)).ok()?;
+    }
+    times
+}
+
+/// Decode the records of the log file with their time, exclude the extra
+pub(crate) fn for_each_record<F>(config: &EZLogConfig, path: &Path, mut op: F) -> Result<()>
+where
+    F: FnMut(OffsetDateTime, &Vec<u8>),
+{
+    let mut cursor = Cursor::new(fs::read(path)?);
+    let header = Header::decode(&mut cursor)?;
+    if header.cipher_hash() != config.cipher_hash() {
+        return Err(LogError::Crypto(format!(
+            "cipher key of {:?} is not matched",
+            path
+        )));
+    }
+    let compression = create_compress(config);
+    let cryptor = create_cryptor(config)?;
+    let mut time = header.timestamp;
+    let mut skip_extra = header.has_extra();
+    decode::decode_with_fn(
+        &mut cursor,
+        &compression,
+        &cryptor,
+        &header,
+        |buf, is_end| {
+            if is_end {
+                return None;
+            }
+            if std::mem::take(&mut skip_extra) {
+                return Some(0);
+            }
+            time = record_time(buf).unwrap_or(time);
+            op(time, buf);
+            Some(0)
+        },
+    );
+    Ok(())
+}
+
+/// The time in the `[time ...]` prefix of the record
+///
+/// it is the RFC 3339 time written by the default formatter, the records of a custom
+/// [crate::Formatter] have no time unless they start with the same prefix.
+fn record_time(buf: &[u8]) -> Option<OffsetDateTime> {
+    let rest = buf.strip_prefix(b"[")?;
+    let len = rest.iter().position(|b| *b == b' ' || *b == b']')?;
+    OffsetDateTime::parse(std::str::from_utf8(&rest[..len]).ok()?, &Rfc3339).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Duration;
+
+    use super::*;
+    use crate::{
+        EZLogConfigBuilder,
+        EZRecord,
+    };
+
+    #[test]
+    fn test_extract_records() {
+        let dir = test_compat::test_path().join("fetch_range");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let config = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("range")
+            .extra("range extra")
+            .cipher(crate::CipherKind::AES256GCMSIV)
+            .cipher_key(b"an example very very secret key.".to_vec())
+            .cipher_nonce(b"unique nonce".to_vec())
+            .build();
+        let base = OffsetDateTime::now_utc().replace_nanosecond(0).unwrap() - Duration::hours(1);
+        let logger = EZLogger::new(config.clone()).unwrap();
+        for minute in 0..60 {
+            logger
+                .append(
+                    EZRecord::builder()
+                        .time(base + Duration::minutes(minute))
+                        .content(format!("minute {:02}", minute))
+                        .build(),
+                )
+                .unwrap();
+        }
+        logger.rotate_if_not_empty().unwrap();
+
+        // the file is selected by its creation and rotation time
+        let now = OffsetDateTime::now_utc();
+        let files = config.query_log_files_in_range(now - Duration::minutes(1), now);
+        assert_eq!(files.len(), 1);
+        assert!(config
+            .query_log_files_in_range(base - Duration::days(2), base - Duration::days(1))
+            .is_empty());
+
+        // the records are selected by the time in record
+        let start = base + Duration::minutes(10);
+        let end = base + Duration::minutes(20);
+        let mut skipped = Vec::new();
+
+        let decoded = extract_records(
+            &config,
+            &files,
+            start,
+            end,
+            FetchMode::Decoded,
+            &mut skipped,
+        )
+        .unwrap()
+        .unwrap();
+        let content = fs::read_to_string(&decoded).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert!(lines[0].ends_with("minute 10"));
+        assert!(lines[9].ends_with("minute 19"));
+
+        let encoded = extract_records(
+            &config,
+            &files,
+            start,
+            end,
+            FetchMode::Encoded,
+            &mut skipped,
+        )
+        .unwrap()
+        .unwrap();
+        let mut cursor = Cursor::new(fs::read(&encoded).unwrap());
+        let header = Header::decode(&mut cursor).unwrap();
+        let cryptor = create_cryptor(&config).unwrap();
+        let mut contents = Vec::new();
+        decode::decode_with_fn(&mut cursor, &None, &cryptor, &header, |buf, is_end| {
+            if is_end {
+                return None;
+            }
+            contents.push(String::from_utf8_lossy(buf).to_string());
+            Some(0)
+        });
+        assert_eq!(contents.len(), 11);
+        assert_eq!(contents[0], "range extra");
+        assert_eq!(&contents[1..], &lines[..]);
+
+        assert!(extract_records(
+            &config,
+            &files,
+            base - Duration::days(2),
+            base - Duration::days(1),
+            FetchMode::Decoded,
+            &mut skipped
+        )
+        .unwrap()
+        .is_none());
+        assert!(skipped.is_empty());
+
+        // the file encrypted by another key is reported
+        let other = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("range_other")
+            .cipher(crate::CipherKind::AES256GCMSIV)
+            .cipher_key(b"another example very secret key.".to_vec())
+            .cipher_nonce(b"unique nonce".to_vec())
+            .build();
+        let other_logger = EZLogger::new(other.clone()).unwrap();
+        other_logger
+            .append(EZRecord::builder().time(start).content("other").build())
+            .unwrap();
+        other_logger.rotate_if_not_empty().unwrap();
+        let mut mixed = files.clone();
+        mixed.extend(other.query_log_files_in_range(now - Duration::minutes(1), now));
+        assert_eq!(mixed.len(), 2);
+        extract_records(
+            &config,
+            &mixed,
+            start,
+            end,
+            FetchMode::Decoded,
+            &mut skipped,
+        )
+        .unwrap();
+        assert_eq!(skipped, &mixed[1..]);
+        drop(other_logger);
+        drop(logger);
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_trim_fetched() {
+        let dir = test_compat::test_path().join("fetch_trim");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let builder = EZLogConfigBuilder::new().dir_path(&dir).name("trim");
+        let config = builder.clone().build();
+        let logger = EZLogger::new(config.clone()).unwrap();
+        let start = OffsetDateTime::now_utc() - Duration::minutes(1);
+        logger
+            .append(EZRecord::builder().content("fetched").build())
+            .unwrap();
+        logger.rotate_if_not_empty().unwrap();
+        let files = config.query_log_files_in_range(start, OffsetDateTime::now_utc());
+        let end = OffsetDateTime::now_utc() + Duration::minutes(1);
+        let mut skipped = Vec::new();
+        for mode in [FetchMode::Decoded, FetchMode::Encoded] {
+            extract_records(&config, &files, start, end, mode, &mut skipped)
+                .unwrap()
+                .unwrap();
+        }
+        // the outputs of another logger with the same prefix are not counted
+        let other = builder.clone().name("trim_other").build();
+        let other_dir = dir.join(FETCH_DIR);
+        fs::write(other_dir.join("trim_other_1_2.log"), b"other").unwrap();
+        assert_eq!(fetched_outputs(&config).len(), 2);
+        assert_eq!(fetched_outputs(&other).len(), 1);
+        drop(logger);
+
+        // the fetched files in the trim duration are kept
+        EZLogger::new(config.clone()).unwrap().trim();
+        assert_eq!(fetched_outputs(&config).len(), 2);
+
+        let expired = builder.clone().trim_duration(Duration::seconds(-1)).build();
+        EZLogger::new(expired).unwrap().trim();
+        assert!(fetched_outputs(&config).is_empty());
+        assert_eq!(fetched_outputs(&other).len(), 1);
+
+        // the fetched files are removed first over the max total size
+        extract_records(
+            &config,
+            &files,
+            start,
+            end,
+            FetchMode::Decoded,
+            &mut skipped,
+        )
+        .unwrap();
+        let limited = builder.max_total_size(1).build();
+        EZLogger::new(limited).unwrap().trim_total_size();
+        assert!(fetched_outputs(&config).is_empty());
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+}
@@ -64,10 +64,8 @@ pub unsafe extern "C" fn ezlog_create_log(
     let compress = CompressKind::from(c_compress);
     let compress_level = CompressLevel::from(c_compress_level);
     let cipher = CipherKind::from(c_cipher);
-    let key_bytes = slice::from_raw_parts(c_cipher_key, c_key_len);
-    let cipher_key: Vec<u8> = key_bytes.to_owned();
-    let nonce_bytes = slice::from_raw_parts(c_cipher_nonce, c_nonce_len);
-    let cipher_nonce: Vec<u8> = nonce_bytes.to_owned();
+    let cipher_key = SecretBytes::from(slice::from_raw_parts(c_cipher_key, c_key_len));
+    let cipher_nonce = SecretBytes::from(slice::from_raw_parts(c_cipher_nonce, c_nonce_len));
     let rotate_duration = Duration::hours(c_rotate_duration as i64);
     let extra = CStr::from_ptr(c_extra).to_string_lossy().into_owned();
 
@@ -206,6 +204,207 @@ impl Drop for Callback {
     }
 }
 
+/// Register callback function for get logger's files with metadata asynchronously
+///
+/// only the first registered callback takes effect, register either this or [ezlog_register_callback]
+#[no_mangle]
+pub unsafe extern "C" fn ezlog_register_files_callback(callback: FilesCallback) {
+    set_boxed_callback(Box::new(callback));
+}
+
+/// map to c EZFetchedFile struct, the times are unix timestamp in milliseconds
+#[repr(C)]
+pub struct EZFetchedFile {
+    path: *const c_char,
+    size: u64,
+    version: c_uchar,
+    compress: c_uchar,
+    cipher: c_uchar,
+    is_active: bool,
+    key_fingerprint: c_uint,
+    created_time: i64,
+    rotated_time: i64,
+    records: u64,
+}
+
+/// map to c FilesCallback struct
+#[repr(C)]
+pub struct FilesCallback {
+    successPoint: *mut c_void,
+    onFilesFetchSuccess:
+        extern "C" fn(*mut c_void, *const c_char, *const c_char, *const EZFetchedFile, i32),
+    failPoint: *mut c_void,
+    onLogsFetchFail: extern "C" fn(*mut c_void, *const c_char, *const c_char, *const c_char),
+}
+
+impl FilesCallback {
+    pub fn success(
+        &self,
+        log_name: &str,
+        date: &str,
+        files: &[FetchedFile],
+    ) -> std::result::Result<(), NulError> {
+        let c_log_name = CString::new(log_name)?;
+        let c_date = CString::new(date)?;
+        with_c_files(files, |c_files, len| {
+            (self.onFilesFetchSuccess)(
+                self.successPoint as *mut _,
+                c_log_name.as_ptr(),
+                c_date.as_ptr(),
+                c_files,
+                len,
+            )
+        })
+    }
+
+    pub fn fail(&self, log_name: &str, date: &str, err: &str) -> std::result::Result<(), NulError> {
+        let c_log_name = CString::new(log_name)?;
+        let c_date = CString::new(date)?;
+        let c_err = CString::new(err)?;
+        (self.onLogsFetchFail)(
+            self.failPoint as *mut _,
+            c_log_name.as_ptr(),
+            c_date.as_ptr(),
+            c_err.as_ptr(),
+        );
+        Ok(())
+    }
+}
+
+impl EZLogCallback for FilesCallback {
+    fn on_fetch_success(&self, _name: &str, _date: &str, _logs: &[&str]) {}
+
+    fn on_fetch_fail(&self, name: &str, date: &str, err_msg: &str) {
+        self.fail(name, date, err_msg)
+            .unwrap_or_else(|e| event!(!Event::FFIError, "fetch fail nul"; &e.into()));
+    }
+
+    fn on_fetch_files(&self, name: &str, date: &str, files: &[FetchedFile]) {
+        self.success(name, date, files)
+            .unwrap_or_else(|e| event!(!Event::FFIError, "fetch success nul"; &e.into()));
+    }
+}
+
+/// Pass the files as a C array, the strings outlive the call, the callee copies what it needs
+fn with_c_files<F>(files: &[FetchedFile], op: F) -> std::result::Result<(), NulError>
+where
+    F: FnOnce(*const EZFetchedFile, i32),
+{
+    let c_paths = files
+        .iter()
+        .map(|f| CString::new(f.path().to_string_lossy().as_bytes()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let c_files = files
+        .iter()
+        .zip(&c_paths)
+        .map(|(f, path)| EZFetchedFile {
+            path: path.as_ptr(),
+            size: f.size(),
+            version: f.version().into(),
+            compress: f.compress().into(),
+            cipher: f.cipher().into(),
+            is_active: f.is_active(),
+            key_fingerprint: f.key_fingerprint(),
+            created_time: unix_millis(f.created_time()),
+            rotated_time: unix_millis(f.rotated_time()),
+            records: f.records(),
+        })
+        .collect::<Vec<_>>();
+    op(c_files.as_ptr(), c_files.len() as i32);
+    Ok(())
+}
+
+/// map to c FetchCallback struct, it is called once with the result of the request
+#[repr(C)]
+pub struct FetchCallback {
+    point: *mut c_void,
+    onFetchSuccess:
+        extern "C" fn(*mut c_void, u64, *const c_char, *const c_char, *const EZFetchedFile, i32),
+    onFetchFail: extern "C" fn(*mut c_void, u64, *const c_char, *const c_char, *const c_char),
+}
+
+// the point is owned by the caller until the callback is called
+unsafe impl Send for FetchCallback {}
+
+impl FetchCallback {
+    fn complete(&self, result: &FetchResult) -> std::result::Result<(), NulError> {
+        let c_log_name = CString::new(result.name())?;
+        let c_date = CString::new(result.date())?;
+        match (result.files(), result.error()) {
+            (None, Some(err)) => {
+                let c_err = CString::new(err.to_string())?;
+                (self.onFetchFail)(
+                    self.point,
+                    result.id(),
+                    c_log_name.as_ptr(),
+                    c_date.as_ptr(),
+                    c_err.as_ptr(),
+                );
+                Ok(())
+            }
+            (files, _) => with_c_files(files.unwrap_or_default(), |c_files, len| {
+                (self.onFetchSuccess)(
+                    self.point,
+                    result.id(),
+                    c_log_name.as_ptr(),
+                    c_date.as_ptr(),
+                    c_files,
+                    len,
+                )
+            }),
+        }
+    }
+}
+
+/// Request logger's files in the time range `[start, end)` with a callback of this request
+///
+/// the times are unix timestamp in milliseconds, `c_mode` is 0 for files, 1 for decoded records and
+/// 2 for encoded records. returns the request id which is passed to the callback, or 0 if the
+/// request is rejected, like the time range is illegal, `onFetchFail` is called with the id 0.
+#[no_mangle]
+pub unsafe extern "C" fn ezlog_request_log_files(
+    c_log_name: *const c_char,
+    c_start_time: i64,
+    c_end_time: i64,
+    c_mode: c_uchar,
+    callback: FetchCallback,
+) -> u64 {
+    let log_name = CStr::from_ptr(c_log_name).to_string_lossy().into_owned();
+    let (Some(start), Some(end)) = (from_unix_millis(c_start_time), from_unix_millis(c_end_time))
+    else {
+        let err = LogError::Illegal(format!(
+            "time range illegal {} {}",
+            c_start_time, c_end_time
+        ));
+        event!(!Event::RequestLogError, "request log files"; &err);
+        callback
+            .complete(&FetchResult::failed(0, log_name, err))
+            .unwrap_or_else(|e| event!(!Event::FFIError, "fetch callback nul"; &e.into()));
+        return 0;
+    };
+    crate::request_log_files(
+        &log_name,
+        start,
+        end,
+        FetchMode::from(c_mode),
+        move |result| {
+            callback
+                .complete(&result)
+                .unwrap_or_else(|e| event!(!Event::FFIError, "fetch callback nul"; &e.into()));
+        },
+    )
+}
+
+#[inline]
+fn from_unix_millis(millis: i64) -> Option<OffsetDateTime> {
+    OffsetDateTime::from_unix_timestamp_nanos((millis as i128) * 1_000_000).ok()
+}
+
+#[inline]
+fn unix_millis(time: OffsetDateTime) -> i64 {
+    (time.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
 /// Request logger's files path array by specified date
 /// before call this function, you should register a callback
 /// call
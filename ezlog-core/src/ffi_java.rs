@@ -45,7 +45,11 @@ use crate::{
     CompressLevel,
     EZLogConfigBuilder,
     EZRecordBuilder,
+    FetchMode,
+    FetchResult,
+    FetchedFile,
     Level,
+    SecretBytes,
 };
 
 static JVM: OnceCell<Arc<JavaVM>> = OnceCell::new();
@@ -98,8 +102,9 @@ pub extern "C" fn Java_wtf_s1_ezlog_EZLog_nativeCreateLogger<'local>(
     let compress: CompressKind = CompressKind::from(j_compress as u8);
     let compress_level: CompressLevel = CompressLevel::from(j_compress_level as u8);
     let cipher: CipherKind = CipherKind::from(j_cipher as u8);
-    let cipher_key = &env.convert_byte_array(j_cipher_key).unwrap_or_default();
-    let cipher_nonce = &env.convert_byte_array(j_cipher_nonce).unwrap_or_default();
+    let cipher_key = SecretBytes::from(env.convert_byte_array(j_cipher_key).unwrap_or_default());
+    let cipher_nonce =
+        SecretBytes::from(env.convert_byte_array(j_cipher_nonce).unwrap_or_default());
     let extra: String = env
         .get_string(&j_extra)
         .map(|s| s.into())
@@ -114,8 +119,8 @@ pub extern "C" fn Java_wtf_s1_ezlog_EZLog_nativeCreateLogger<'local>(
         .compress(compress)
         .compress_level(compress_level)
         .cipher(cipher)
-        .cipher_key(cipher_key.to_owned())
-        .cipher_nonce(cipher_nonce.to_owned())
+        .cipher_key(cipher_key)
+        .cipher_nonce(cipher_nonce)
         .extra(extra)
         .build();
 
@@ -195,13 +200,28 @@ pub extern "C" fn Java_wtf_s1_ezlog_EZLog_nativeTrim(_env: JNIEnv, _: JClass) {
 // TODO: thread safe
 #[no_mangle]
 pub extern "C" fn Java_wtf_s1_ezlog_EZLog_nativeRegisterCallback(
-    env: JNIEnv,
+    mut env: JNIEnv,
     _jclass: JClass,
     j_callback: JObject,
 ) {
+    // the app class can not be found in the callback thread, keep it here
+    let fetched_file_class = env
+        .find_class(FETCHED_FILE_CLASS)
+        .and_then(|class| env.new_global_ref(class))
+        .map_err(|e| {
+            event!(
+                !Event::FFIError,
+                "find fetched file class error";
+                &LogError::FFI(e.to_string())
+            )
+        })
+        .ok();
     match env.new_global_ref(j_callback) {
         Ok(gloableCallback) => {
-            set_boxed_callback(Box::new(AndroidCallback::new(gloableCallback)));
+            set_boxed_callback(Box::new(AndroidCallback::new(
+                gloableCallback,
+                fetched_file_class,
+            )));
         }
         Err(e) => event!(
             !Event::FFIError,
@@ -242,13 +262,50 @@ pub extern "C" fn Java_wtf_s1_ezlog_EZLog_nativeRequestLogFilesForDate(
     crate::request_log_files_for_date(&log_name, start, end);
 }
 
+const FETCHED_FILE_CLASS: &str = "wtf/s1/ezlog/FetchedFile";
+
 struct AndroidCallback {
     callback: GlobalRef,
+    fetched_file_class: Option<GlobalRef>,
 }
 
 impl AndroidCallback {
-    fn new(callback: GlobalRef) -> Self {
-        Self { callback }
+    fn new(callback: GlobalRef, fetched_file_class: Option<GlobalRef>) -> Self {
+        Self {
+            callback,
+            fetched_file_class,
+        }
+    }
+
+    fn internal_fetch_files(
+        &self,
+        name: &str,
+        date: &str,
+        files: &[FetchedFile],
+    ) -> Result<(), jni::errors::Error> {
+        let Some(class) = &self.fetched_file_class else {
+            let paths: Vec<&str> = files
+                .iter()
+                .map(|f| f.path().to_str().unwrap_or(""))
+                .collect();
+            return self.internal_fetch_success(name, date, &paths);
+        };
+        let mut env = get_env()?;
+        let name = env.new_string(name)?;
+        let date = env.new_string(date)?;
+        let j_files = fetched_files_to_jobjectArray(&mut env, class, files)?;
+        let args: [JValue; 3] = [
+            JValueGen::Object(&name),
+            JValueGen::Object(&date),
+            JValueGen::Object(&j_files),
+        ];
+        env.call_method(
+            self.callback.as_obj(),
+            "onFilesSuccess",
+            "(Ljava/lang/String;Ljava/lang/String;[Lwtf/s1/ezlog/FetchedFile;)V",
+            &args,
+        )?;
+        Ok(())
     }
 
     fn internal_fetch_success(
@@ -330,6 +387,158 @@ impl crate::EZLogCallback for AndroidCallback {
         self.internal_fetch_fail(name, date, err)
             .unwrap_or_else(|e| event!(!Event::FFIError, "on fetch fail"; &e.into()));
     }
+
+    fn on_fetch_files(&self, name: &str, date: &str, files: &[FetchedFile]) {
+        self.internal_fetch_files(name, date, files)
+            .unwrap_or_else(|e| event!(!Event::FFIError, "on fetch files"; &e.into()));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn Java_wtf_s1_ezlog_EZLog_nativeRequestLogFiles(
+    mut env: JNIEnv,
+    _: JClass,
+    j_log_name: JString,
+    j_start: jlong,
+    j_end: jlong,
+    j_mode: jint,
+    j_callback: JObject,
+) -> jlong {
+    let log_name: String = env
+        .get_string(&j_log_name)
+        .map(|name| name.into())
+        .unwrap_or_default();
+    let (Some(start), Some(end)) = (from_unix_millis(j_start), from_unix_millis(j_end)) else {
+        let err = LogError::Illegal(format!("time range illegal {} {}", j_start, j_end));
+        event!(!Event::RequestLogError, "request log files"; &err);
+        fetch_fail(
+            &mut env,
+            &j_callback,
+            &FetchResult::failed(0, log_name, err),
+        )
+        .unwrap_or_else(|e| event!(!Event::FFIError, "on fetch fail"; &e.into()));
+        return 0;
+    };
+    let refs = env.new_global_ref(&j_callback).and_then(|callback| {
+        let class = env.find_class(FETCHED_FILE_CLASS)?;
+        Ok((callback, env.new_global_ref(class)?))
+    });
+    let (callback, class) = match refs {
+        Ok(refs) => refs,
+        Err(e) => {
+            let err = LogError::FFI(e.to_string());
+            event!(!Event::FFIError, "request log files error"; &err);
+            // the pending exception, like the class not found, is reported by the callback
+            env.exception_clear().ok();
+            fetch_fail(
+                &mut env,
+                &j_callback,
+                &FetchResult::failed(0, log_name, err),
+            )
+            .unwrap_or_else(|e| event!(!Event::FFIError, "on fetch fail"; &e.into()));
+            return 0;
+        }
+    };
+    crate::request_log_files(
+        &log_name,
+        start,
+        end,
+        FetchMode::from(j_mode as u8),
+        move |result| {
+            fetch_complete(&callback, &class, &result)
+                .unwrap_or_else(|e| event!(!Event::FFIError, "on fetch complete"; &e.into()));
+        },
+    ) as jlong
+}
+
+/// Call `EZLogFetchCallback` with the result of the request
+fn fetch_complete(
+    callback: &GlobalRef,
+    class: &GlobalRef,
+    result: &FetchResult,
+) -> Result<(), jni::errors::Error> {
+    let mut env = get_env()?;
+    let files = match (result.files(), result.error()) {
+        (None, Some(_)) => return fetch_fail(&mut env, callback, result),
+        (files, _) => files.unwrap_or_default(),
+    };
+    let id = JValueGen::Long(result.id() as jlong);
+    let name = env.new_string(result.name())?;
+    let date = env.new_string(result.date())?;
+    let j_files = fetched_files_to_jobjectArray(&mut env, class, files)?;
+    env.call_method(
+        callback,
+        "onSuccess",
+        "(JLjava/lang/String;Ljava/lang/String;[Lwtf/s1/ezlog/FetchedFile;)V",
+        &[
+            id,
+            JValueGen::Object(&name),
+            JValueGen::Object(&date),
+            JValueGen::Object(&j_files),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Call `EZLogFetchCallback.onFail` with the error of the result
+fn fetch_fail<'a>(
+    env: &mut JNIEnv,
+    callback: impl AsRef<JObject<'a>>,
+    result: &FetchResult,
+) -> Result<(), jni::errors::Error> {
+    let err = result.error().map(ToString::to_string).unwrap_or_default();
+    let name = env.new_string(result.name())?;
+    let date = env.new_string(result.date())?;
+    let err_msg = env.new_string(err)?;
+    env.call_method(
+        callback,
+        "onFail",
+        "(JLjava/lang/String;Ljava/lang/String;Ljava/lang/String;)V",
+        &[
+            JValueGen::Long(result.id() as jlong),
+            JValueGen::Object(&name),
+            JValueGen::Object(&date),
+            JValueGen::Object(&err_msg),
+        ],
+    )?;
+    Ok(())
+}
+
+fn fetched_files_to_jobjectArray<'a>(
+    env: &mut JNIEnv<'a>,
+    class: &GlobalRef,
+    files: &[FetchedFile],
+) -> Result<JObjectArray<'a>, jni::errors::Error> {
+    let class: &JClass = class.as_obj().into();
+    let j_files = env.new_object_array(files.len() as i32, class, JObject::null())?;
+    for (i, file) in files.iter().enumerate() {
+        let path = env.new_string(file.path().to_string_lossy())?;
+        let args: [JValue; 10] = [
+            JValueGen::Object(&path),
+            JValueGen::Long(file.size() as jlong),
+            JValueGen::Int(u8::from(file.version()) as jint),
+            JValueGen::Int(u8::from(file.compress()) as jint),
+            JValueGen::Int(u8::from(file.cipher()) as jint),
+            JValueGen::Int(file.key_fingerprint() as jint),
+            JValueGen::Long(unix_millis(file.created_time())),
+            JValueGen::Long(unix_millis(file.rotated_time())),
+            JValueGen::Long(file.records() as jlong),
+            JValueGen::Bool(file.is_active() as jboolean),
+        ];
+        let j_file = env.new_object(class, "(Ljava/lang/String;JIIIIJJJZ)V", &args)?;
+        env.set_object_array_element(&j_files, i as i32, j_file)?;
+    }
+    Ok(j_files)
+}
+
+#[inline]
+fn from_unix_millis(millis: jlong) -> Option<OffsetDateTime> {
+    OffsetDateTime::from_unix_timestamp_nanos((millis as i128) * 1_000_000).ok()
+}
+
+#[inline]
+fn unix_millis(time: OffsetDateTime) -> jlong {
+    (time.unix_timestamp_nanos() / 1_000_000) as jlong
 }
 
 #[inline]
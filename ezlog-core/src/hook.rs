@@ -0,0 +1,270 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::OnceLock,
+    thread,
+};
+
+use crossbeam_channel::Sender;
+use parking_lot::Mutex;
+
+#[cfg(feature = "sign")]
+use crate::manifest::Manifest;
+use crate::{
+    errors::LogError,
+    events::event,
+    EZLogConfig,
+    Event,
+    Header,
+    Result,
+};
+
+/// Hook called after the log file is rotated, it runs on the `ezlog_rotate` thread
+///
+/// the file is renamed, sealed if the signing key is set, and recorded in the manifest before the
+/// hook is called. it can compress, move or sign the file, the error is reported by
+/// [Event::RotateHookError].
+/// the file is not removed by [crate::EZLogConfigBuilder::max_total_size] until the hook returns.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+///
+/// use ezlog::{
+///     Header,
+///     LogError,
+///     RotateHook,
+/// };
+///
+/// struct UploadQueue;
+///
+/// impl RotateHook for UploadQueue {
+///     fn on_rotate(&self, name: &str, path: &Path, header: &Header) -> Result<(), LogError> {
+///         println!(
+///             "{} rotated {:?} created at {}",
+///             name,
+///             path,
+///             header.timestamp()
+///         );
+///         Ok(())
+///     }
+/// }
+///
+/// ezlog::InitBuilder::new()
+///     .with_rotate_hook(UploadQueue)
+///     .init();
+/// ```
+pub trait RotateHook: Send + Sync {
+    fn on_rotate(
+        &self,
+        name: &str,
+        path: &Path,
+        header: &Header,
+    ) -> std::result::Result<(), LogError>;
+}
+
+type RotateFn = dyn Fn(&str, &Path, &Header) -> Result<()> + Send + Sync;
+
+pub(crate) struct RotateHookFn {
+    op: Box<RotateFn>,
+}
+
+impl RotateHookFn {
+    pub(crate) fn new(
+        op: impl Fn(&str, &Path, &Header) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        Self { op: Box::new(op) }
+    }
+}
+
+impl RotateHook for RotateHookFn {
+    fn on_rotate(&self, name: &str, path: &Path, header: &Header) -> Result<()> {
+        (self.op)(name, path, header)
+    }
+}
+
+static ROTATE_HOOK: OnceLock<Box<dyn RotateHook>> = OnceLock::new();
+static ROTATE_SENDER: OnceLock<Sender<(EZLogConfig, PathBuf)>> = OnceLock::new();
+/// The rotated files not sealed or hooked yet
+static PENDING: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Set the hook called on the `ezlog_rotate` thread, the hook can be set only once
+pub(crate) fn set_rotate_hook(hook: Box<dyn RotateHook>) {
+    if ROTATE_HOOK.set(hook).is_err() {
+        event!(Event::RotateHookError, "rotate hook is already set");
+    }
+}
+
+/// Send the rotated file to the `ezlog_rotate` thread to be sealed and hooked
+///
+/// nothing happens if neither the signing key nor the hook is set
+pub(crate) fn on_rotate(config: &EZLogConfig, path: &Path) {
+    #[cfg(feature = "sign")]
+    let need_seal = config.signing_key().is_some();
+    #[cfg(not(feature = "sign"))]
+    let need_seal = false;
+    if !need_seal && ROTATE_HOOK.get().is_none() {
+        return;
+    }
+    PENDING.lock().push(path.to_path_buf());
+    rotate_sender()
+        .try_send((config.clone(), path.to_path_buf()))
+        .unwrap_or_else(|e| {
+            done(path);
+            crate::report_channel_send_err(e)
+        });
+}
+
+/// Whether the rotated file is still waiting to be sealed or hooked, it must not be removed
+pub(crate) fn is_pending(path: &Path) -> bool {
+    PENDING.lock().iter().any(|p| p == path)
+}
+
+fn done(path: &Path) {
+    PENDING.lock().retain(|p| p != path);
+}
+
+fn rotate_sender() -> &'static Sender<(EZLogConfig, PathBuf)> {
+    ROTATE_SENDER.get_or_init(|| {
+        let (sender, receiver) = crossbeam_channel::unbounded::<(EZLogConfig, PathBuf)>();
+        match thread::Builder::new()
+            .name("ezlog_rotate".to_string())
+            .spawn(move || {
+                for (config, path) in receiver {
+                    #[cfg(feature = "sign")]
+                    seal(&config, &path);
+                    if let Some(hook) = ROTATE_HOOK.get() {
+                        call_hook(hook.as_ref(), config.name(), &path);
+                    }
+                    done(&path);
+                }
+            }) {
+            Ok(_) => event!(Event::Init, "init rotate thread success"),
+            Err(e) => event!(!Event::InitError, "init rotate thread err"; &e.into()),
+        }
+        sender
+    })
+}
+
+/// Seal the rotated file and update its size in the manifest
+#[cfg(feature = "sign")]
+fn seal(config: &EZLogConfig, path: &Path) {
+    let Some(signing_key) = config.signing_key() else {
+        return;
+    };
+    crate::sign::seal_file(path, signing_key)
+        .and_then(|_| Manifest::update_size(config, path))
+        .unwrap_or_else(|e| event!(!Event::RotateFileError, "seal file error"; &e));
+}
+
+/// Seal the rotated files in the manifest which are not sealed, like the process exits before
+/// they are sealed on the `ezlog_rotate` thread
+#[cfg(feature = "sign")]
+pub(crate) fn reseal(config: &EZLogConfig) {
+    if config.signing_key().is_none() {
+        return;
+    }
+    let entries = match config.manifest_entries() {
+        Ok(entries) => entries,
+        Err(e) => {
+            event!(!Event::RotateFileError, "load manifest to reseal"; &e);
+            return;
+        }
+    };
+    for entry in entries {
+        let path = config.dir_path().join(entry.file_name());
+        if is_pending(&path) {
+            continue;
+        }
+        match crate::sign::is_sealed(&path) {
+            Ok(true) => {}
+            Ok(false) => {
+                event!(Event::RotateFile, "reseal {}", path.display());
+                seal(config, &path);
+            }
+            Err(e) => event!(!Event::RotateFileError, "read sealed file {}", path.display(); &e),
+        }
+    }
+}
+
+fn call_hook(hook: &dyn RotateHook, name: &str, path: &Path) {
+    event!(Event::RotateHook, "{} {:?}", name, path);
+    let result = File::open(path)
+        .map_err(LogError::from)
+        .and_then(|file| Header::decode(&mut BufReader::new(file)))
+        .and_then(|header| hook.on_rotate(name, path, &header));
+    match result {
+        Ok(()) => event!(Event::RotateHookEnd, "{} {:?}", name, path),
+        Err(e) => event!(!Event::RotateHookError, "{} {:?}", name, path; &e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        EZLogConfigBuilder,
+        EZLogger,
+        EZRecord,
+    };
+
+    #[test]
+    fn test_rotate_hook() {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let (release, released) = crossbeam_channel::unbounded::<()>();
+        set_rotate_hook(Box::new(RotateHookFn::new(move |name, path, header| {
+            // other tests rotate their files too
+            if name == "rotate_hook" {
+                sender
+                    .send((path.to_path_buf(), header.has_record()))
+                    .unwrap_or_default();
+                released.recv_timeout(Duration::from_secs(10)).ok();
+            }
+            Ok(())
+        })));
+
+        let dir = test_compat::test_path().join("rotate_hook");
+        std::fs::remove_dir_all(&dir).unwrap_or_default();
+        let config = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("rotate_hook")
+            .max_size(1024)
+            .max_total_size(1024)
+            .build();
+        let logger = EZLogger::new(config).unwrap();
+        logger
+            .append(EZRecord::builder().content("rotate hook").build())
+            .unwrap();
+        logger.rotate_if_not_empty().unwrap();
+
+        let (path, has_record) = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(has_record);
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("rotate_hook_"));
+        // the file is not removed by the total size limit until the hook returns
+        assert!(is_pending(&path));
+        logger.trim_total_size();
+        assert!(path.exists());
+
+        release.send(()).unwrap();
+        for _ in 0..500 {
+            if !is_pending(&path) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        logger.trim_total_size();
+        assert!(!path.exists());
+        drop(logger);
+        std::fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+}
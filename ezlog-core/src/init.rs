@@ -2,13 +2,16 @@ use std::time::Duration;
 
 use crate::{
     event,
+    hook::RotateHookFn,
     EZLogCallback,
     EZMsg,
     EZRecord,
     EventListener,
     EventPrinter,
     Formatter,
+    Header,
     LogService,
+    RotateHook,
 };
 
 /// InitBuilder is used to init ezlog
@@ -18,6 +21,7 @@ pub struct InitBuilder {
     layers: Vec<Box<dyn MsgHandler + Send + Sync>>,
     callback: Option<Box<dyn EZLogCallback>>,
     formatter: Option<Box<dyn Formatter>>,
+    rotate_hook: Option<Box<dyn RotateHook>>,
 }
 
 impl InitBuilder {
@@ -28,6 +32,7 @@ impl InitBuilder {
             layers: vec![],
             callback: None,
             formatter: None,
+            rotate_hook: None,
         }
     }
 
@@ -206,6 +211,34 @@ impl InitBuilder {
         self
     }
 
+    /// set a hook called with the sealed file after every rotation, see [RotateHook]
+    pub fn with_rotate_hook(mut self, hook: impl RotateHook + 'static) -> Self {
+        self.rotate_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// set a hook called with the sealed file after every rotation
+    ///
+    /// # Example
+    /// ```
+    /// ezlog::InitBuilder::new()
+    ///     .with_rotate_hook_fn(|name, path, _header| {
+    ///         println!("{} rotated to {:?}", name, path);
+    ///         Ok(())
+    ///     })
+    ///     .init();
+    /// ```
+    pub fn with_rotate_hook_fn(
+        mut self,
+        op: impl Fn(&str, &std::path::Path, &Header) -> std::result::Result<(), crate::LogError>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.rotate_hook = Some(Box::new(RotateHookFn::new(op)));
+        self
+    }
+
     /// real init ezlog
     pub fn init(self) -> EZLog {
         let log_service = crate::LOG_SERVICE.get_or_init(|| {
@@ -235,6 +268,10 @@ impl InitBuilder {
             if let Some(formatter) = self.formatter {
                 crate::set_boxed_formatter(formatter);
             }
+
+            if let Some(hook) = self.rotate_hook {
+                crate::hook::set_rotate_hook(hook);
+            }
             LogService::new(self.layers)
         });
 
@@ -0,0 +1,206 @@
+use std::io::{
+    Read,
+    Write,
+};
+
+use byteorder::{
+    BigEndian,
+    ReadBytesExt,
+    WriteBytesExt,
+};
+
+#[cfg(feature = "kdf")]
+use crate::errors::LogError;
+
+/// Salt length of a generated salt
+#[cfg(feature = "kdf")]
+pub(crate) const KDF_SALT_LEN: usize = 16;
+
+/// Key derivation function kind current support
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub enum KdfKind {
+    ARGON2ID,
+    PBKDF2SHA256,
+    UNKNOWN,
+}
+
+impl From<u8> for KdfKind {
+    fn from(orig: u8) -> Self {
+        match orig {
+            0x01 => KdfKind::ARGON2ID,
+            0x02 => KdfKind::PBKDF2SHA256,
+            _ => KdfKind::UNKNOWN,
+        }
+    }
+}
+
+impl From<KdfKind> for u8 {
+    fn from(orig: KdfKind) -> Self {
+        match orig {
+            KdfKind::ARGON2ID => 0x01,
+            KdfKind::PBKDF2SHA256 => 0x02,
+            KdfKind::UNKNOWN => 0xff,
+        }
+    }
+}
+
+/// Parameters to derive the cipher key from a passphrase
+///
+/// The parameters and the salt are recorded in the log file header,
+/// so the decoder can derive the same key from the same passphrase.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct KdfParams {
+    pub(crate) kind: KdfKind,
+    /// argon2 memory size in KiB, unused by pbkdf2
+    pub(crate) m_cost: u32,
+    /// argon2 iterations or pbkdf2 rounds
+    pub(crate) t_cost: u32,
+    /// argon2 parallelism, unused by pbkdf2
+    pub(crate) p_cost: u32,
+    /// empty salt means generate a random one when the logger is created
+    pub(crate) salt: Vec<u8>,
+}
+
+impl KdfParams {
+    pub fn argon2id(m_cost: u32, t_cost: u32, p_cost: u32) -> Self {
+        KdfParams {
+            kind: KdfKind::ARGON2ID,
+            m_cost,
+            t_cost,
+            p_cost,
+            salt: Vec::new(),
+        }
+    }
+
+    pub fn pbkdf2_sha256(rounds: u32) -> Self {
+        KdfParams {
+            kind: KdfKind::PBKDF2SHA256,
+            m_cost: 0,
+            t_cost: rounds,
+            p_cost: 0,
+            salt: Vec::new(),
+        }
+    }
+
+    /// Use a fixed salt instead of a random one
+    pub fn salt(mut self, salt: impl AsRef<[u8]>) -> Self {
+        self.salt = salt.as_ref().to_vec();
+        self
+    }
+
+    pub fn kind(&self) -> KdfKind {
+        self.kind
+    }
+
+    /// Same algorithm and costs, the salt is not compared
+    #[cfg(feature = "kdf")]
+    pub(crate) fn is_same_cost(&self, other: &KdfParams) -> bool {
+        self.kind == other.kind
+            && self.m_cost == other.m_cost
+            && self.t_cost == other.t_cost
+            && self.p_cost == other.p_cost
+    }
+
+    pub(crate) fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        writer.write_u8(self.kind.into())?;
+        writer.write_u32::<BigEndian>(self.m_cost)?;
+        writer.write_u32::<BigEndian>(self.t_cost)?;
+        writer.write_u32::<BigEndian>(self.p_cost)?;
+        writer.write_u8(self.salt.len() as u8)?;
+        writer.write_all(&self.salt)
+    }
+
+    pub(crate) fn decode(reader: &mut dyn Read) -> std::io::Result<Self> {
+        let kind = KdfKind::from(reader.read_u8()?);
+        let m_cost = reader.read_u32::<BigEndian>()?;
+        let t_cost = reader.read_u32::<BigEndian>()?;
+        let p_cost = reader.read_u32::<BigEndian>()?;
+        let mut salt = vec![0u8; reader.read_u8()? as usize];
+        reader.read_exact(&mut salt)?;
+        Ok(KdfParams {
+            kind,
+            m_cost,
+            t_cost,
+            p_cost,
+            salt,
+        })
+    }
+
+    /// Derive a `len` bytes key from the passphrase
+    #[cfg(feature = "kdf")]
+    pub fn derive_key(&self, passphrase: &[u8], len: usize) -> crate::Result<Vec<u8>> {
+        let mut key = vec![0u8; len];
+        match self.kind {
+            KdfKind::ARGON2ID => {
+                let params = argon2::Params::new(self.m_cost, self.t_cost, self.p_cost, Some(len))
+                    .map_err(|e| LogError::Crypto(format!("argon2 params invalid {}", e)))?;
+                argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                    .hash_password_into(passphrase, &self.salt, &mut key)
+                    .map_err(|e| LogError::Crypto(format!("argon2 derive error {}", e)))?;
+            }
+            KdfKind::PBKDF2SHA256 => {
+                if self.t_cost == 0 {
+                    return Err(LogError::Crypto("pbkdf2 rounds must not be 0".to_string()));
+                }
+                pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase, &self.salt, self.t_cost, &mut key);
+            }
+            KdfKind::UNKNOWN => {
+                return Err(LogError::Crypto("unknown kdf kind".to_string()));
+            }
+        }
+        Ok(key)
+    }
+}
+
+impl Default for KdfParams {
+    /// Argon2id with the OWASP recommended minimum cost
+    fn default() -> Self {
+        KdfParams::argon2id(19 * 1024, 2, 1)
+    }
+}
+
+#[cfg(feature = "kdf")]
+pub(crate) fn random_salt() -> crate::Result<Vec<u8>> {
+    let mut salt = vec![0u8; KDF_SALT_LEN];
+    getrandom::getrandom(&mut salt)
+        .map_err(|e| LogError::Crypto(format!("generate salt error {}", e)))?;
+    Ok(salt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kdf_params_encode_decode() {
+        let params = KdfParams::argon2id(64, 1, 1).salt(b"0123456789abcdef");
+        let mut buf = Vec::new();
+        params.encode(&mut buf).unwrap();
+        let decoded = KdfParams::decode(&mut buf.as_slice()).unwrap();
+        assert_eq!(params, decoded);
+    }
+
+    #[test]
+    #[cfg(feature = "kdf")]
+    fn test_derive_key() {
+        let argon2 = KdfParams::argon2id(64, 1, 1).salt(b"0123456789abcdef");
+        let key = argon2.derive_key(b"passphrase", 32).unwrap();
+        assert_eq!(key.len(), 32);
+        assert_eq!(key, argon2.derive_key(b"passphrase", 32).unwrap());
+        assert_ne!(key, argon2.derive_key(b"other", 32).unwrap());
+
+        let pbkdf2 = KdfParams::pbkdf2_sha256(1000).salt(b"0123456789abcdef");
+        let key = pbkdf2.derive_key(b"passphrase", 16).unwrap();
+        assert_eq!(key.len(), 16);
+        assert_ne!(
+            key,
+            pbkdf2
+                .clone()
+                .salt(b"fedcba9876543210")
+                .derive_key(b"passphrase", 16)
+                .unwrap()
+        );
+    }
+}
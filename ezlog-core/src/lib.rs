@@ -43,16 +43,33 @@
 //! ```
 
 mod appender;
+mod chain;
 mod compress;
 mod config;
 #[allow(deprecated)]
 mod crypto;
 mod errors;
 mod events;
+#[cfg(feature = "export")]
+mod export;
+#[cfg(feature = "decode")]
+mod fetch;
+mod hook;
 mod init;
+mod kdf;
 mod logger;
+mod manifest;
 mod recorder;
+mod redact;
+mod registry;
+mod request;
+mod ring;
+mod secret;
+#[cfg(feature = "sign")]
+mod sign;
 mod thread_name;
+#[cfg(feature = "upload")]
+mod upload;
 
 #[cfg(feature = "decode")]
 pub mod decode;
@@ -66,6 +83,8 @@ mod ffi_java;
 
 use core::fmt;
 use std::collections::VecDeque;
+#[cfg(feature = "export")]
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::{
     LazyLock,
@@ -79,42 +98,96 @@ use std::{
     io::{
         self,
         Cursor,
-        Read,
         Write,
     },
     sync::Once,
     thread,
+    time::Instant,
 };
 
 use crossbeam_channel::{
+    RecvError,
+    RecvTimeoutError,
     Sender,
     TrySendError,
 };
 use memmap2::MmapMut;
 use parking_lot::Mutex;
+use time::format_description::well_known::Rfc3339;
 use time::Duration;
 use time::OffsetDateTime;
 
+pub use self::chain::chain_mac;
+pub use self::chain::verify_chain;
+pub use self::chain::ChainBreak;
+pub use self::chain::ChainMac;
+pub use self::chain::ChainSummary;
+pub use self::chain::CHAIN_MAC_LEN;
 pub use self::compress::CompressKind;
 pub use self::compress::CompressLevel;
+pub use self::config::AppenderKind;
+pub use self::config::Durability;
 pub use self::config::EZLogConfig;
 pub use self::config::EZLogConfigBuilder;
 pub use self::config::Level;
+pub use self::crypto::key_fingerprint;
 pub use self::crypto::CipherKind;
 pub use self::errors::LogError;
 pub(crate) use self::events::event;
 pub use self::events::Event;
 pub use self::events::EventListener;
 pub use self::events::EventPrinter;
+#[cfg(feature = "export")]
+pub use self::export::ExportManifest;
+#[cfg(feature = "export")]
+pub use self::export::ExportedFile;
+pub use self::hook::RotateHook;
 pub use self::init::InitBuilder;
 pub use self::init::MsgHandler;
+pub use self::kdf::KdfKind;
+pub use self::kdf::KdfParams;
 pub use self::logger::create_compress;
 pub use self::logger::create_cryptor;
 pub use self::logger::EZLogger;
 pub use self::logger::Header;
+pub use self::manifest::FetchedFile;
+pub use self::manifest::ManifestEntry;
 pub use self::recorder::EZRecord;
 pub use self::recorder::EZRecordBuilder;
+pub use self::redact::RedactAction;
+pub use self::redact::RedactRule;
+pub use self::redact::REDACT_MASK;
+pub use self::registry::is_compress_registered;
+pub use self::registry::is_cryptor_registered;
+pub use self::registry::register_compress;
+pub use self::registry::register_cryptor;
+pub use self::registry::CompressFactory;
+pub use self::registry::CryptorFactory;
+pub use self::registry::CUSTOM_ID_END;
+pub use self::registry::CUSTOM_ID_START;
+pub use self::request::FetchFuture;
+pub use self::ring::RingState;
+pub use self::secret::SecretBytes;
+#[cfg(feature = "sign")]
+pub use self::sign::public_key;
+#[cfg(feature = "sign")]
+pub use self::sign::verify_signature;
+#[cfg(feature = "sign")]
+pub use self::sign::SIGNATURE_FOOTER_LEN;
+#[cfg(feature = "upload")]
+pub use self::upload::UploadAction;
+#[cfg(feature = "upload")]
+pub use self::upload::UploadItem;
+#[cfg(feature = "upload")]
+pub use self::upload::UploadQueue;
+#[cfg(feature = "upload")]
+pub use self::upload::UploadQueueBuilder;
+#[cfg(feature = "upload")]
+pub use self::upload::UploadStatus;
+#[cfg(feature = "upload")]
+pub use self::upload::Uploader;
 use crate::init::dispatch_cache_records;
+use crate::request::Completion;
 
 /// A [EZLogger] default name. current is "default".
 pub const DEFAULT_LOG_NAME: &str = "default";
@@ -135,6 +208,10 @@ pub(crate) const MIN_LOG_SIZE: u64 = 100;
 /// Log file fixed header length.
 pub const V1_LOG_HEADER_SIZE: usize = 10;
 pub const V2_LOG_HEADER_SIZE: usize = 22;
+/// V3 header fixed part, followed by the extensions.
+pub const V3_LOG_HEADER_SIZE: usize = 24;
+/// Log file header max length, include the extensions.
+pub const MAX_LOG_HEADER_SIZE: usize = 256;
 
 const MAX_PRE_INIT_QUEUE_SIZE: usize = 64;
 
@@ -217,18 +294,28 @@ impl LogService {
                 if let Some(logger) = map.get(&task.name) {
                     // Perform operations with the logger
                     let now = OffsetDateTime::now_utc();
-                    if (now < task.end || now < task.start + Duration::days(1)) && now > task.start
-                    {
+                    let includes_now = if task.by_day {
+                        now < task.end || now < task.start + Duration::days(1)
+                    } else {
+                        logger
+                            .current_file_time()
+                            .is_ok_and(|created| created < task.end)
+                    };
+                    if includes_now && now > task.start {
                         logger.rotate_if_not_empty().unwrap_or_else(|e| {
                             error = Some(e);
                         });
                     }
 
-                    let days = (task.end - task.start).whole_days();
-                    for day in 0..=days {
-                        let mut query =
-                            logger.query_log_files_for_date(task.start + Duration::days(day));
-                        logs.append(&mut query);
+                    if task.by_day {
+                        let days = (task.end - task.start).whole_days();
+                        for day in 0..=days {
+                            let mut query =
+                                logger.query_log_files_for_date(task.start + Duration::days(day));
+                            logs.append(&mut query);
+                        }
+                    } else {
+                        logs = logger.query_log_files_in_range(task.start, task.end);
                     }
                 } else {
                     error = Some(LogError::Illegal(format!("{} Logger not found", task.name)));
@@ -238,14 +325,113 @@ impl LogService {
                 error = Some(e);
             });
 
+        let mut result_logs = Some(logs);
+        let mut skipped = Vec::new();
+        if error.is_none() && task.mode != FetchMode::Files {
+            match self.extract_records(
+                &task,
+                result_logs.as_deref().unwrap_or_default(),
+                &mut skipped,
+            ) {
+                Ok(extracted) => result_logs = Some(extracted.into_iter().collect()),
+                Err(e) => {
+                    result_logs = None;
+                    error = Some(e);
+                }
+            }
+        }
+
+        let fetched = result_logs.map(|logs| self.fetched_files(&task.name, logs, &mut skipped));
+        let files = match fetched {
+            Some(Ok(files)) => Some(files),
+            Some(Err(e)) => {
+                error = Some(e);
+                None
+            }
+            None => None,
+        };
+
+        let date = if task.by_day {
+            task.start.date().to_string()
+        } else {
+            format!(
+                "{}/{}",
+                task.start.format(&Rfc3339).unwrap_or_default(),
+                task.end.format(&Rfc3339).unwrap_or_default(),
+            )
+        };
         self.on_fetch(FetchResult {
+            id: task.id,
+            completion: task.completion,
             name: task.name,
-            date: task.start.date().to_string(),
-            logs: Some(logs),
+            date,
+            logs: files,
+            skipped,
             error,
         })
     }
 
+    /// Read the metadata of the fetched files, the removed or unreadable files are skipped
+    fn fetched_files(
+        &self,
+        name: &str,
+        logs: Vec<PathBuf>,
+        skipped: &mut Vec<PathBuf>,
+    ) -> crate::Result<Vec<FetchedFile>> {
+        let map = self.loggers_read()?;
+        let logger = map
+            .get(name)
+            .ok_or_else(|| LogError::Illegal(format!("{} Logger not found", name)))?;
+        let active = logger.current_file_path().ok();
+        let entries = logger.config.manifest_entries().unwrap_or_default();
+        let mut files = Vec::with_capacity(logs.len());
+        for path in logs {
+            let entry = entries
+                .iter()
+                .find(|e| path.file_name().and_then(|n| n.to_str()) == Some(e.file_name()));
+            match FetchedFile::from_file(&path, entry, active.as_ref() == Some(&path)) {
+                Ok(file) => files.push(file),
+                Err(e) => {
+                    event!(!Event::RequestLogError, "read fetched file {:?}", &path; &e);
+                    skipped.push(path);
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// Extract the records in the range of the fetched files
+    fn extract_records(
+        &self,
+        task: &FetchReq,
+        logs: &[PathBuf],
+        skipped: &mut Vec<PathBuf>,
+    ) -> crate::Result<Option<PathBuf>> {
+        #[cfg(feature = "decode")]
+        {
+            let map = self.loggers_read()?;
+            let logger = map
+                .get(&task.name)
+                .ok_or_else(|| LogError::Illegal(format!("{} Logger not found", task.name)))?;
+            fetch::extract_records(
+                &logger.config,
+                logs,
+                task.start,
+                task.end,
+                task.mode,
+                skipped,
+            )
+        }
+        #[cfg(not(feature = "decode"))]
+        {
+            let _ = (logs, skipped);
+            Err(LogError::Illegal(format!(
+                "fetch {:?} requires the decode feature",
+                task.mode
+            )))
+        }
+    }
+
     fn on_fetch(&self, result: FetchResult) -> crate::Result<()> {
         self.fetch_sender.try_send(result).map_err(|e| e.into())
     }
@@ -288,6 +474,30 @@ impl LogService {
         })
     }
 
+    fn rotate_key(
+        &self,
+        name: impl AsRef<str>,
+        key: SecretBytes,
+        nonce: SecretBytes,
+    ) -> crate::Result<()> {
+        self.loggers_write().and_then(|mut map| {
+            map.get_mut(name.as_ref())
+                .map(|v| v.rotate_key(key, nonce))
+                .unwrap_or_else(|| Err(LogError::Illegal("Logger not found".into())))
+        })
+    }
+
+    /// Flush the logger and return its config
+    #[cfg(feature = "export")]
+    fn flushed_config(&self, name: impl AsRef<str>) -> crate::Result<EZLogConfig> {
+        let map = self.loggers_read()?;
+        let logger = map
+            .get(name.as_ref())
+            .ok_or_else(|| LogError::Illegal(format!("{} Logger not found", name.as_ref())))?;
+        logger.flush()?;
+        Ok(logger.config.clone())
+    }
+
     fn flush_all(&self) -> crate::Result<()> {
         self.loggers_read()?
             .values()
@@ -296,6 +506,34 @@ impl LogService {
             .map(|_| ())
     }
 
+    /// Flush the loggers whose [Durability::Interval] flush is due, returns the next due time
+    ///
+    /// the loggers are checked only when the earliest scheduled flush is due
+    fn flush_due(&self) -> Option<Instant> {
+        let now = Instant::now();
+        if !logger::take_due_flush(now) {
+            return logger::next_flush();
+        }
+        let next = self
+            .loggers_read()
+            .ok()?
+            .values()
+            .filter_map(|logger| logger.flush_if_due(now))
+            .min();
+        if let Some(time) = next {
+            logger::schedule_flush(time);
+        }
+        logger::next_flush()
+    }
+
+    fn rotate(&self, name: impl AsRef<str>) -> crate::Result<()> {
+        self.loggers_read().and_then(|map| {
+            map.get(name.as_ref())
+                .map(|v| v.rotate_if_not_empty())
+                .unwrap_or_else(|| Err(LogError::Illegal("Logger not found".into())))
+        })
+    }
+
     fn trim(&self) -> crate::Result<()> {
         self.loggers_read()?
             .values()
@@ -335,7 +573,13 @@ fn init_log_channel() -> Sender<EZMsg> {
     match thread::Builder::new()
         .name("ezlog_task".to_string())
         .spawn(move || loop {
-            match receiver.recv() {
+            // wait for the message until the next interval flush, see [Durability::Interval]
+            let next_flush = LOG_SERVICE.get().and_then(LogService::flush_due);
+            let received = match next_flush {
+                Some(deadline) => receiver.recv_deadline(deadline),
+                None => receiver.recv().map_err(RecvTimeoutError::from),
+            };
+            match received {
                 Ok(msg) => match msg {
                     EZMsg::CreateLogger(config) => {
                         let name = config.name().to_string();
@@ -377,6 +621,12 @@ fn init_log_channel() -> Sender<EZMsg> {
                             event!(!Event::FlushError; &e);
                         });
                     }
+                    EZMsg::RotateKey(name, key, nonce) => {
+                        match LOG_SERVICE.wait().rotate_key(&name, key, nonce) {
+                            Ok(_) => event!(Event::RotateKeyEnd, name),
+                            Err(e) => event!(!Event::RotateKeyError, "rotate key of {}", name; &e),
+                        }
+                    }
                     EZMsg::FlushAll() => {
                         LOG_SERVICE.wait().flush_all().unwrap_or_else(|e| {
                             event!(!Event::FlushError; &e);
@@ -396,8 +646,9 @@ fn init_log_channel() -> Sender<EZMsg> {
                         call();
                     }
                 },
-                Err(err) => {
-                    event!(!Event::ChannelError, "log channel rec error"; &err.into());
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    event!(!Event::ChannelError, "log channel rec error"; &RecvError.into());
                 }
             }
         }) {
@@ -486,6 +737,35 @@ pub fn flush(log_name: impl AsRef<str>) {
     post_msg(msg);
 }
 
+/// Rotate the cipher key and nonce of the [EZLogger]
+///
+/// the current log file is renamed, the following records are written to a new file
+/// with the new key. the key material is zeroized after use.
+pub fn rotate_key(
+    log_name: impl AsRef<str>,
+    key: impl Into<SecretBytes>,
+    nonce: impl Into<SecretBytes>,
+) {
+    let msg = EZMsg::RotateKey(log_name.as_ref().to_owned(), key.into(), nonce.into());
+    event!(Event::RotateKey, log_name.as_ref());
+    post_msg(msg);
+}
+
+/// Rotate the current log file of the [EZLogger] if it has records
+///
+/// the following records are written to a new file, the rotated file is sealed and passed to the
+/// [RotateHook] on the `ezlog_rotate` thread.
+pub fn rotate(log_name: impl AsRef<str>) {
+    let name = log_name.as_ref().to_owned();
+    event!(Event::RotateFile, &name);
+    post_msg(EZMsg::Action(Box::new(move || {
+        LOG_SERVICE
+            .wait()
+            .rotate(&name)
+            .unwrap_or_else(|e| event!(!Event::RotateFileError, "rotate {}", &name; &e));
+    })));
+}
+
 /// Flush all log files
 pub fn flush_all() {
     event!(Event::Flush);
@@ -500,9 +780,190 @@ pub fn request_log_files_for_date(
     end: OffsetDateTime,
 ) {
     let req = FetchReq {
+        id: request::next_request_id(),
+        name: log_name.as_ref().to_owned(),
+        start,
+        end,
+        by_day: true,
+        mode: FetchMode::Files,
+        completion: None,
+    };
+    post_msg(EZMsg::FetchLog(req));
+}
+
+/// Request the logs in the time range `[start, end)` which [EZLogger]'s name is define in the parameter
+///
+/// the log files are selected by the creation time in header and the rotation time in manifest,
+/// the current file is rotated if it overlaps the range.
+/// with [FetchMode::Decoded] or [FetchMode::Encoded], only the records in the range are written to
+/// one file in the `fetch` dir of the log dir. remove it after use, the decoded records are plain
+/// text. it is removed by the trim after the trim duration, and counted in the max total size.
+/// the record time is read from the RFC 3339 prefix of the default formatter, a record of a
+/// custom [Formatter] has the time of the previous record, or the creation time of the file.
+/// the files encrypted by another key are reported by [FetchResult::skipped].
+///
+/// the `date` of the callback is the range in RFC 3339, like `2020-01-01T14:00:00Z/2020-01-01T14:30:00Z`
+pub fn request_logs_in_range(
+    log_name: impl AsRef<str>,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    mode: FetchMode,
+) {
+    let req = FetchReq {
+        id: request::next_request_id(),
+        name: log_name.as_ref().to_owned(),
+        start,
+        end,
+        by_day: false,
+        mode,
+        completion: None,
+    };
+    post_msg(EZMsg::FetchLog(req));
+}
+
+/// Request the logs in the time range `[start, end)`, the result is passed to `on_complete` instead
+/// of the global [EZLogCallback]
+///
+/// returns the request id, it is the same as [FetchResult::id].
+/// see [request_logs_in_range] for the range and the mode.
+/// `on_complete` is always called once, with an error result if the request is dropped, like it
+/// is requested before init or the channel is full.
+///
+/// # Examples
+/// ```no_run
+/// # use ezlog::FetchMode;
+/// # use time::{Duration, OffsetDateTime};
+/// let end = OffsetDateTime::now_utc();
+/// let start = end - Duration::minutes(30);
+/// ezlog::request_log_files("default", start, end, FetchMode::Files, |result| {
+///     println!("{} {:?}", result.id(), result.files());
+/// });
+/// ```
+pub fn request_log_files<F>(
+    log_name: impl AsRef<str>,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    mode: FetchMode,
+    on_complete: F,
+) -> u64
+where
+    F: FnOnce(FetchResult) + Send + 'static,
+{
+    let id = request::next_request_id();
+    let completion = Completion::new(id, log_name.as_ref(), on_complete);
+    post_fetch(id, log_name, start, end, mode, completion);
+    id
+}
+
+/// Request the logs in the time range `[start, end)`, returns a [FetchFuture] of the result
+///
+/// the future can be awaited, or waited on by [FetchFuture::wait].
+pub fn fetch_log_files(
+    log_name: impl AsRef<str>,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    mode: FetchMode,
+) -> FetchFuture {
+    let (future, completion) = FetchFuture::new(request::next_request_id(), log_name.as_ref());
+    post_fetch(future.id(), log_name, start, end, mode, completion);
+    future
+}
+
+/// Export the logs of the logger in `[start, end)` to a tar file at `dest`, for a support ticket
+///
+/// the bundle has the rotated files overlapping the range, a copy of the current file, and a
+/// `manifest.json` of their headers, extras, key fingerprints and time spans. the export runs on
+/// the log thread after the records sent before it, so no file is appended or rotated meanwhile.
+/// it blocks until the export is done, do not call it in the log thread.
+///
+/// # Examples
+/// ```no_run
+/// use time::{
+///     Duration,
+///     OffsetDateTime,
+/// };
+///
+/// let now = OffsetDateTime::now_utc();
+/// let start = now - Duration::hours(2);
+/// let manifest = ezlog::export("default", start, now, "/tmp/default.tar").unwrap();
+/// println!("{} files exported", manifest.files().len());
+/// ```
+#[cfg(feature = "export")]
+pub fn export(
+    log_name: impl AsRef<str>,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    dest: impl AsRef<Path>,
+) -> Result<ExportManifest> {
+    post_export(log_name, start, end, false, dest)
+}
+
+/// Export like [export], the records in `[start, end)` are decoded and redacted by the rules of
+/// the logger
+#[cfg(feature = "export")]
+pub fn export_decoded(
+    log_name: impl AsRef<str>,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    dest: impl AsRef<Path>,
+) -> Result<ExportManifest> {
+    post_export(log_name, start, end, true, dest)
+}
+
+/// Export the logs in the dir of the config without the log service, see [export]
+#[cfg(feature = "export")]
+pub fn export_with_config(
+    config: &EZLogConfig,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    decoded: bool,
+    dest: impl AsRef<Path>,
+) -> Result<ExportManifest> {
+    export::export(config, start, end, decoded, dest.as_ref())
+}
+
+#[cfg(feature = "export")]
+fn post_export(
+    log_name: impl AsRef<str>,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    decoded: bool,
+    dest: impl AsRef<Path>,
+) -> Result<ExportManifest> {
+    if LOG_SERVICE.get().is_none() {
+        return Err(LogError::NotInit);
+    }
+    let name = log_name.as_ref().to_owned();
+    let dest = dest.as_ref().to_owned();
+    let (sender, receiver) = crossbeam_channel::bounded::<Result<ExportManifest>>(1);
+    post_msg(EZMsg::Action(Box::new(move || {
+        let result = LOG_SERVICE
+            .wait()
+            .flushed_config(&name)
+            .and_then(|config| export::export(&config, start, end, decoded, &dest));
+        sender.send(result).unwrap_or_default();
+    })));
+    receiver.recv().map_err(|_| {
+        LogError::Illegal("export request is dropped before it is completed".to_string())
+    })?
+}
+
+fn post_fetch(
+    id: u64,
+    log_name: impl AsRef<str>,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    mode: FetchMode,
+    completion: Completion,
+) {
+    let req = FetchReq {
+        id,
         name: log_name.as_ref().to_owned(),
         start,
         end,
+        by_day: false,
+        mode,
+        completion: Some(completion),
     };
     post_msg(EZMsg::FetchLog(req));
 }
@@ -524,26 +985,26 @@ pub(crate) fn report_channel_send_err<T>(err: TrySendError<T>) {
     event!(!Event::ChannelError, "channel send err"; &err.into());
 }
 
-fn invoke_fetch_callback(result: FetchResult) {
+fn invoke_fetch_callback(mut result: FetchResult) {
+    if let Some(completion) = result.completion.take() {
+        event!(Event::RequestLogEnd, "fetch completed: {}", result.id);
+        match completion.complete(result) {
+            Some(called) => result = called,
+            None => return,
+        }
+    }
     let name = &result.name;
     let date = &result.date;
     match result.logs {
-        Some(logs) => {
+        Some(files) => {
             event!(
                 Event::RequestLogEnd,
                 "fetch completed: {} {} {}",
                 name,
                 date,
-                logs.len()
-            );
-            callback().on_fetch_success(
-                name,
-                date,
-                &logs
-                    .iter()
-                    .map(|l| l.to_str().unwrap_or(""))
-                    .collect::<Vec<&str>>(),
+                files.len()
             );
+            callback().on_fetch_files(name, date, &files);
         }
         None => {
             if let Some(err) = result.error {
@@ -588,6 +1049,19 @@ pub(crate) fn callback() -> &'static dyn EZLogCallback {
 pub trait EZLogCallback {
     fn on_fetch_success(&self, name: &str, date: &str, logs: &[&str]);
     fn on_fetch_fail(&self, name: &str, date: &str, err: &str);
+
+    /// Called with the metadata of the fetched files, it calls [EZLogCallback::on_fetch_success]
+    /// with the paths by default
+    fn on_fetch_files(&self, name: &str, date: &str, files: &[FetchedFile]) {
+        self.on_fetch_success(
+            name,
+            date,
+            &files
+                .iter()
+                .map(|f| f.path().to_str().unwrap_or(""))
+                .collect::<Vec<&str>>(),
+        );
+    }
 }
 
 /// Set the boxed [EZLogCallback]
@@ -633,10 +1107,14 @@ impl EZLogCallback for EZLogCallbackFn {
     }
 }
 
+// `CreateLogger` is public, boxing the config would break the users who build or match it. the
+// size only costs the slots of the bounded log channel
+#[allow(clippy::large_enum_variant)]
 pub enum EZMsg {
     CreateLogger(EZLogConfig),
     Record(EZRecord),
     ForceFlush(String),
+    RotateKey(String, SecretBytes, SecretBytes),
     FlushAll(),
     Trim(),
     FetchLog(FetchReq),
@@ -649,6 +1127,7 @@ impl fmt::Debug for EZMsg {
             EZMsg::CreateLogger(cfg) => f.debug_tuple("CreateLogger").field(cfg).finish(),
             EZMsg::Record(rec) => f.debug_tuple("Record").field(rec).finish(),
             EZMsg::ForceFlush(name) => f.debug_tuple("ForceFlush").field(name).finish(),
+            EZMsg::RotateKey(name, _, _) => f.debug_tuple("RotateKey").field(name).finish(),
             EZMsg::FlushAll() => f.write_str("FlushAll"),
             EZMsg::Trim() => f.write_str("Trim"),
             EZMsg::FetchLog(req) => f.debug_tuple("FetchLog").field(req).finish(),
@@ -664,9 +1143,36 @@ impl fmt::Debug for EZMsg {
 /// task_sender: channel sender for fetch result
 #[derive(Debug, Clone)]
 pub struct FetchReq {
+    id: u64,
     name: String,
     start: OffsetDateTime,
     end: OffsetDateTime,
+    /// query the whole days of the range
+    by_day: bool,
+    mode: FetchMode,
+    /// called with the result instead of the global callback
+    completion: Option<Completion>,
+}
+
+/// What is returned by [request_logs_in_range]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchMode {
+    /// the log files overlapping the range
+    Files,
+    /// a plain text file of the records in the range, needs the `decode` feature
+    Decoded,
+    /// a log file of the records in the range, encoded by the logger's config, needs the `decode` feature
+    Encoded,
+}
+
+impl From<u8> for FetchMode {
+    fn from(orig: u8) -> Self {
+        match orig {
+            0x01 => FetchMode::Decoded,
+            0x02 => FetchMode::Encoded,
+            _ => FetchMode::Files,
+        }
+    }
 }
 
 /// # Fetch Logs file‘s path result.
@@ -675,16 +1181,71 @@ pub struct FetchReq {
 /// logs maybe None if no logs write at the date.
 #[derive(Debug)]
 pub struct FetchResult {
+    /// request id
+    id: u64,
+    completion: Option<Completion>,
     /// logger's name
     name: String,
-    /// request date in string, like "2020_01_01"
+    /// request date in string, like "2020-01-01", or the range in RFC 3339 of [request_logs_in_range]
     date: String,
-    /// logs file's path
-    logs: Option<Vec<PathBuf>>,
+    /// fetched log files
+    logs: Option<Vec<FetchedFile>>,
+    /// the files not fetched, removed, encrypted by another key or broken
+    skipped: Vec<PathBuf>,
     /// error message
     error: Option<LogError>,
 }
 
+impl FetchResult {
+    /// The result of a request failed before it is fetched
+    pub(crate) fn failed(id: u64, name: impl Into<String>, error: LogError) -> Self {
+        FetchResult {
+            id,
+            completion: None,
+            name: name.into(),
+            date: String::new(),
+            logs: None,
+            skipped: Vec::new(),
+            error: Some(error),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn date(&self) -> &str {
+        &self.date
+    }
+
+    pub fn files(&self) -> Option<&[FetchedFile]> {
+        self.logs.as_deref()
+    }
+
+    /// The files in the range which are not fetched, like the files removed during the fetch or
+    /// encrypted by the key before [rotate_key], see [Event::RequestLogError] for the reason
+    pub fn skipped(&self) -> &[PathBuf] {
+        &self.skipped
+    }
+
+    pub fn error(&self) -> Option<&LogError> {
+        self.error.as_ref()
+    }
+
+    /// The fetched files, or the error if fetch failed
+    pub fn into_files(self) -> Result<Vec<FetchedFile>> {
+        match (self.logs, self.error) {
+            (Some(files), _) => Ok(files),
+            (None, Some(error)) => Err(error),
+            (None, None) => Ok(Vec::new()),
+        }
+    }
+}
+
 /// The Logger struct to implement the Log encode.
 /// Compress function abstract
 pub trait Compression {
@@ -785,6 +1346,7 @@ pub enum Version {
     NONE,
     V1,
     V2,
+    V3,
     UNKNOWN,
 }
 
@@ -793,6 +1355,7 @@ impl From<u8> for Version {
         match v {
             1 => Version::V1,
             2 => Version::V2,
+            3 => Version::V3,
             0 => Version::NONE,
             _ => Version::UNKNOWN,
         }
@@ -804,6 +1367,7 @@ impl From<Version> for u8 {
         match v {
             Version::V1 => 1,
             Version::V2 => 2,
+            Version::V3 => 3,
             Version::UNKNOWN => u8::MAX,
             Version::NONE => 0,
         }
@@ -976,4 +1540,65 @@ mod tests {
         std::fs::remove_dir_all(dir_clone).unwrap();
         assert!(count == 1)
     }
+
+    #[test]
+    fn test_fetched_files_skip_removed() {
+        let dir = test_compat::test_path().join("fetch_skip");
+        std::fs::remove_dir_all(&dir).unwrap_or_default();
+        crate::InitBuilder::new().debug(true).init();
+        crate::create_log(
+            EZLogConfigBuilder::new()
+                .dir_path(&dir)
+                .name("fetch_skip")
+                .build(),
+        );
+        let (tx, rx) = crossbeam_channel::bounded::<()>(1);
+        crate::post_msg(crate::EZMsg::Action(Box::new(move || {
+            tx.send(()).unwrap_or_default();
+        })));
+        rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+
+        let active = dir.join("fetch_skip.mmap");
+        let removed = dir.join("fetch_skip_2020_01_01_00_00.1.mmap");
+        let mut skipped = Vec::new();
+        let files = crate::LOG_SERVICE
+            .wait()
+            .fetched_files("fetch_skip", vec![active, removed.clone()], &mut skipped)
+            .unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].is_active());
+        assert_eq!(skipped, vec![removed]);
+        std::fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[cfg(feature = "export")]
+    #[test]
+    fn test_export_on_log_thread() {
+        let dir = test_compat::test_path().join("export_service");
+        std::fs::remove_dir_all(&dir).unwrap_or_default();
+        crate::InitBuilder::new().debug(true).init();
+        crate::create_log(
+            EZLogConfigBuilder::new()
+                .dir_path(&dir)
+                .name("export_service")
+                .build(),
+        );
+        crate::log(
+            EZRecordBuilder::new()
+                .log_name("export_service")
+                .content("export record")
+                .build(),
+        );
+        let start = OffsetDateTime::now_utc() - time::Duration::hours(1);
+        let end = OffsetDateTime::now_utc() + time::Duration::hours(1);
+        let manifest =
+            crate::export_decoded("export_service", start, end, dir.join("export.tar")).unwrap();
+        assert_eq!(manifest.files().len(), 1);
+        assert_eq!(manifest.files()[0].records(), 1);
+        let bundle = std::fs::read(dir.join("export.tar")).unwrap();
+        assert!(String::from_utf8_lossy(&bundle).contains("export record"));
+
+        assert!(crate::export("export_missing", start, end, dir.join("missing.tar")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap_or_default();
+    }
 }
@@ -5,6 +5,7 @@ use std::path::PathBuf;
 use std::{
     fs,
     io,
+    time::Instant,
 };
 
 use byteorder::ReadBytesExt;
@@ -13,7 +14,11 @@ use byteorder::{
     WriteBytesExt,
 };
 use integer_encoding::VarIntWriter;
-use time::OffsetDateTime;
+use parking_lot::Mutex;
+use time::{
+    OffsetDateTime,
+    UtcOffset,
+};
 
 #[cfg(feature = "decode")]
 use crate::crypto::{
@@ -23,6 +28,8 @@ use crate::crypto::{
 use crate::crypto::{
     Aes128GcmSiv,
     Aes256GcmSiv,
+    ChaCha20Poly1305,
+    XChaCha20Poly1305,
 };
 use crate::events::Event::{
     self,
@@ -41,10 +48,26 @@ use crate::{
     RECORD_SIGNATURE_START,
 };
 use crate::{
+    chain,
     errors,
     event,
+    manifest::Manifest,
+    redact,
+    registry,
+    ring::{
+        self,
+        RING_STATE_LEN,
+    },
+    ChainMac,
+    Durability,
+    KdfParams,
+    Level,
     NonceGenFn,
+    RingState,
+    SecretBytes,
+    MAX_LOG_HEADER_SIZE,
     V1_LOG_HEADER_SIZE,
+    V3_LOG_HEADER_SIZE,
 };
 use crate::{
     Version,
@@ -74,8 +97,14 @@ pub(crate) fn encode_content(mut buf: Vec<u8>) -> Result<Vec<u8>> {
 
 #[allow(deprecated)]
 pub fn create_cryptor(config: &EZLogConfig) -> Result<Option<Box<dyn Cryptor + Send + Sync>>> {
-    if let Some(key) = &config.cipher_key() {
-        if let Some(nonce) = &config.cipher_nonce() {
+    if let CipherKind::CUSTOM(id) = config.cipher_kind() {
+        // custom cryptor may hold its own key material
+        let key = config.cipher_key().unwrap_or_default();
+        let nonce = config.cipher_nonce().unwrap_or_default();
+        return registry::create_cryptor(id, key, nonce).map(Some);
+    }
+    if let Some(key) = config.cipher_key() {
+        if let Some(nonce) = config.cipher_nonce() {
             #[warn(unreachable_patterns)]
             match config.cipher_kind() {
                 #[cfg(feature = "decode")]
@@ -96,6 +125,14 @@ pub fn create_cryptor(config: &EZLogConfig) -> Result<Option<Box<dyn Cryptor + S
                     let encryptor = Aes256GcmSiv::new(key, nonce)?;
                     Ok(Some(Box::new(encryptor)))
                 }
+                CipherKind::CHACHA20POLY1305 => {
+                    let encryptor = ChaCha20Poly1305::new(key, nonce)?;
+                    Ok(Some(Box::new(encryptor)))
+                }
+                CipherKind::XCHACHA20POLY1305 => {
+                    let encryptor = XChaCha20Poly1305::new(key, nonce)?;
+                    Ok(Some(Box::new(encryptor)))
+                }
                 CipherKind::NONE => Ok(None),
                 unknown => Err(LogError::Crypto(format!("unknown cryption {}", unknown))),
             }
@@ -110,16 +147,46 @@ pub fn create_cryptor(config: &EZLogConfig) -> Result<Option<Box<dyn Cryptor + S
 pub fn create_compress(config: &EZLogConfig) -> Option<Box<dyn Compress + Send + Sync>> {
     match config.compress_kind() {
         CompressKind::ZLIB => Some(Box::new(ZlibCodec::new(&config.compress_level()))),
+        CompressKind::CUSTOM(id) => registry::create_compress(id, config.compress_level()),
         CompressKind::NONE => None,
         CompressKind::UNKNOWN => None,
     }
 }
 
+/// The earliest scheduled flush of all loggers, the log thread waits until it
+static NEXT_FLUSH: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Schedule the flush at `time` if it is earlier than the scheduled one
+pub(crate) fn schedule_flush(time: Instant) {
+    let mut next = NEXT_FLUSH.lock();
+    if next.is_none_or(|next| time < next) {
+        *next = Some(time);
+    }
+}
+
+/// Clear the scheduled flush if it is due at `now`, returns whether it is cleared
+pub(crate) fn take_due_flush(now: Instant) -> bool {
+    let mut next = NEXT_FLUSH.lock();
+    let due = next.is_some_and(|time| time <= now);
+    if due {
+        *next = None;
+    }
+    due
+}
+
+pub(crate) fn next_flush() -> Option<Instant> {
+    *NEXT_FLUSH.lock()
+}
+
 pub struct EZLogger {
     pub(crate) config: EZLogConfig,
     pub(crate) appender: EZAppender,
     pub(crate) compression: Option<Box<dyn Compress + Send + Sync>>,
     pub(crate) cryptor: Option<Box<dyn Cryptor + Send + Sync>>,
+    /// last record MAC of the hash chain, `None` if the chain key is not set
+    pub(crate) chain_tail: Option<Mutex<ChainMac>>,
+    /// the time to sync the records written since the last flush, see [Durability::Interval]
+    pub(crate) next_flush: Mutex<Option<Instant>>,
 }
 
 /// log result
@@ -131,20 +198,63 @@ pub(crate) enum AppendSuccess {
 
 impl EZLogger {
     pub fn new(config: EZLogConfig) -> Result<Self> {
+        #[cfg(feature = "kdf")]
+        let config = {
+            let mut config = config;
+            config.derive_cipher_key()?;
+            config
+        };
         let appender = EZAppender::new(&config)?;
+        #[cfg(feature = "sign")]
+        crate::hook::reseal(&config);
+        // the current file may be rotated by the config, read its tail first
+        let chain_tail = match config.chain_key() {
+            Some(_) => {
+                let path = appender.get_inner()?.file_path().clone();
+                let tail = chain::last_mac(&fs::read(path)?).ok().flatten();
+                Some(Mutex::new(tail.unwrap_or_default()))
+            }
+            None => None,
+        };
         appender.check_config_rolling(&config)?;
         let compression = create_compress(&config);
+        if compression.is_none() && matches!(config.compress_kind(), CompressKind::CUSTOM(_)) {
+            return Err(LogError::Compress(io::Error::other(format!(
+                "compress {:?} is not registered",
+                config.compress_kind()
+            ))));
+        }
         let cryptor = create_cryptor(&config)?;
-        Ok(Self {
+        let logger = Self {
             config,
             appender,
             compression,
             cryptor,
-        })
+            chain_tail,
+            next_flush: Mutex::new(None),
+        };
+        logger.link_chain()?;
+        logger.trim_total_size();
+        Ok(logger)
+    }
+
+    /// Rotate the current log file, the new file is encrypted by the new key and nonce.
+    ///
+    /// the old key material is zeroized on drop, and nothing changes if the new cryptor can not be created.
+    pub(crate) fn rotate_key(&mut self, key: SecretBytes, nonce: SecretBytes) -> Result<()> {
+        let mut config = self.config.clone();
+        config.replace_cipher_key(key, nonce);
+        let cryptor = create_cryptor(&config)?;
+        self.rotate_appender(&config)?;
+        self.cryptor = cryptor;
+        self.config = config;
+        Ok(())
     }
 
     /// TODO buggy add test case
-    pub(crate) fn append(&self, record: EZRecord) -> Result<AppendSuccess> {
+    pub(crate) fn append(&self, mut record: EZRecord) -> Result<AppendSuccess> {
+        record.set_offset(self.config.time_offset());
+        let level = record.level();
         let mut rotate = false;
         let splits = if record.content().len() > self.config.max_size() as usize / 2 {
             record.trunks(&self.config)
@@ -153,10 +263,11 @@ impl EZLogger {
         };
         for record in splits.iter() {
             let id = record.t_id();
-            let buf = self.encode_as_block(record)?;
+            let (buf, mac) = self.encode_as_chained_block(record)?;
             let result = { self.appender.get_inner_mut()?.append(&buf) };
             match result {
                 Ok(_) => {
+                    self.advance_chain(mac);
                     event!(Event::RecordEnd, &id);
                 }
                 Err(e) => {
@@ -164,10 +275,24 @@ impl EZLogger {
                     // Check if the error is an appender error (e.g., file is full or needs rotation)
                     if let Some(is_rotation_error) = self.is_rotation_needed(&e) {
                         if is_rotation_error {
-                            // Rotate the appender and retry
-                            self.appender.rotate(&self.config).inspect_err(
-                                |e| event!(!Event::RotateFileError, "rotate error"; e),
-                            )?;
+                            let (buf, mac) = if self.config.is_ring_buffer() {
+                                // Wrap the ring buffer, the position in nonce is changed, so encode again
+                                self.appender
+                                    .get_inner_mut()?
+                                    .wrap()
+                                    .map_err(LogError::from)
+                                    .inspect_err(
+                                        |e| event!(!Event::WrapRing, "wrap ring error"; e),
+                                    )?;
+                                event!(Event::WrapRing, &id);
+                                self.encode_as_chained_block(record)?
+                            } else {
+                                // Rotate the appender and retry
+                                self.rotate_appender(&self.config).inspect_err(
+                                    |e| event!(!Event::RotateFileError, "rotate error"; e),
+                                )?;
+                                (buf, mac)
+                            };
                             // Retry write once after rotation
                             let retry_result = {
                                 let mut inner = self.appender.get_inner_mut()?;
@@ -175,6 +300,7 @@ impl EZLogger {
                             };
                             match retry_result {
                                 Ok(_) => {
+                                    self.advance_chain(mac);
                                     event!(Event::RecordEnd, &id);
                                     rotate = true;
                                 }
@@ -188,6 +314,7 @@ impl EZLogger {
                 }
             }
         }
+        self.sync_by_durability(level);
         if rotate {
             Ok(AppendSuccess::RotatedAndRetried)
         } else {
@@ -195,43 +322,92 @@ impl EZLogger {
         }
     }
 
+    /// Flush after the record is appended or schedule the flush, see [Durability]
+    fn sync_by_durability(&self, level: Level) {
+        let sync = match self.config.durability() {
+            Durability::None => false,
+            Durability::Interval(duration) => {
+                let mut next = self.next_flush.lock();
+                if next.is_none() {
+                    let time = Instant::now()
+                        + std::time::Duration::try_from(duration).unwrap_or_default();
+                    *next = Some(time);
+                    schedule_flush(time);
+                }
+                false
+            }
+            Durability::OnLevel(min) => level <= min,
+            Durability::EveryRecord => true,
+        };
+        if sync {
+            self.flush()
+                .unwrap_or_else(|e| event!(!Event::FlushError, "durability flush"; &e));
+        }
+    }
+
+    /// Flush if the scheduled flush is due, returns the next scheduled time
+    pub(crate) fn flush_if_due(&self, now: Instant) -> Option<Instant> {
+        let next = *self.next_flush.lock();
+        match next {
+            Some(time) if time <= now => {
+                self.flush()
+                    .unwrap_or_else(|e| event!(!Event::FlushError, "interval flush"; &e));
+                None
+            }
+            _ => next,
+        }
+    }
+
     #[inline]
     fn encode(&self, record: &EZRecord) -> Result<Vec<u8>> {
+        let buf = self.format(record)?;
+        self.encode_formatted(&record.t_id(), buf)
+    }
+
+    /// Redact, compress and encrypt the formatted record
+    fn encode_formatted(&self, id: &str, mut buf: Vec<u8>) -> Result<Vec<u8>> {
         let nonce_fn: NonceGenFn = self.gen_nonce()?;
-        let mut buf = self.format(record)?;
         if buf.is_empty() {
             return Ok(buf);
         }
+        let rules = self.config.redact_rules();
+        if !rules.is_empty() {
+            let (redacted, count) = redact::redact(rules, &String::from_utf8_lossy(&buf));
+            if count > 0 {
+                event!(Event::Redact, "{} redacted {}", id, count);
+                buf = redacted.into_bytes();
+            }
+        }
         if self.config.version() == Version::V1 {
             if let Some(encryptor) = &self.cryptor {
-                event!(Event::Encrypt, &record.t_id());
+                event!(Event::Encrypt, id);
                 buf = encryptor.encrypt(&buf, nonce_fn)?;
-                event!(Event::EncryptEnd, &record.t_id());
+                event!(Event::EncryptEnd, id);
             }
             if let Some(compression) = &self.compression {
-                event!(Event::Compress, &record.t_id());
+                event!(Event::Compress, id);
                 buf = compression.compress(&buf).map_err(LogError::Compress)?;
-                event!(Event::CompressEnd, &record.t_id());
+                event!(Event::CompressEnd, id);
             }
         } else {
             let len = buf.len();
             if let Some(compression) = &self.compression {
-                event!(Event::Compress, &record.t_id());
+                event!(Event::Compress, id);
                 buf = compression.compress(&buf).map_err(LogError::Compress)?;
                 event!(
                     Event::CompressEnd,
                     "{} compress ratio = {} ",
-                    &record.t_id(),
+                    id,
                     buf.len() as f64 / len as f64
                 );
             }
             if let Some(encryptor) = &self.cryptor {
-                event!(Event::Encrypt, &record.t_id());
+                event!(Event::Encrypt, id);
                 buf = encryptor.encrypt(&buf, nonce_fn)?;
                 event!(
                     Event::EncryptEnd,
                     "{} process ratio = {} ",
-                    &record.t_id(),
+                    id,
                     buf.len() as f64 / len as f64
                 );
             }
@@ -251,16 +427,71 @@ impl EZLogger {
         let inner = self.appender.inner.read()?;
         let timestamp = inner.header().timestamp.unix_timestamp();
         let position = inner.header().recorder_position;
-        let combine = combine_time_position(timestamp, position.into());
+        let lap = inner.header().ring.map(|ring| ring.lap);
+        let combine = ring::nonce_combine(timestamp, position.into(), lap);
 
         // create and return a closure that XORs each input slice with the count
         Ok(Box::new(move |input| xor_slice(input, &combine)))
     }
 
+    /// Append the formatted record as is, it is used to write the fetched records
+    #[cfg(feature = "decode")]
+    pub(crate) fn append_formatted(&self, id: &str, buf: Vec<u8>) -> Result<()> {
+        let buf = encode_content(self.encode_formatted(id, buf)?)?;
+        self.appender.get_inner_mut()?.append(&buf)?;
+        Ok(())
+    }
+
     #[inline]
     pub fn encode_as_block(&self, record: &EZRecord) -> Result<Vec<u8>> {
-        let buf = self.encode(record)?;
-        encode_content(buf)
+        self.encode_as_chained_block(record).map(|(block, _)| block)
+    }
+
+    /// Encode the record as block, the chain MAC is appended to the content if the chain key is set
+    fn encode_as_chained_block(&self, record: &EZRecord) -> Result<(Vec<u8>, Option<ChainMac>)> {
+        let mut buf = self.encode(record)?;
+        let mac = match (self.config.chain_key(), &self.chain_tail) {
+            (Some(key), Some(tail)) => {
+                let mac = chain::chain_mac(key, &tail.lock(), &buf)?;
+                buf.extend_from_slice(&mac);
+                Some(mac)
+            }
+            _ => None,
+        };
+        Ok((encode_content(buf)?, mac))
+    }
+
+    #[inline]
+    fn advance_chain(&self, mac: Option<ChainMac>) {
+        if let (Some(tail), Some(mac)) = (&self.chain_tail, mac) {
+            *tail.lock() = mac;
+        }
+    }
+
+    /// Rotate the log file, the new file is linked to the chain tail
+    fn rotate_appender(&self, config: &EZLogConfig) -> Result<()> {
+        self.appender.rotate(config)?;
+        self.link_chain()?;
+        self.trim_total_size();
+        Ok(())
+    }
+
+    /// Record the chain tail in the header of the current file if it has no record yet
+    fn link_chain(&self) -> Result<()> {
+        let Some(tail) = &self.chain_tail else {
+            return Ok(());
+        };
+        let tail = *tail.lock();
+        let mut inner = self.appender.get_inner_mut()?;
+        let header = inner.header();
+        if header.chain_prev.is_some()
+            && header.chain_prev != Some(tail)
+            && !header.has_record_exclude_extra(&self.config)
+        {
+            inner.header_mut().chain_prev = Some(tail);
+            inner.write_header_to_log()?;
+        }
+        Ok(())
     }
 
     fn format(&self, record: &EZRecord) -> Result<Vec<u8>> {
@@ -268,6 +499,7 @@ impl EZLogger {
     }
 
     pub(crate) fn flush(&self) -> crate::Result<()> {
+        *self.next_flush.lock() = None;
         self.appender
             .get_inner_mut()?
             .flush()
@@ -275,37 +507,115 @@ impl EZLogger {
     }
 
     pub(crate) fn trim(&self) {
-        match fs::read_dir(self.config.dir_path()) {
-            Ok(dir) => {
-                for file in dir {
-                    match file {
-                        Ok(file) => {
-                            if let Some(name) = file.file_name().to_str() {
-                                match self.config.is_file_out_of_date(name) {
-                                    Ok(out_of_date) => {
-                                        if out_of_date {
-                                            fs::remove_file(file.path()).unwrap_or_else(|e| {
-                                                event!(
-                                                    !Event::TrimError,
-                                                    "remove file err";
-                                                    &e.into()
-                                                )
-                                            });
-                                        }
-                                    }
-                                    Err(e) => {
-                                        event!(!Event::TrimError, "judge file out of date error"; &e)
-                                    }
-                                }
-                            };
-                        }
-                        Err(e) => {
-                            event!(!Event::TrimError, "traversal file error"; &e.into())
-                        }
+        match self.config.repair_manifest() {
+            Ok(entries) => {
+                let now = OffsetDateTime::now_utc();
+                let mut removed = Vec::new();
+                for entry in entries {
+                    if !self.config.is_out_of_date(entry.start(), now) {
+                        continue;
                     }
+                    let path = self.config.dir_path().join(entry.file_name());
+                    match fs::remove_file(&path) {
+                        Ok(_) => removed.push(path),
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => removed.push(path),
+                        Err(e) => event!(!Event::TrimError, "remove file err"; &e.into()),
+                    }
+                }
+                if !removed.is_empty() {
+                    Manifest::remove(&self.config, &removed)
+                        .unwrap_or_else(|e| event!(!Event::TrimError, "update manifest error"; &e));
                 }
             }
-            Err(e) => event!(!Event::TrimError, "read dir error"; &e.into()),
+            Err(e) => event!(!Event::TrimError, "load manifest error"; &e),
+        }
+        #[cfg(feature = "decode")]
+        self.trim_fetched();
+        self.trim_total_size();
+    }
+
+    /// Remove the out of date records files fetched in a time range, see [crate::FetchMode]
+    #[cfg(feature = "decode")]
+    fn trim_fetched(&self) {
+        let now = OffsetDateTime::now_utc();
+        for (path, _, modified) in crate::fetch::fetched_outputs(&self.config) {
+            if !self.config.is_out_of_date(modified, now) {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(_) => event!(Event::Trim, "remove fetched file {:?}", path),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => event!(!Event::TrimError, "remove fetched file err"; &e.into()),
+            }
+        }
+    }
+
+    /// Remove the oldest rotated files until the total size is not greater than the max total size
+    pub(crate) fn trim_total_size(&self) {
+        let Some(max_total_size) = self.config.max_total_size() else {
+            return;
+        };
+        let files = match self.config.rotated_log_files() {
+            Ok(files) => files,
+            Err(e) => {
+                event!(!Event::TrimError, "list rotated files error"; &e);
+                return;
+            }
+        };
+        // the fetched records files are the copies of the log files, they are removed first
+        #[cfg(feature = "decode")]
+        let fetched = crate::fetch::fetched_outputs(&self.config);
+        #[cfg(not(feature = "decode"))]
+        let fetched: Vec<(PathBuf, u64, OffsetDateTime)> = Vec::new();
+        let current = self
+            .appender
+            .get_inner()
+            .map(|inner| inner.file_len() as u64)
+            .unwrap_or_else(|_| self.config.max_size());
+        let mut total = current
+            + files.iter().map(|(_, len)| len).sum::<u64>()
+            + fetched.iter().map(|(_, len, _)| len).sum::<u64>();
+        for (path, len, _) in fetched {
+            if total <= max_total_size {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(_) => total -= len,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => total -= len,
+                Err(e) => event!(!Event::TrimError, "remove fetched file err"; &e.into()),
+            }
+        }
+        let mut removed = Vec::new();
+        for (path, len) in files {
+            if total <= max_total_size {
+                break;
+            }
+            // the file is still being sealed or hooked
+            if crate::hook::is_pending(&path) {
+                continue;
+            }
+            match fs::remove_file(&path) {
+                Ok(_) => {
+                    total -= len;
+                    removed.push(path);
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    total -= len;
+                    removed.push(path);
+                }
+                Err(e) => event!(!Event::TrimError, "remove file err"; &e.into()),
+            }
+        }
+        if !removed.is_empty() {
+            Manifest::remove(&self.config, &removed)
+                .unwrap_or_else(|e| event!(!Event::TrimError, "update manifest error"; &e));
+            event!(
+                Event::Trim,
+                "{} over max total size {}, removed {:?}",
+                self.config.name(),
+                max_total_size,
+                removed
+            );
         }
     }
 
@@ -313,6 +623,24 @@ impl EZLogger {
         self.config.query_log_files_for_date(date)
     }
 
+    pub fn query_log_files_in_range(
+        &self,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+    ) -> Vec<PathBuf> {
+        self.config.query_log_files_in_range(start, end)
+    }
+
+    /// The path of the current log file
+    pub(crate) fn current_file_path(&self) -> Result<PathBuf> {
+        Ok(self.appender.get_inner()?.file_path().clone())
+    }
+
+    /// The creation time of the current log file
+    pub(crate) fn current_file_time(&self) -> Result<OffsetDateTime> {
+        Ok(self.appender.get_inner()?.header().timestamp)
+    }
+
     pub(crate) fn rotate_if_not_empty(&self) -> Result<()> {
         if self
             .appender
@@ -320,7 +648,7 @@ impl EZLogger {
             .header()
             .has_record_exclude_extra(&self.config)
         {
-            self.appender.rotate(&self.config)
+            self.rotate_appender(&self.config)
         } else {
             Ok(())
         }
@@ -373,9 +701,20 @@ bitflags! {
     pub(crate) struct Flags: u8 {
         const NONE = 0b0000_0000;
         const HAS_EXTRA = 0b0000_0001;
+        const SEALED = 0b0000_0010;
     }
 }
 
+/// V3 header extension tag of the [KdfParams]
+pub(crate) const EXTENSION_KDF: u8 = 0x01;
+/// V3 header extension tag of the last [ChainMac] of the previous file
+pub(crate) const EXTENSION_CHAIN: u8 = 0x02;
+
+/// V3 header extension tag of the [RingState]
+pub(crate) const EXTENSION_RING: u8 = 0x03;
+/// V3 header extension tag of the local time offset in seconds, see [EZLogConfigBuilder::utc_offset]
+pub(crate) const EXTENSION_OFFSET: u8 = 0x04;
+
 /// EZLog file Header
 ///
 /// every log file starts with a header,
@@ -393,7 +732,7 @@ pub struct Header {
     pub(crate) compress: CompressKind,
     /// cipher kind
     pub(crate) cipher: CipherKind,
-    // config key and nonce hash
+    /// cipher key fingerprint, see [crate::key_fingerprint]
     pub(crate) cipher_hash: u32,
     /// timestamp
     #[cfg_attr(feature = "json", serde(serialize_with = "crate::serialize_time"))]
@@ -402,6 +741,18 @@ pub struct Header {
     /// rotate time
     #[cfg_attr(feature = "json", serde(skip))]
     pub(crate) rotate_time: Option<OffsetDateTime>,
+    /// key derivation params, V3 extension
+    pub(crate) kdf: Option<KdfParams>,
+    /// last record MAC of the previous file, V3 extension, see [crate::verify_chain]
+    pub(crate) chain_prev: Option<ChainMac>,
+    /// ring buffer offsets, V3 extension, see [crate::EZLogConfigBuilder::ring_buffer]
+    pub(crate) ring: Option<RingState>,
+    /// local time offset if it is not UTC, V3 extension, see [crate::EZLogConfigBuilder::utc_offset]
+    #[cfg_attr(feature = "json", serde(skip))]
+    pub(crate) utc_offset: Option<UtcOffset>,
+    /// extensions this version can not parse, kept as is
+    #[cfg_attr(feature = "json", serde(skip))]
+    pub(crate) unknown_extensions: Vec<u8>,
 }
 
 impl Default for Header {
@@ -428,6 +779,11 @@ impl Header {
             cipher_hash: 0,
             timestamp: OffsetDateTime::now_utc().replace_nanosecond(0).unwrap_or_else(|_| OffsetDateTime::now_utc()),
             rotate_time: None,
+            kdf: None,
+            chain_prev: None,
+            ring: None,
+            utc_offset: None,
+            unknown_extensions: Vec::new(),
         }
     }
 
@@ -441,6 +797,11 @@ impl Header {
             cipher_hash: 0,
             timestamp: OffsetDateTime::UNIX_EPOCH,
             rotate_time: None,
+            kdf: None,
+            chain_prev: None,
+            ring: None,
+            utc_offset: None,
+            unknown_extensions: Vec::new(),
         }
     }
 
@@ -460,13 +821,18 @@ impl Header {
             compress: config.compress_kind(),
             cipher: config.cipher_kind(),
             cipher_hash: config.cipher_hash(),
-            timestamp: time,
+            timestamp: time.to_offset(config.time_offset()),
             rotate_time: Some(rotate_time),
+            kdf: config.kdf(),
+            chain_prev: config.chain_key().map(|_| ChainMac::default()),
+            ring: config.is_ring_buffer().then(RingState::default),
+            utc_offset: (config.time_offset() != UtcOffset::UTC).then(|| config.time_offset()),
+            unknown_extensions: Vec::new(),
         }
     }
 
     pub fn max_length() -> usize {
-        V2_LOG_HEADER_SIZE
+        MAX_LOG_HEADER_SIZE
     }
 
     #[inline]
@@ -474,18 +840,25 @@ impl Header {
         match version {
             Version::V1 => V1_LOG_HEADER_SIZE,
             Version::V2 => V2_LOG_HEADER_SIZE,
+            Version::V3 => V3_LOG_HEADER_SIZE,
             _ => 0,
         }
     }
 
     pub fn length(&self) -> usize {
-        Self::length_compat(&self.version)
+        match self.version {
+            Version::V3 => {
+                V3_LOG_HEADER_SIZE + self.encode_extensions().map(|e| e.len()).unwrap_or(0)
+            }
+            _ => Self::length_compat(&self.version),
+        }
     }
 
     pub fn encode(&self, writer: &mut dyn Write) -> std::result::Result<(), io::Error> {
         match self.version {
             Version::V1 => self.encode_v1(writer),
             Version::V2 => self.encode_v2(writer),
+            Version::V3 => self.encode_v3(writer),
             _ => Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "unknown version",
@@ -513,6 +886,77 @@ impl Header {
         writer.write_u32::<BigEndian>(self.cipher_hash)
     }
 
+    pub fn encode_v3(&self, writer: &mut dyn Write) -> std::result::Result<(), io::Error> {
+        let extensions = self.encode_extensions()?;
+        self.encode_v2(writer)?;
+        writer.write_u16::<BigEndian>(extensions.len() as u16)?;
+        writer.write_all(&extensions)
+    }
+
+    /// Encode the V3 extensions, every entry is `tag(u8) + len(u8) + value`
+    fn encode_extensions(&self) -> std::result::Result<Vec<u8>, io::Error> {
+        let mut extensions = Vec::new();
+        if let Some(kdf) = &self.kdf {
+            let mut value = Vec::new();
+            kdf.encode(&mut value)?;
+            write_extension(&mut extensions, EXTENSION_KDF, &value)?;
+        }
+        if let Some(chain_prev) = &self.chain_prev {
+            write_extension(&mut extensions, EXTENSION_CHAIN, chain_prev)?;
+        }
+        if let Some(ring) = &self.ring {
+            let mut value = Vec::with_capacity(RING_STATE_LEN);
+            ring.encode(&mut value)?;
+            write_extension(&mut extensions, EXTENSION_RING, &value)?;
+        }
+        if let Some(offset) = &self.utc_offset {
+            write_extension(
+                &mut extensions,
+                EXTENSION_OFFSET,
+                &offset.whole_seconds().to_be_bytes(),
+            )?;
+        }
+        extensions.extend_from_slice(&self.unknown_extensions);
+        if V3_LOG_HEADER_SIZE + extensions.len() > MAX_LOG_HEADER_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("header extensions too long {}", extensions.len()),
+            ));
+        }
+        Ok(extensions)
+    }
+
+    fn decode_extensions(&mut self, mut extensions: &[u8]) -> std::result::Result<(), io::Error> {
+        while !extensions.is_empty() {
+            let tag = extensions.read_u8()?;
+            let len = extensions.read_u8()? as usize;
+            let value = extensions.get(..len).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::UnexpectedEof, "header extension truncated")
+            })?;
+            match tag {
+                EXTENSION_KDF => self.kdf = Some(KdfParams::decode(&mut &value[..])?),
+                EXTENSION_CHAIN => {
+                    self.chain_prev = Some(ChainMac::try_from(value).map_err(|_| {
+                        io::Error::new(io::ErrorKind::InvalidData, "chain mac length invalid")
+                    })?)
+                }
+                EXTENSION_RING => self.ring = Some(RingState::decode(&mut &value[..])?),
+                EXTENSION_OFFSET => {
+                    let offset =
+                        UtcOffset::from_whole_seconds((&value[..]).read_i32::<BigEndian>()?)
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    self.timestamp = self.timestamp.to_offset(offset);
+                    self.utc_offset = Some(offset);
+                }
+                _ => {
+                    write_extension(&mut self.unknown_extensions, tag, value)?;
+                }
+            }
+            extensions = &extensions[len..];
+        }
+        Ok(())
+    }
+
     pub fn decode_with_config(
         reader: &mut dyn Read,
         config: &EZLogConfig,
@@ -530,7 +974,7 @@ impl Header {
         let version = Version::from(reader.read_u8()?);
         let flag = Flags::from_bits(reader.read_u8()?).unwrap_or(Flags::NONE);
         let mut timestamp = OffsetDateTime::now_utc().unix_timestamp();
-        if version == Version::V2 {
+        if version == Version::V2 || version == Version::V3 {
             timestamp = reader.read_i64::<BigEndian>()?
         }
         let recorder_size = reader.read_u32::<BigEndian>()?;
@@ -539,10 +983,10 @@ impl Header {
         let cipher = reader.read_u8()?;
         let mut hash: u32 = 0;
 
-        if version == Version::V2 {
+        if version == Version::V2 || version == Version::V3 {
             hash = reader.read_u32::<BigEndian>()?;
         }
-        Ok(Header {
+        let mut header = Header {
             version,
             flag,
             recorder_position: recorder_size,
@@ -552,7 +996,18 @@ impl Header {
             timestamp: OffsetDateTime::from_unix_timestamp(timestamp)
                 .unwrap_or_else(|_| OffsetDateTime::now_utc()),
             rotate_time: None,
-        })
+            kdf: None,
+            chain_prev: None,
+            ring: None,
+            utc_offset: None,
+            unknown_extensions: Vec::new(),
+        };
+        if version == Version::V3 {
+            let mut extensions = vec![0u8; reader.read_u16::<BigEndian>()? as usize];
+            reader.read_exact(&mut extensions)?;
+            header.decode_extensions(&extensions)?;
+        }
+        Ok(header)
     }
 
     pub fn is_match(&self, config: &EZLogConfig) -> bool {
@@ -560,6 +1015,8 @@ impl Header {
             && self.compress == config.compress_kind()
             && self.cipher == config.cipher_kind()
             && self.cipher_hash == config.cipher_hash()
+            && self.chain_prev.is_some() == config.chain_key().is_some()
+            && self.ring.is_some() == config.is_ring_buffer()
     }
 
     pub fn is_none(&self) -> bool {
@@ -587,6 +1044,11 @@ impl Header {
         self.flag.contains(Flags::HAS_EXTRA)
     }
 
+    /// The file is finished and signed, see [crate::verify_signature]
+    pub fn is_sealed(&self) -> bool {
+        self.flag.contains(Flags::SEALED)
+    }
+
     #[inline]
     fn extra_len(&self, config: &EZLogConfig) -> usize {
         match &config.extra() {
@@ -606,11 +1068,67 @@ impl Header {
         &self.version
     }
 
+    pub fn compress(&self) -> CompressKind {
+        self.compress
+    }
+
+    pub fn cipher(&self) -> CipherKind {
+        self.cipher
+    }
+
+    pub fn cipher_hash(&self) -> u32 {
+        self.cipher_hash
+    }
+
+    /// The creation time of the file
+    pub fn timestamp(&self) -> OffsetDateTime {
+        self.timestamp
+    }
+
+    /// The time the file is planned to be rotated at
+    pub fn rotate_time(&self) -> Option<OffsetDateTime> {
+        self.rotate_time
+    }
+
+    pub fn kdf(&self) -> Option<&KdfParams> {
+        self.kdf.as_ref()
+    }
+
+    pub fn chain_prev(&self) -> Option<&ChainMac> {
+        self.chain_prev.as_ref()
+    }
+
+    pub fn ring(&self) -> Option<&RingState> {
+        self.ring.as_ref()
+    }
+
+    /// The local time offset of the logger, `None` if it is UTC or not recorded
+    pub fn utc_offset(&self) -> Option<UtcOffset> {
+        self.utc_offset
+    }
+
     pub(crate) fn init_record_position(&mut self) {
-        self.recorder_position = Self::length_compat(&self.version) as u32;
+        self.recorder_position = self.length() as u32;
     }
 }
 
+#[inline]
+fn write_extension(
+    writer: &mut dyn Write,
+    tag: u8,
+    value: &[u8],
+) -> std::result::Result<(), io::Error> {
+    let len = u8::try_from(value.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("header extension {:#04x} too long", tag),
+        )
+    })?;
+    writer.write_u8(tag)?;
+    writer.write_u8(len)?;
+    writer.write_all(value)
+}
+
 #[cfg(test)]
 mod tests {
     use std::{sync::mpsc::channel};
@@ -634,6 +1152,258 @@ mod tests {
         assert_eq!(buf.len(), V2_LOG_HEADER_SIZE);
     }
 
+    #[test]
+    fn test_header_encode_decode_v3() {
+        let mut header = Header::new();
+        header.version = Version::V3;
+        header.kdf = Some(KdfParams::argon2id(64, 1, 1).salt(b"0123456789abcdef"));
+        header.chain_prev = Some([0x5a; crate::CHAIN_MAC_LEN]);
+        header.unknown_extensions = vec![0x7f, 0x02, 0xaa, 0xbb];
+        let mut buf = Vec::new();
+        header.encode(&mut buf).unwrap();
+        assert_eq!(buf.len(), header.length());
+        assert!(header.length() > V3_LOG_HEADER_SIZE);
+        let decoded_header = Header::decode(&mut buf.as_slice()).unwrap();
+        assert_eq!(header, decoded_header);
+
+        header.unknown_extensions = vec![0u8; MAX_LOG_HEADER_SIZE];
+        assert!(header.encode(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_header_utc_offset() {
+        let offset = UtcOffset::from_hms(8, 0, 0).unwrap();
+        let config = crate::EZLogConfigBuilder::new()
+            .version(Version::V3)
+            .utc_offset(offset)
+            .build();
+        let header = Header::create(&config);
+        assert_eq!(header.utc_offset(), Some(offset));
+        let mut buf = Vec::new();
+        header.encode(&mut buf).unwrap();
+        let decoded_header = Header::decode(&mut buf.as_slice()).unwrap();
+        assert_eq!(decoded_header.utc_offset(), Some(offset));
+        assert_eq!(decoded_header.timestamp().offset(), offset);
+        assert_eq!(
+            decoded_header.timestamp().unix_timestamp(),
+            header.timestamp().unix_timestamp()
+        );
+
+        let header = Header::create(&EZLogConfig::default());
+        assert_eq!(header.utc_offset(), None);
+
+        // the record time is formatted in the offset
+        let test_dir = test_compat::test_path().join("utc_offset");
+        fs::remove_dir_all(&test_dir).unwrap_or_default();
+        let config = crate::EZLogConfigBuilder::new()
+            .dir_path(&test_dir)
+            .name("utc_offset")
+            .version(Version::V3)
+            .utc_offset(offset)
+            .build();
+        let logger = EZLogger::new(config.clone()).unwrap();
+        logger
+            .append(EZRecord::builder().content("local time").build())
+            .unwrap();
+        logger.flush().unwrap();
+        let (path, _mmap) = config.create_mmap_file().unwrap();
+        let buf = String::from_utf8_lossy(&fs::read(path).unwrap()).to_string();
+        assert!(buf.contains("+08:00 "));
+        drop(logger);
+        fs::remove_dir_all(&test_dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_durability() {
+        let test_dir = test_compat::test_path().join("durability");
+        fs::remove_dir_all(&test_dir).unwrap_or_default();
+        let builder = crate::EZLogConfigBuilder::new()
+            .dir_path(&test_dir)
+            .name("durability");
+        // the recorder position in the file is updated by the flush
+        let written = |config: &EZLogConfig| {
+            let (path, _mmap) = config.create_mmap_file().unwrap();
+            Header::decode(&mut fs::read(path).unwrap().as_slice())
+                .unwrap()
+                .recorder_position
+        };
+
+        let config = builder
+            .clone()
+            .durability(Durability::OnLevel(Level::Error))
+            .build();
+        let logger = EZLogger::new(config.clone()).unwrap();
+        let position = written(&config);
+        logger
+            .append(
+                EZRecord::builder()
+                    .level(Level::Info)
+                    .content("info")
+                    .build(),
+            )
+            .unwrap();
+        assert_eq!(written(&config), position);
+        logger
+            .append(
+                EZRecord::builder()
+                    .level(Level::Error)
+                    .content("error")
+                    .build(),
+            )
+            .unwrap();
+        let position = written(&config);
+        assert_eq!(
+            position,
+            logger
+                .appender
+                .get_inner()
+                .unwrap()
+                .header()
+                .recorder_position
+        );
+        drop(logger);
+
+        let config = builder
+            .clone()
+            .durability(Durability::Interval(time::Duration::milliseconds(10)))
+            .build();
+        let logger = EZLogger::new(config.clone()).unwrap();
+        let now = Instant::now();
+        assert_eq!(logger.flush_if_due(now), None);
+        logger
+            .append(EZRecord::builder().content("interval").build())
+            .unwrap();
+        let due = logger.flush_if_due(now).unwrap();
+        assert!(due > now);
+        // the log thread waits until the earliest flush of all loggers
+        assert!(next_flush().unwrap() <= due);
+        assert_eq!(written(&config), position);
+        assert_eq!(logger.flush_if_due(due), None);
+        assert!(written(&config) > position);
+        drop(logger);
+        fs::remove_dir_all(&test_dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_redact_before_write() {
+        let test_dir = test_compat::test_path().join("redact");
+        fs::remove_dir_all(&test_dir).unwrap_or_default();
+        let config = crate::EZLogConfigBuilder::new()
+            .dir_path(&test_dir)
+            .name("redact")
+            .redact(crate::RedactRule::literal("token-123"))
+            .redact(crate::RedactRule::literal("alice@example.com").hash(b"key".to_vec()))
+            .build();
+        let logger = EZLogger::new(config.clone()).unwrap();
+        logger
+            .append(
+                EZRecord::builder()
+                    .content("alice@example.com login with token-123")
+                    .build(),
+            )
+            .unwrap();
+        logger.flush().unwrap();
+
+        let (path, _mmap) = config.create_mmap_file().unwrap();
+        let buf = String::from_utf8_lossy(&fs::read(path).unwrap()).to_string();
+        assert!(!buf.contains("token-123"));
+        assert!(!buf.contains("alice@example.com"));
+        assert!(buf.contains(&format!("login with {}", crate::REDACT_MASK)));
+        drop(logger);
+        fs::remove_dir_all(&test_dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_trim_total_size() {
+        let test_dir = test_compat::test_path().join("total_size");
+        fs::remove_dir_all(&test_dir).unwrap_or_default();
+        let config = crate::EZLogConfigBuilder::new()
+            .dir_path(&test_dir)
+            .name("total_size")
+            .max_size(1024)
+            .max_total_size(3 * 1024)
+            .build();
+        let logger = EZLogger::new(config.clone()).unwrap();
+        let now = OffsetDateTime::now_utc();
+        let rotated: Vec<PathBuf> = (1..=4)
+            .rev()
+            .map(|days| {
+                let name = config
+                    .file_name_with_date(now - time::Duration::days(days), 1)
+                    .unwrap();
+                let path = test_dir.join(name);
+                fs::write(&path, vec![0u8; 1024]).unwrap();
+                path
+            })
+            .collect();
+
+        logger.trim();
+        assert!(!rotated[0].exists());
+        assert!(!rotated[1].exists());
+        assert!(rotated[2].exists());
+        assert!(rotated[3].exists());
+
+        // the new rotated file is counted too
+        logger
+            .append(EZRecord::builder().content("total size").build())
+            .unwrap();
+        logger.rotate_if_not_empty().unwrap();
+        assert!(!rotated[2].exists());
+        assert!(rotated[3].exists());
+        assert_eq!(config.rotated_log_files().unwrap().len(), 2);
+        drop(logger);
+        fs::remove_dir_all(&test_dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_rotate_key() {
+        let test_dir = test_compat::test_path().join("rotate_key");
+        fs::remove_dir_all(&test_dir).unwrap_or_default();
+
+        let key = b"an example very very secret key.";
+        let new_key = b"another example very secret key.";
+        let config = crate::EZLogConfigBuilder::new()
+            .dir_path(&test_dir)
+            .name("rotate_key")
+            .cipher(CipherKind::AES256GCMSIV)
+            .cipher_key(key.to_vec())
+            .cipher_nonce(b"unique nonce".to_vec())
+            .build();
+        let mut logger = EZLogger::new(config.clone()).unwrap();
+        logger
+            .append(EZRecord::builder().content("old key").build())
+            .unwrap();
+
+        // invalid key length, keep the current key
+        assert!(logger
+            .rotate_key(b"short".to_vec().into(), b"unique nonce".to_vec().into())
+            .is_err());
+        assert_eq!(logger.config.cipher_key(), Some(&key[..]));
+
+        logger
+            .rotate_key(new_key.to_vec().into(), b"another nonc".to_vec().into())
+            .unwrap();
+        logger
+            .append(EZRecord::builder().content("new key").build())
+            .unwrap();
+        let current_hash = logger.appender.get_inner().unwrap().header().cipher_hash;
+        assert_eq!(
+            current_hash,
+            crate::key_fingerprint(CipherKind::AES256GCMSIV, new_key)
+        );
+
+        let rotated = config.query_log_files_for_date(OffsetDateTime::now_utc());
+        assert_eq!(rotated.len(), 1);
+        let buf = fs::read(&rotated[0]).unwrap();
+        let header = Header::decode(&mut buf.as_slice()).unwrap();
+        assert_eq!(
+            header.cipher_hash,
+            crate::key_fingerprint(CipherKind::AES256GCMSIV, key)
+        );
+        drop(logger);
+        fs::remove_dir_all(&test_dir).unwrap_or_default();
+    }
+
     #[test]
     fn test_ezlog_trim() {
         use std::fs;
@@ -0,0 +1,615 @@
+use std::{
+    fs,
+    io::{
+        Cursor,
+        Read,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Mutex,
+};
+
+use time::OffsetDateTime;
+
+use crate::{
+    ring,
+    CipherKind,
+    CompressKind,
+    EZLogConfig,
+    Header,
+    Result,
+    Version,
+    FILE_SIGNATURE,
+};
+
+const MANIFEST_MAGIC: &str = "ezlog manifest 1";
+pub(crate) const MANIFEST_SUFFIX: &str = "manifest";
+
+/// Bytes read for the header of the file in manifest, greater than the longest header
+const HEADER_READ_LEN: u64 = 4096;
+
+/// Serialize the updates from the logger thread and the rotate thread
+static UPDATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// A rotated log file recorded in the manifest of the logger
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ManifestEntry {
+    /// file name in the log dir
+    pub(crate) file_name: String,
+    /// the file creation time in header
+    pub(crate) start: OffsetDateTime,
+    /// the time the file is rotated
+    pub(crate) end: OffsetDateTime,
+    /// file size in bytes
+    pub(crate) size: u64,
+    /// count of the records, exclude the extra
+    pub(crate) records: u64,
+    /// cipher key fingerprint in header, see [crate::key_fingerprint]
+    pub(crate) key_fingerprint: u32,
+}
+
+impl ManifestEntry {
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn start(&self) -> OffsetDateTime {
+        self.start
+    }
+
+    pub fn end(&self) -> OffsetDateTime {
+        self.end
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    pub fn key_fingerprint(&self) -> u32 {
+        self.key_fingerprint
+    }
+
+    /// Read the entry from the log file, the time in file name is used if the header is invalid
+    pub(crate) fn from_file(config: &EZLogConfig, path: &Path) -> Result<Self> {
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(String::from)
+            .unwrap_or_default();
+        let name_time = config.read_file_name_as_date(&file_name)?;
+        let metadata = fs::metadata(path)?;
+        let end = metadata
+            .modified()
+            .map(OffsetDateTime::from)
+            .unwrap_or(name_time);
+        let mut entry = ManifestEntry {
+            file_name,
+            start: name_time,
+            end,
+            size: metadata.len(),
+            records: 0,
+            key_fingerprint: 0,
+        };
+        let buf = fs::read(path)?;
+        if let Some(header) = valid_header(&buf) {
+            entry.start = header.timestamp;
+            entry.records = count_records(&header, &buf);
+            entry.key_fingerprint = header.cipher_hash;
+        }
+        Ok(entry)
+    }
+
+    fn encode(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{:08x}",
+            self.file_name,
+            self.start.unix_timestamp(),
+            self.end.unix_timestamp(),
+            self.size,
+            self.records,
+            self.key_fingerprint
+        )
+    }
+
+    fn decode(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let file_name = fields.next()?.to_string();
+        let mut time = || {
+            fields
+                .next()?
+                .parse()
+                .ok()
+                .and_then(|t| OffsetDateTime::from_unix_timestamp(t).ok())
+        };
+        let start = time()?;
+        let end = time()?;
+        let entry = ManifestEntry {
+            file_name,
+            start,
+            end,
+            size: fields.next()?.parse().ok()?,
+            records: fields.next()?.parse().ok()?,
+            key_fingerprint: u32::from_str_radix(fields.next()?, 16).ok()?,
+        };
+        Some(entry)
+    }
+}
+
+/// A log file returned by fetch, see [crate::EZLogCallback::on_fetch_files]
+///
+/// the header fields are empty if the file is not a log file, like the decoded records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchedFile {
+    /// file path
+    pub(crate) path: PathBuf,
+    /// file size in bytes
+    pub(crate) size: u64,
+    pub(crate) version: Version,
+    pub(crate) compress: CompressKind,
+    pub(crate) cipher: CipherKind,
+    /// cipher key fingerprint in header, see [crate::key_fingerprint]
+    pub(crate) key_fingerprint: u32,
+    /// the file creation time in header, no record is written before it
+    ///
+    /// decode the records by [FetchedFile::record_times] for the time in the records
+    pub(crate) created_time: OffsetDateTime,
+    /// the rotation time in manifest, or the modified time of the active file, no record is written
+    /// after it
+    pub(crate) rotated_time: OffsetDateTime,
+    /// count of the records, exclude the extra
+    pub(crate) records: u64,
+    /// whether it is the file the logger is writing
+    pub(crate) is_active: bool,
+}
+
+impl FetchedFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn compress(&self) -> CompressKind {
+        self.compress
+    }
+
+    pub fn cipher(&self) -> CipherKind {
+        self.cipher
+    }
+
+    pub fn key_fingerprint(&self) -> u32 {
+        self.key_fingerprint
+    }
+
+    pub fn created_time(&self) -> OffsetDateTime {
+        self.created_time
+    }
+
+    pub fn rotated_time(&self) -> OffsetDateTime {
+        self.rotated_time
+    }
+
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.is_active
+    }
+
+    /// Decode the records for the time of the first and the last record
+    ///
+    /// the time is read from the prefix of the default formatter, see [crate::request_logs_in_range]
+    #[cfg(feature = "decode")]
+    pub fn record_times(&self, config: &EZLogConfig) -> Option<(OffsetDateTime, OffsetDateTime)> {
+        crate::fetch::record_times(config, &self.path)
+    }
+
+    /// Read the metadata of the fetched file
+    ///
+    /// the rotation time and the record count are taken from the manifest entry, only the header of
+    /// the file in manifest is read
+    pub(crate) fn from_file(
+        path: &Path,
+        entry: Option<&ManifestEntry>,
+        is_active: bool,
+    ) -> Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata
+            .modified()
+            .map(OffsetDateTime::from)
+            .unwrap_or_else(|_| OffsetDateTime::now_utc());
+        let mut file = FetchedFile {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            version: Version::NONE,
+            compress: CompressKind::NONE,
+            cipher: CipherKind::NONE,
+            key_fingerprint: 0,
+            created_time: modified,
+            rotated_time: entry.map(|e| e.end).unwrap_or(modified),
+            records: 0,
+            is_active,
+        };
+        let header = match entry {
+            Some(entry) => {
+                file.records = entry.records;
+                let mut buf = Vec::new();
+                fs::File::open(path)?
+                    .take(HEADER_READ_LEN)
+                    .read_to_end(&mut buf)?;
+                valid_header(&buf)
+            }
+            None => {
+                let buf = fs::read(path)?;
+                let header = valid_header(&buf);
+                if let Some(header) = &header {
+                    file.records = count_records(header, &buf);
+                }
+                header
+            }
+        };
+        if let Some(header) = header {
+            file.version = header.version;
+            file.compress = header.compress;
+            file.cipher = header.cipher;
+            file.key_fingerprint = header.cipher_hash;
+            file.created_time = header.timestamp;
+        }
+        Ok(file)
+    }
+}
+
+/// The index of the rotated files of a logger, saved as `name.manifest` in the log dir
+///
+/// the saved manifest is trusted when loaded. it is rebuilt from the file names of the logger in
+/// the log dir only when it is missing or broken, or repaired explicitly to notice the files removed
+/// or added by others. only the header of the file not in the manifest is read.
+pub(crate) struct Manifest {
+    path: PathBuf,
+    entries: Vec<ManifestEntry>,
+    /// the last sequence of the rotated file names, it is kept after the files are removed
+    sequence: u64,
+}
+
+impl Manifest {
+    /// Load the manifest of the logger, the entries are sorted from the oldest
+    ///
+    /// it is rebuilt from the log dir but not saved if the manifest is missing or broken
+    pub(crate) fn load(config: &EZLogConfig) -> Result<Self> {
+        match Self::read(config) {
+            Some((entries, sequence)) => Ok(Manifest {
+                path: config.manifest_path(),
+                entries,
+                sequence,
+            }),
+            None => Self::reconcile(config, &[], 0),
+        }
+    }
+
+    /// Reconcile the manifest with the log dir and save it if changed
+    pub(crate) fn repair(config: &EZLogConfig) -> Result<Self> {
+        let saved = Self::read(config);
+        let (known, sequence) = saved.clone().unwrap_or_default();
+        let manifest = Self::reconcile(config, &known, sequence)?;
+        if saved != Some((manifest.entries.clone(), manifest.sequence))
+            && config.dir_path().is_dir()
+        {
+            manifest.save()?;
+        }
+        Ok(manifest)
+    }
+
+    fn read(config: &EZLogConfig) -> Option<(Vec<ManifestEntry>, u64)> {
+        fs::read_to_string(config.manifest_path())
+            .ok()
+            .and_then(|content| Self::decode(&content))
+    }
+
+    pub(crate) fn entries(&self) -> &[ManifestEntry] {
+        &self.entries
+    }
+
+    /// The last sequence of the rotated files, include the removed ones
+    pub(crate) fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Record the rotated file, it is called after the file is renamed
+    pub(crate) fn add(config: &EZLogConfig, path: &Path) -> Result<()> {
+        let _lock = update_lock();
+        let mut manifest = Self::load(config)?;
+        let mut entry = ManifestEntry::from_file(config, path)?;
+        entry.end = OffsetDateTime::now_utc();
+        manifest.entries.retain(|e| e.file_name != entry.file_name);
+        if let Some(sequence) = config.read_file_name_sequence(&entry.file_name) {
+            manifest.sequence = manifest.sequence.max(sequence);
+        }
+        manifest.entries.push(entry);
+        manifest.sort(config);
+        manifest.save()
+    }
+
+    /// Remove the entries of the removed files
+    pub(crate) fn remove(config: &EZLogConfig, removed: &[PathBuf]) -> Result<()> {
+        let _lock = update_lock();
+        let mut manifest = Self::load(config)?;
+        manifest.entries.retain(|e| {
+            !removed
+                .iter()
+                .any(|path| path.file_name().and_then(|n| n.to_str()) == Some(&e.file_name))
+        });
+        manifest.save()
+    }
+
+    /// Update the size of the rotated file, it is called after the file is sealed
+    #[cfg(feature = "sign")]
+    pub(crate) fn update_size(config: &EZLogConfig, path: &Path) -> Result<()> {
+        let _lock = update_lock();
+        let mut manifest = Self::load(config)?;
+        let size = fs::metadata(path)?.len();
+        let file_name = path.file_name().and_then(|n| n.to_str());
+        manifest
+            .entries
+            .iter_mut()
+            .filter(|e| Some(e.file_name.as_str()) == file_name)
+            .for_each(|e| e.size = size);
+        manifest.save()
+    }
+
+    /// Rebuild from the rotated files in the log dir, the known entries are reused
+    fn reconcile(config: &EZLogConfig, known: &[ManifestEntry], sequence: u64) -> Result<Self> {
+        let mut entries = Vec::new();
+        if config.dir_path().is_dir() {
+            for dir_entry in fs::read_dir(config.dir_path())? {
+                let path = dir_entry?.path();
+                let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                if !path.is_file() || config.read_file_name_as_date(file_name).is_err() {
+                    continue;
+                }
+                match known.iter().find(|e| e.file_name == file_name) {
+                    Some(entry) => entries.push(entry.clone()),
+                    None => entries.push(ManifestEntry::from_file(config, &path)?),
+                }
+            }
+        }
+        let sequence = entries
+            .iter()
+            .filter_map(|e| config.read_file_name_sequence(&e.file_name))
+            .fold(sequence, u64::max);
+        let mut manifest = Manifest {
+            path: config.manifest_path(),
+            entries,
+            sequence,
+        };
+        manifest.sort(config);
+        Ok(manifest)
+    }
+
+    /// Sort by the time and sequence in file name
+    fn sort(&mut self, config: &EZLogConfig) {
+        self.entries.sort_by_cached_key(|e| {
+            (
+                config.read_file_name_as_date(&e.file_name).ok(),
+                config.read_file_name_sequence(&e.file_name),
+                e.start,
+                e.file_name.clone(),
+            )
+        });
+    }
+
+    /// Decode the entries and the last sequence
+    fn decode(content: &str) -> Option<(Vec<ManifestEntry>, u64)> {
+        let mut lines = content.lines();
+        if lines.next()? != MANIFEST_MAGIC {
+            return None;
+        }
+        let sequence = lines.next()?.strip_prefix("sequence\t")?.parse().ok()?;
+        let entries = lines.map(ManifestEntry::decode).collect::<Option<_>>()?;
+        Some((entries, sequence))
+    }
+
+    /// Write to a temporary file and rename it, the manifest is never seen half written
+    fn save(&self) -> Result<()> {
+        let mut content = format!("{}\nsequence\t{}", MANIFEST_MAGIC, self.sequence);
+        for entry in &self.entries {
+            content.push('\n');
+            content.push_str(&entry.encode());
+        }
+        let tmp = self.path.with_extension(format!("{}.tmp", MANIFEST_SUFFIX));
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+fn update_lock() -> std::sync::MutexGuard<'static, ()> {
+    UPDATE_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn valid_header(buf: &[u8]) -> Option<Header> {
+    if !buf.starts_with(FILE_SIGNATURE) {
+        return None;
+    }
+    Header::decode(&mut Cursor::new(buf))
+        .ok()
+        .filter(|header| matches!(header.version, Version::V2 | Version::V3))
+}
+
+/// Count the records of the log file, exclude the extra
+fn count_records(header: &Header, buf: &[u8]) -> u64 {
+    let segments = match header.ring.filter(|ring| ring.lap > 0) {
+        Some(ring) => ring.segments(header.recorder_position),
+        None => vec![(header.length() as u64, header.recorder_position.into(), 0)],
+    };
+    let mut records = 0;
+    for (from, to, _) in segments {
+        let mut position = from as usize;
+        while position < to as usize {
+            let Ok(len) = ring::frame_len(buf, position) else {
+                break;
+            };
+            if !header.is_extra_index(position as u64) {
+                records += 1;
+            }
+            position += len;
+        }
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        EZLogConfigBuilder,
+        EZLogger,
+        EZRecord,
+    };
+
+    #[test]
+    fn test_manifest() {
+        let dir = test_compat::test_path().join("manifest");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let config = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("app")
+            .extra("manifest extra")
+            .max_size(1024)
+            .build();
+        let other = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("app_net")
+            .max_size(1024)
+            .build();
+
+        let logger = EZLogger::new(config.clone()).unwrap();
+        let other_logger = EZLogger::new(other.clone()).unwrap();
+        for i in 0..3 {
+            logger
+                .append(EZRecord::builder().content(format!("app {}", i)).build())
+                .unwrap();
+        }
+        other_logger
+            .append(EZRecord::builder().content("net").build())
+            .unwrap();
+        logger.rotate_if_not_empty().unwrap();
+        other_logger.rotate_if_not_empty().unwrap();
+
+        let manifest = Manifest::load(&config).unwrap();
+        assert_eq!(manifest.entries().len(), 1);
+        let entry = &manifest.entries()[0];
+        assert!(entry.file_name().starts_with("app_2"));
+        assert_eq!(entry.records(), 3);
+        assert_eq!(entry.size(), 1024);
+        assert_eq!(entry.key_fingerprint(), config.cipher_hash());
+        assert!(entry.start() <= entry.end());
+        assert_eq!(Manifest::load(&other).unwrap().entries().len(), 1);
+
+        // rebuilt from the log dir when the manifest is missing, but saved only by repair
+        let file_name = entry.file_name().to_string();
+        fs::remove_file(config.manifest_path()).unwrap();
+        let rebuilt = Manifest::load(&config).unwrap();
+        assert_eq!(rebuilt.entries().len(), 1);
+        assert_eq!(rebuilt.entries()[0].file_name(), file_name);
+        assert_eq!(rebuilt.entries()[0].records(), 3);
+        assert!(!config.manifest_path().exists());
+        assert_eq!(Manifest::repair(&config).unwrap().entries().len(), 1);
+        assert!(config.manifest_path().exists());
+
+        // the saved manifest is trusted, the file added by others is noticed by repair
+        let copied = dir.join("app_1999_01_01.mmap");
+        fs::copy(dir.join(&file_name), &copied).unwrap();
+        assert_eq!(Manifest::load(&config).unwrap().entries().len(), 1);
+        assert_eq!(Manifest::repair(&config).unwrap().entries().len(), 2);
+        fs::remove_file(&copied).unwrap();
+        assert_eq!(Manifest::repair(&config).unwrap().entries().len(), 1);
+
+        // a broken manifest is rebuilt too
+        fs::write(config.manifest_path(), "broken").unwrap();
+        assert_eq!(Manifest::load(&config).unwrap().entries().len(), 1);
+        Manifest::repair(&config).unwrap();
+
+        // the sequence is kept after the rotated files are removed
+        let sequence = config.read_file_name_sequence(&file_name).unwrap();
+        assert_eq!(Manifest::load(&config).unwrap().sequence(), sequence);
+        fs::remove_file(dir.join(&file_name)).unwrap();
+        Manifest::remove(&config, &[dir.join(&file_name)]).unwrap();
+        let manifest = Manifest::load(&config).unwrap();
+        assert!(manifest.entries().is_empty());
+        assert_eq!(manifest.sequence(), sequence);
+        assert_eq!(config.next_sequence(), sequence + 1);
+
+        drop(logger);
+        drop(other_logger);
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_fetched_file() {
+        let dir = test_compat::test_path().join("fetched_file");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let config = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("fetched")
+            .extra("fetched extra")
+            .max_size(1024)
+            .build();
+        let logger = EZLogger::new(config.clone()).unwrap();
+        for i in 0..2 {
+            logger
+                .append(
+                    EZRecord::builder()
+                        .content(format!("fetched {}", i))
+                        .build(),
+                )
+                .unwrap();
+        }
+        logger.rotate_if_not_empty().unwrap();
+
+        let manifest = Manifest::load(&config).unwrap();
+        let entry = &manifest.entries()[0];
+        let path = config.dir_path().join(entry.file_name());
+        let file = FetchedFile::from_file(&path, Some(entry), false).unwrap();
+        assert_eq!(file.path(), path);
+        assert_eq!(file.size(), 1024);
+        assert_eq!(file.version(), config.version());
+        assert_eq!(file.compress(), config.compress_kind());
+        assert_eq!(file.cipher(), config.cipher_kind());
+        assert_eq!(file.key_fingerprint(), config.cipher_hash());
+        assert_eq!(file.records(), 2);
+        // the times are in the header and manifest, the same with or without the decode feature
+        assert_eq!(file.created_time(), entry.start());
+        assert_eq!(file.rotated_time(), entry.end());
+        #[cfg(feature = "decode")]
+        {
+            let (first, last) = file.record_times(&config).unwrap();
+            assert!(entry.start() <= first && first <= last);
+        }
+        assert!(!file.is_active());
+
+        let active = logger.current_file_path().unwrap();
+        let file = FetchedFile::from_file(&active, None, true).unwrap();
+        assert!(file.is_active());
+        assert_eq!(file.records(), 0);
+
+        drop(logger);
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+}
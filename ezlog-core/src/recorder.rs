@@ -13,6 +13,7 @@ use log::Record;
 use time::{
     format_description::well_known::Rfc3339,
     OffsetDateTime,
+    UtcOffset,
 };
 
 use crate::{
@@ -92,6 +93,11 @@ impl EZRecord {
         &self.time
     }
 
+    /// Convert the record time to the offset, the instant is not changed
+    pub(crate) fn set_offset(&mut self, offset: UtcOffset) {
+        self.time = self.time.to_offset(offset);
+    }
+
     pub fn file(&self) -> Option<&str> {
         self.file.as_deref()
     }
@@ -0,0 +1,235 @@
+use std::{
+    fmt,
+    hash::{
+        Hash,
+        Hasher,
+    },
+};
+
+use hmac::{
+    Hmac,
+    Mac,
+};
+use sha2::Sha256;
+
+use crate::SecretBytes;
+
+/// The replacement of [RedactAction::Mask]
+pub const REDACT_MASK: &str = "***";
+
+/// Bytes of the keyed hash in the replacement of [RedactAction::Hash]
+const REDACT_HASH_LEN: usize = 8;
+
+/// How to replace the matched text
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum RedactAction {
+    /// Replace with [REDACT_MASK]
+    Mask,
+    /// Replace with `#` and the hex of the truncated HMAC-SHA256 of the matched text,
+    /// the same text has the same hash, so the records can still be correlated
+    Hash(SecretBytes),
+}
+
+impl fmt::Debug for RedactAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedactAction::Mask => write!(f, "Mask"),
+            RedactAction::Hash(_) => write!(f, "Hash"),
+        }
+    }
+}
+
+impl RedactAction {
+    fn replace(&self, matched: &str) -> String {
+        match self {
+            RedactAction::Mask => REDACT_MASK.to_string(),
+            RedactAction::Hash(key) => {
+                let Ok(mut hmac) = <Hmac<Sha256> as Mac>::new_from_slice(key.expose()) else {
+                    return REDACT_MASK.to_string();
+                };
+                hmac.update(matched.as_bytes());
+                let hash = hmac.finalize().into_bytes();
+                let mut replaced = String::with_capacity(1 + REDACT_HASH_LEN * 2);
+                replaced.push('#');
+                for byte in &hash[..REDACT_HASH_LEN] {
+                    replaced.push_str(&format!("{:02x}", byte));
+                }
+                replaced
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Matcher {
+    Literal(String),
+    #[cfg(feature = "redact")]
+    Regex(regex::Regex),
+}
+
+impl Matcher {
+    fn pattern(&self) -> &str {
+        match self {
+            Matcher::Literal(literal) => literal,
+            #[cfg(feature = "redact")]
+            Matcher::Regex(regex) => regex.as_str(),
+        }
+    }
+}
+
+/// A rule to redact the sensitive text of the record before it is compressed and encrypted
+///
+/// ```
+/// use ezlog::RedactRule;
+///
+/// let token = RedactRule::literal("my-secret-token");
+/// let user = RedactRule::literal("alice@example.com").hash(b"correlation key".to_vec());
+/// ```
+#[derive(Clone)]
+pub struct RedactRule {
+    matcher: Matcher,
+    action: RedactAction,
+}
+
+impl RedactRule {
+    /// Match the literal text, masked by default
+    pub fn literal(literal: impl Into<String>) -> Self {
+        RedactRule {
+            matcher: Matcher::Literal(literal.into()),
+            action: RedactAction::Mask,
+        }
+    }
+
+    /// Match the regex pattern, masked by default
+    #[cfg(feature = "redact")]
+    pub fn regex(pattern: impl AsRef<str>) -> crate::Result<Self> {
+        let regex = regex::Regex::new(pattern.as_ref())
+            .map_err(|e| crate::LogError::Illegal(format!("redact regex invalid {}", e)))?;
+        Ok(RedactRule {
+            matcher: Matcher::Regex(regex),
+            action: RedactAction::Mask,
+        })
+    }
+
+    /// Replace the matched text with [REDACT_MASK]
+    pub fn mask(mut self) -> Self {
+        self.action = RedactAction::Mask;
+        self
+    }
+
+    /// Replace the matched text with its keyed hash
+    pub fn hash(mut self, key: impl Into<SecretBytes>) -> Self {
+        self.action = RedactAction::Hash(key.into());
+        self
+    }
+
+    pub fn action(&self) -> &RedactAction {
+        &self.action
+    }
+
+    /// Apply the rule, returns the redacted text and the count of matches
+    fn apply(&self, text: &str) -> (String, usize) {
+        match &self.matcher {
+            Matcher::Literal(literal) => {
+                if literal.is_empty() {
+                    return (text.to_string(), 0);
+                }
+                let count = text.matches(literal.as_str()).count();
+                if count == 0 {
+                    return (text.to_string(), 0);
+                }
+                (
+                    text.replace(literal.as_str(), &self.action.replace(literal)),
+                    count,
+                )
+            }
+            #[cfg(feature = "redact")]
+            Matcher::Regex(regex) => {
+                let mut count = 0;
+                let replaced = regex.replace_all(text, |caps: &regex::Captures| {
+                    count += 1;
+                    self.action.replace(&caps[0])
+                });
+                (replaced.into_owned(), count)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for RedactRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedactRule")
+            .field("pattern", &self.matcher.pattern())
+            .field("action", &self.action)
+            .finish()
+    }
+}
+
+impl PartialEq for RedactRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.matcher.pattern() == other.matcher.pattern() && self.action == other.action
+    }
+}
+
+impl Hash for RedactRule {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.matcher.pattern().hash(state);
+        self.action.hash(state);
+    }
+}
+
+/// Apply the rules in order, returns the redacted text and the total count of matches
+pub(crate) fn redact(rules: &[RedactRule], text: &str) -> (String, usize) {
+    let mut total = 0;
+    let mut text = text.to_string();
+    for rule in rules {
+        let (redacted, count) = rule.apply(&text);
+        if count > 0 {
+            text = redacted;
+            total += count;
+        }
+    }
+    (text, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_literal() {
+        let rules = vec![
+            RedactRule::literal("token-123"),
+            RedactRule::literal("alice@example.com").hash(b"key".to_vec()),
+        ];
+        let (text, count) = redact(
+            &rules,
+            "login alice@example.com token-123, alice@example.com again",
+        );
+        assert_eq!(count, 3);
+        assert!(!text.contains("token-123"));
+        assert!(!text.contains("alice@example.com"));
+        assert!(text.contains(REDACT_MASK));
+
+        let hashed = RedactAction::Hash(b"key".to_vec().into()).replace("alice@example.com");
+        assert_eq!(hashed.len(), 1 + REDACT_HASH_LEN * 2);
+        assert_eq!(text.matches(&hashed).count(), 2);
+        assert_ne!(
+            hashed,
+            RedactAction::Hash(b"other".to_vec().into()).replace("alice@example.com")
+        );
+    }
+
+    #[cfg(feature = "redact")]
+    #[test]
+    fn test_redact_regex() {
+        let rules = vec![
+            RedactRule::regex(r"[\w.+-]+@[\w-]+\.[\w.]+").unwrap(),
+            RedactRule::regex(r"\+?\d{3}[- ]?\d{4}[- ]?\d{4}").unwrap(),
+        ];
+        let (text, count) = redact(&rules, "mail bob@example.org or call 138-1234-5678");
+        assert_eq!(count, 2);
+        assert_eq!(text, "mail *** or call ***");
+        assert!(RedactRule::regex("(").is_err());
+    }
+}
@@ -0,0 +1,254 @@
+//! Registry of user provided [Cryptor] and [Compress] implementations.
+//!
+//! A custom implementation is registered under a numeric id in the range
+//! [CUSTOM_ID_START]..=[CUSTOM_ID_END]. The id is written to the log file header,
+//! so the same factory must be registered before a file is decoded.
+//!
+//! # Examples
+//! ```
+//! use ezlog::{
+//!     CipherKind,
+//!     EZLogConfigBuilder,
+//! };
+//!
+//! struct Plain;
+//!
+//! impl ezlog::Encryptor for Plain {
+//!     fn encrypt(
+//!         &self,
+//!         data: &[u8],
+//!         _op: Box<dyn Fn(&[u8]) -> Vec<u8>>,
+//!     ) -> Result<Vec<u8>, ezlog::LogError> {
+//!         Ok(data.to_vec())
+//!     }
+//! }
+//!
+//! impl ezlog::Decryptor for Plain {
+//!     fn decrypt(
+//!         &self,
+//!         data: &[u8],
+//!         _op: Box<dyn Fn(&[u8]) -> Vec<u8>>,
+//!     ) -> Result<Vec<u8>, ezlog::LogError> {
+//!         Ok(data.to_vec())
+//!     }
+//! }
+//!
+//! ezlog::register_cryptor(0x80, |_key, _nonce| Ok(Box::new(Plain))).unwrap();
+//! let config = EZLogConfigBuilder::new()
+//!     .cipher(CipherKind::CUSTOM(0x80))
+//!     .build();
+//! ```
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use parking_lot::RwLock;
+
+use crate::{
+    errors::LogError,
+    Compress,
+    CompressLevel,
+    Cryptor,
+};
+
+/// The first id can be used by custom implementations.
+pub const CUSTOM_ID_START: u8 = 0x80;
+/// The last id can be used by custom implementations, `0xff` means unknown.
+pub const CUSTOM_ID_END: u8 = 0xfe;
+
+/// Create a [Cryptor] from the config's cipher key and nonce.
+///
+/// the key and nonce are empty if the config does not set them.
+pub type CryptorFactory =
+    dyn Fn(&[u8], &[u8]) -> crate::Result<Box<dyn Cryptor + Send + Sync>> + Send + Sync;
+
+/// Create a [Compress] with the config's compress level.
+pub type CompressFactory = dyn Fn(CompressLevel) -> Box<dyn Compress + Send + Sync> + Send + Sync;
+
+static CRYPTORS: LazyLock<RwLock<HashMap<u8, Box<CryptorFactory>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+static COMPRESSES: LazyLock<RwLock<HashMap<u8, Box<CompressFactory>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+#[inline]
+pub(crate) fn is_custom_id(id: u8) -> bool {
+    (CUSTOM_ID_START..=CUSTOM_ID_END).contains(&id)
+}
+
+fn check_custom_id(id: u8) -> crate::Result<()> {
+    if is_custom_id(id) {
+        Ok(())
+    } else {
+        Err(LogError::Illegal(format!(
+            "custom id must be in {:#04x}..={:#04x}, but current is {:#04x}",
+            CUSTOM_ID_START, CUSTOM_ID_END, id
+        )))
+    }
+}
+
+/// Register a [Cryptor] factory, it will be used by [crate::CipherKind::CUSTOM] with the same id.
+///
+/// returns an error if the id is out of range or already registered.
+pub fn register_cryptor<F>(id: u8, factory: F) -> crate::Result<()>
+where
+    F: Fn(&[u8], &[u8]) -> crate::Result<Box<dyn Cryptor + Send + Sync>> + Send + Sync + 'static,
+{
+    check_custom_id(id)?;
+    let mut cryptors = CRYPTORS.write();
+    if cryptors.contains_key(&id) {
+        return Err(LogError::Illegal(format!(
+            "cryptor {:#04x} already registered",
+            id
+        )));
+    }
+    cryptors.insert(id, Box::new(factory));
+    Ok(())
+}
+
+/// Register a [Compress] factory, it will be used by [crate::CompressKind::CUSTOM] with the same id.
+///
+/// returns an error if the id is out of range or already registered.
+pub fn register_compress<F>(id: u8, factory: F) -> crate::Result<()>
+where
+    F: Fn(CompressLevel) -> Box<dyn Compress + Send + Sync> + Send + Sync + 'static,
+{
+    check_custom_id(id)?;
+    let mut compresses = COMPRESSES.write();
+    if compresses.contains_key(&id) {
+        return Err(LogError::Illegal(format!(
+            "compress {:#04x} already registered",
+            id
+        )));
+    }
+    compresses.insert(id, Box::new(factory));
+    Ok(())
+}
+
+/// Is there a [Cryptor] factory registered with the id
+pub fn is_cryptor_registered(id: u8) -> bool {
+    CRYPTORS.read().contains_key(&id)
+}
+
+/// Is there a [Compress] factory registered with the id
+pub fn is_compress_registered(id: u8) -> bool {
+    COMPRESSES.read().contains_key(&id)
+}
+
+pub(crate) fn create_cryptor(
+    id: u8,
+    key: &[u8],
+    nonce: &[u8],
+) -> crate::Result<Box<dyn Cryptor + Send + Sync>> {
+    match CRYPTORS.read().get(&id) {
+        Some(factory) => factory(key, nonce),
+        None => Err(LogError::Crypto(format!(
+            "cryptor {:#04x} is not registered",
+            id
+        ))),
+    }
+}
+
+pub(crate) fn create_compress(
+    id: u8,
+    level: CompressLevel,
+) -> Option<Box<dyn Compress + Send + Sync>> {
+    COMPRESSES.read().get(&id).map(|factory| factory(level))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        CipherKind,
+        CompressKind,
+        Compression,
+        Decompression,
+        Decryptor,
+        EZLogConfigBuilder,
+        Encryptor,
+        NonceGenFn,
+    };
+
+    struct XorCryptor {
+        key: Vec<u8>,
+    }
+
+    impl XorCryptor {
+        fn xor(&self, data: &[u8]) -> Vec<u8> {
+            data.iter()
+                .zip(self.key.iter().cycle())
+                .map(|(d, k)| d ^ k)
+                .collect()
+        }
+    }
+
+    impl Encryptor for XorCryptor {
+        fn encrypt(&self, data: &[u8], _op: NonceGenFn) -> crate::Result<Vec<u8>> {
+            Ok(self.xor(data))
+        }
+    }
+
+    impl Decryptor for XorCryptor {
+        fn decrypt(&self, data: &[u8], _op: NonceGenFn) -> crate::Result<Vec<u8>> {
+            Ok(self.xor(data))
+        }
+    }
+
+    struct ReverseCompress;
+
+    impl Compression for ReverseCompress {
+        fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+            Ok(data.iter().rev().copied().collect())
+        }
+    }
+
+    impl Decompression for ReverseCompress {
+        fn decompress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+            Ok(data.iter().rev().copied().collect())
+        }
+    }
+
+    #[test]
+    fn test_register_out_of_range() {
+        assert!(register_cryptor(0x01, |_, _| Err(LogError::unknown("unused"))).is_err());
+        assert!(register_compress(0xff, |_| Box::new(ReverseCompress)).is_err());
+    }
+
+    #[test]
+    fn test_registry_create() {
+        register_cryptor(0xf0, |key, _nonce| {
+            Ok(Box::new(XorCryptor { key: key.to_vec() }))
+        })
+        .unwrap();
+        register_compress(0xf0, |_| Box::new(ReverseCompress)).unwrap();
+        assert!(register_compress(0xf0, |_| Box::new(ReverseCompress)).is_err());
+
+        let config = EZLogConfigBuilder::new()
+            .dir_path(test_compat::test_path())
+            .cipher(CipherKind::CUSTOM(0xf0))
+            .cipher_key(vec![0x5a])
+            .compress(CompressKind::CUSTOM(0xf0))
+            .build();
+        assert!(config.check_valid().is_ok());
+
+        let cryptor = crate::create_cryptor(&config).unwrap().unwrap();
+        let compress = crate::create_compress(&config).unwrap();
+        let encoded = cryptor
+            .encrypt(
+                &compress.compress(b"ezlog").unwrap(),
+                Box::new(|n| n.to_vec()),
+            )
+            .unwrap();
+        assert_ne!(encoded, b"ezlog");
+        let decoded = compress
+            .decompress(&cryptor.decrypt(&encoded, Box::new(|n| n.to_vec())).unwrap())
+            .unwrap();
+        assert_eq!(decoded, b"ezlog");
+
+        let unregistered = EZLogConfigBuilder::new()
+            .dir_path(test_compat::test_path())
+            .cipher(CipherKind::CUSTOM(0xf1))
+            .build();
+        assert!(unregistered.check_valid().is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{
+            AtomicU64,
+            Ordering,
+        },
+        Arc,
+    },
+    task::{
+        Context,
+        Poll,
+        Waker,
+    },
+    time::Duration,
+};
+
+use parking_lot::{
+    Condvar,
+    Mutex,
+};
+
+use crate::{
+    FetchResult,
+    LogError,
+};
+
+type CompletionFn = Box<dyn FnOnce(FetchResult) + Send>;
+
+/// Id of the next fetch request, starts from 1
+pub(crate) fn next_request_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The closure called once with the result of the request, instead of the global callback
+///
+/// if the request is dropped before it is completed, like it is posted before init or the channel
+/// is full, the closure is called with an error result.
+#[derive(Clone)]
+pub(crate) struct Completion(Arc<Pending>);
+
+struct Pending {
+    id: u64,
+    name: String,
+    op: Mutex<Option<CompletionFn>>,
+}
+
+impl Completion {
+    pub(crate) fn new<F>(id: u64, name: impl Into<String>, op: F) -> Self
+    where
+        F: FnOnce(FetchResult) + Send + 'static,
+    {
+        Completion(Arc::new(Pending {
+            id,
+            name: name.into(),
+            op: Mutex::new(Some(Box::new(op))),
+        }))
+    }
+
+    /// Call the closure, returns the result back if it is already called
+    pub(crate) fn complete(&self, result: FetchResult) -> Option<FetchResult> {
+        match self.0.op.lock().take() {
+            Some(op) => {
+                op(result);
+                None
+            }
+            None => Some(result),
+        }
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if let Some(op) = self.op.get_mut().take() {
+            op(FetchResult::failed(
+                self.id,
+                std::mem::take(&mut self.name),
+                LogError::Illegal("fetch request is dropped before it is completed".into()),
+            ));
+        }
+    }
+}
+
+impl fmt::Debug for Completion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Completion(<dyn FnOnce>)")
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    result: Option<FetchResult>,
+    waker: Option<Waker>,
+}
+
+/// The result of [crate::fetch_log_files], it can be awaited or waited on
+pub struct FetchFuture {
+    id: u64,
+    shared: Arc<(Mutex<Shared>, Condvar)>,
+}
+
+impl FetchFuture {
+    pub(crate) fn new(id: u64, name: impl Into<String>) -> (Self, Completion) {
+        let shared = Arc::new((Mutex::new(Shared::default()), Condvar::new()));
+        let sender = shared.clone();
+        let completion = Completion::new(id, name, move |result| {
+            let (state, condvar) = &*sender;
+            let mut state = state.lock();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+            condvar.notify_all();
+        });
+        (FetchFuture { id, shared }, completion)
+    }
+
+    /// The request id, the same as [FetchResult::id]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Block the current thread until the result is ready
+    pub fn wait(self) -> FetchResult {
+        let (state, condvar) = &*self.shared;
+        let mut state = state.lock();
+        loop {
+            if let Some(result) = state.result.take() {
+                return result;
+            }
+            condvar.wait(&mut state);
+        }
+    }
+
+    /// Block the current thread until the result is ready or timeout
+    pub fn wait_timeout(self, timeout: Duration) -> Option<FetchResult> {
+        let (state, condvar) = &*self.shared;
+        let mut state = state.lock();
+        if state.result.is_none() {
+            condvar.wait_for(&mut state, timeout);
+        }
+        state.result.take()
+    }
+}
+
+impl Future for FetchFuture {
+    type Output = FetchResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.0.lock();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl fmt::Debug for FetchFuture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FetchFuture").field("id", &self.id).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(id: u64) -> FetchResult {
+        FetchResult {
+            id,
+            completion: None,
+            name: "request".into(),
+            date: "2020-01-01".into(),
+            logs: Some(Vec::new()),
+            skipped: Vec::new(),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_fetch_future() {
+        let id = next_request_id();
+        assert!(next_request_id() > id);
+
+        let (mut future, completion) = FetchFuture::new(id, "request");
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        assert!(completion.clone().complete(result(id)).is_none());
+        // called only once
+        assert!(completion.complete(result(id)).is_some());
+        match Pin::new(&mut future).poll(&mut cx) {
+            Poll::Ready(result) => assert_eq!(result.id(), id),
+            Poll::Pending => panic!("future is not ready"),
+        }
+
+        let (future, completion) = FetchFuture::new(next_request_id(), "request");
+        assert!(future.wait_timeout(Duration::from_millis(10)).is_none());
+        drop(completion);
+    }
+
+    #[test]
+    fn test_dropped_request() {
+        // the fetch request is rejected by the pre-init queue and dropped
+        let (future, completion) = FetchFuture::new(next_request_id(), "request");
+        let req = crate::FetchReq {
+            id: future.id(),
+            name: "request".into(),
+            start: time::OffsetDateTime::now_utc(),
+            end: time::OffsetDateTime::now_utc(),
+            by_day: false,
+            mode: crate::FetchMode::Files,
+            completion: Some(completion),
+        };
+        assert!(crate::insert_init_cache(crate::EZMsg::FetchLog(req)).is_err());
+        let result = future.wait_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(result.name(), "request");
+        assert!(result.error().is_some());
+        assert!(result.into_files().is_err());
+
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let completion = Completion::new(1, "request", move |result| {
+            sender.send(result).unwrap();
+        });
+        let cloned = completion.clone();
+        drop(completion);
+        assert!(receiver.try_recv().is_err());
+        drop(cloned);
+        assert!(receiver.try_recv().unwrap().error().is_some());
+    }
+}
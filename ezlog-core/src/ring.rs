@@ -0,0 +1,312 @@
+use std::io::{
+    self,
+    Read,
+    Write,
+};
+
+use byteorder::{
+    BigEndian,
+    ReadBytesExt,
+    WriteBytesExt,
+};
+use integer_encoding::VarInt;
+
+use crate::{
+    logger::combine_time_position,
+    Header,
+    RECORD_SIGNATURE_END,
+    RECORD_SIGNATURE_START,
+};
+
+/// Length of the encoded [RingState]
+pub(crate) const RING_STATE_LEN: usize = 16;
+
+/// Offsets of the ring buffer log file, see [crate::EZLogConfigBuilder::ring_buffer]
+///
+/// the records are written from `start` to the end of the file, then the writer wraps to
+/// `start` and overwrites the oldest records.
+/// the records of the last lap are kept in `head..end`, the current lap is `start..recorder_position`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct RingState {
+    /// position of the first record after the header and the extra
+    pub(crate) start: u32,
+    /// position of the oldest record of the last lap
+    pub(crate) head: u32,
+    /// end position of the last lap, the last lap is empty if `head == end`
+    pub(crate) end: u32,
+    /// count of the wraps, it takes part in the nonce
+    pub(crate) lap: u32,
+}
+
+impl RingState {
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    pub fn head(&self) -> u32 {
+        self.head
+    }
+
+    pub fn end(&self) -> u32 {
+        self.end
+    }
+
+    pub fn lap(&self) -> u32 {
+        self.lap
+    }
+
+    pub(crate) fn encode(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_u32::<BigEndian>(self.start)?;
+        writer.write_u32::<BigEndian>(self.head)?;
+        writer.write_u32::<BigEndian>(self.end)?;
+        writer.write_u32::<BigEndian>(self.lap)
+    }
+
+    pub(crate) fn decode(reader: &mut dyn Read) -> io::Result<Self> {
+        Ok(RingState {
+            start: reader.read_u32::<BigEndian>()?,
+            head: reader.read_u32::<BigEndian>()?,
+            end: reader.read_u32::<BigEndian>()?,
+            lap: reader.read_u32::<BigEndian>()?,
+        })
+    }
+
+    /// Wrap the writer to the start, the current lap becomes the last lap
+    pub(crate) fn wrap(&mut self, start: u32, tail: u32) {
+        self.start = start;
+        self.head = start;
+        self.end = tail;
+        self.lap += 1;
+    }
+
+    /// Drop the records of the last lap which will be overwritten by `tail..tail + len`
+    pub(crate) fn drop_overwritten(&mut self, buf: &[u8], tail: u32, len: usize) -> io::Result<()> {
+        let write_end = tail as usize + len;
+        while self.head < self.end && (self.head as usize) < write_end {
+            self.head += frame_len(buf, self.head as usize)? as u32;
+        }
+        if self.head >= self.end {
+            self.head = self.end;
+        }
+        Ok(())
+    }
+
+    /// The record segments in chronological order, `(from, to, lap)`
+    pub(crate) fn segments(&self, recorder_position: u32) -> Vec<(u64, u64, u32)> {
+        let mut segments = Vec::with_capacity(2);
+        if self.lap == 0 {
+            return segments;
+        }
+        if self.head < self.end {
+            segments.push((self.head.into(), self.end.into(), self.lap - 1));
+        }
+        segments.push((self.start.into(), recorder_position.into(), self.lap));
+        segments
+    }
+}
+
+/// The timestamp and the position combined into the nonce of the record, `lap` is set in the ring buffer
+///
+/// the lap is in the high 32 bits of the timestamp, which are zero until 2106, and the position is
+/// moved to the high 32 bits, so both are in the first 12 bytes and the 12 bytes nonce ciphers
+/// do not fold them together
+#[inline]
+pub(crate) fn nonce_combine(timestamp: i64, position: u64, lap: Option<u32>) -> Vec<u8> {
+    match lap {
+        Some(lap) => combine_time_position(timestamp ^ (i64::from(lap) << 32), position << 32),
+        None => combine_time_position(timestamp, position),
+    }
+}
+
+/// Length of the record frame `start sign + size + content + end sign` at the position
+pub(crate) fn frame_len(buf: &[u8], position: usize) -> io::Result<usize> {
+    let corrupted = |reason: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} at {}", reason, position),
+        )
+    };
+    if buf.get(position) != Some(&RECORD_SIGNATURE_START) {
+        return Err(corrupted("ring record start signature not found"));
+    }
+    let (size, size_len) = usize::decode_var(&buf[position + 1..])
+        .ok_or_else(|| corrupted("ring record size invalid"))?;
+    let len = size
+        .checked_add(size_len + 2)
+        .ok_or_else(|| corrupted("ring record size invalid"))?;
+    if position.checked_add(len - 1).and_then(|end| buf.get(end)) != Some(&RECORD_SIGNATURE_END) {
+        return Err(corrupted("ring record end signature not found"));
+    }
+    Ok(len)
+}
+
+/// Position of the first record after the header and the extra
+pub(crate) fn data_start(header: &Header, buf: &[u8]) -> io::Result<u32> {
+    let mut start = header.length();
+    if header.has_extra() {
+        start += frame_len(buf, start)?;
+    }
+    Ok(start as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{
+        logger::xor_slice,
+        CipherKind,
+        EZLogConfigBuilder,
+        EZLogger,
+        EZRecord,
+    };
+
+    #[test]
+    fn test_ring_nonce_distinct() {
+        #[allow(deprecated)]
+        let kinds = [
+            #[cfg(feature = "decode")]
+            (CipherKind::AES128GCM, 16, 12),
+            #[cfg(feature = "decode")]
+            (CipherKind::AES256GCM, 32, 12),
+            (CipherKind::AES128GCMSIV, 16, 12),
+            (CipherKind::AES256GCMSIV, 32, 12),
+            (CipherKind::CHACHA20POLY1305, 32, 12),
+            (CipherKind::XCHACHA20POLY1305, 32, 24),
+        ];
+        for (kind, key_len, nonce_len) in kinds {
+            let config = EZLogConfigBuilder::new()
+                .cipher(kind)
+                .cipher_key(vec![7u8; key_len])
+                .cipher_nonce(vec![9u8; nonce_len])
+                .build();
+            let cryptor = crate::logger::create_cryptor(&config).unwrap().unwrap();
+            // the same content with the same key, the ciphertext differs only if the nonce does
+            let mut encrypted = HashSet::new();
+            for lap in [0, 1, 2, 0x100, 0x101] {
+                for position in [0, 1, 0x100, 0x101, 0x1_0000, u32::MAX as u64] {
+                    let combine = nonce_combine(1_700_000_000, position, Some(lap));
+                    let op = Box::new(move |input: &[u8]| xor_slice(input, &combine));
+                    assert!(
+                        encrypted.insert(cryptor.encrypt(b"ring record", op).unwrap()),
+                        "{} nonce repeats at lap {} position {}",
+                        kind,
+                        lap,
+                        position
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ring_state_wrap() {
+        let frame = |size: usize| {
+            let mut frame = vec![RECORD_SIGNATURE_START];
+            frame.extend(size.encode_var_vec());
+            frame.extend(vec![b'a'; size]);
+            frame.push(RECORD_SIGNATURE_END);
+            frame
+        };
+        let mut buf = vec![0u8; 8];
+        for _ in 0..4 {
+            buf.extend(frame(8));
+        }
+        let tail = buf.len() as u32;
+        assert_eq!(frame_len(&buf, 8).unwrap(), 11);
+
+        let mut ring = RingState::default();
+        assert!(ring.segments(tail).is_empty());
+        ring.wrap(8, tail);
+        ring.drop_overwritten(&buf, 8, 12).unwrap();
+        assert_eq!(ring.head, 30);
+        assert_eq!(ring.segments(20), vec![(30, 52, 0), (8, 20, 1)]);
+
+        ring.drop_overwritten(&buf, 20, 40).unwrap();
+        assert_eq!(ring.head, ring.end);
+        assert_eq!(ring.segments(60), vec![(8, 60, 1)]);
+    }
+
+    #[test]
+    fn test_ring_logger() {
+        let dir = test_compat::test_path().join("ring");
+        std::fs::remove_dir_all(&dir).unwrap_or_default();
+        let config = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("ring")
+            .extra("ring extra")
+            .max_size(1024)
+            .ring_buffer(true)
+            .cipher(crate::CipherKind::CHACHA20POLY1305)
+            .cipher_key(b"an example very very secret key.".to_vec())
+            .cipher_nonce(b"unique nonce".to_vec())
+            .build();
+        let logger = EZLogger::new(config.clone()).unwrap();
+        for i in 0..200 {
+            logger
+                .append(
+                    EZRecord::builder()
+                        .content(format!("ring {:03}", i))
+                        .build(),
+                )
+                .unwrap();
+        }
+        let lap = logger
+            .appender
+            .get_inner()
+            .unwrap()
+            .header()
+            .ring()
+            .unwrap()
+            .lap();
+        assert!(lap > 0);
+        drop(logger);
+
+        // reopen the ring file and continue
+        let logger = EZLogger::new(config.clone()).unwrap();
+        assert_eq!(
+            logger
+                .appender
+                .get_inner()
+                .unwrap()
+                .header()
+                .ring()
+                .unwrap()
+                .lap(),
+            lap
+        );
+        logger
+            .append(EZRecord::builder().content("ring 200").build())
+            .unwrap();
+        drop(logger);
+
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 1);
+
+        #[cfg(feature = "decode")]
+        {
+            let (path, _mmap) = config.create_mmap_file().unwrap();
+            let mut cursor = io::Cursor::new(std::fs::read(path).unwrap());
+            let header = Header::decode(&mut cursor).unwrap();
+            let cryptor = crate::logger::create_cryptor(&config).unwrap();
+            let mut contents = Vec::new();
+            crate::decode::decode_with_fn(&mut cursor, &None, &cryptor, &header, |data, is_end| {
+                if !is_end {
+                    contents.push(String::from_utf8_lossy(data).to_string());
+                }
+                Some(0)
+            });
+            assert_eq!(contents[0], "ring extra");
+            let records: Vec<usize> = contents[1..]
+                .iter()
+                .map(|c| c[c.len() - 3..].parse().unwrap())
+                .collect();
+            assert!(records[0] > 0);
+            assert_eq!(*records.last().unwrap(), 200);
+            assert!(records.windows(2).all(|w| w[1] == w[0] + 1));
+        }
+        std::fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+}
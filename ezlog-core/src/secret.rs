@@ -0,0 +1,101 @@
+use core::fmt;
+use std::str::FromStr;
+
+use zeroize::Zeroize;
+
+/// Bytes of key material, such as the cipher key, nonce and passphrase
+///
+/// it is redacted in `Debug` and zeroized on drop.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct SecretBytes(Vec<u8>);
+
+impl SecretBytes {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        SecretBytes(bytes)
+    }
+
+    /// The raw bytes, do not keep a copy of them
+    #[inline]
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes(<redacted>)")
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        SecretBytes(bytes)
+    }
+}
+
+impl From<&[u8]> for SecretBytes {
+    fn from(bytes: &[u8]) -> Self {
+        SecretBytes(bytes.to_vec())
+    }
+}
+
+impl From<&str> for SecretBytes {
+    fn from(s: &str) -> Self {
+        SecretBytes(s.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for SecretBytes {
+    fn from(s: String) -> Self {
+        SecretBytes(s.into_bytes())
+    }
+}
+
+/// The UTF-8 bytes of the string
+impl FromStr for SecretBytes {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(SecretBytes::from(s))
+    }
+}
+
+/// Deserialize from a string, it is never serialized
+#[cfg(feature = "json")]
+impl<'de> serde::Deserialize<'de> for SecretBytes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(SecretBytes::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_redacted() {
+        let secret = SecretBytes::from("an example very very secret key.");
+        let debug = format!("{:?}", Some(&secret));
+        assert!(!debug.contains("secret key"));
+        assert_eq!(secret.expose(), b"an example very very secret key.");
+    }
+}
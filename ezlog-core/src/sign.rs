@@ -0,0 +1,262 @@
+use std::{
+    fs::{
+        self,
+        File,
+    },
+    io::{
+        BufReader,
+        Cursor,
+        Read,
+        Seek,
+        SeekFrom,
+        Write,
+    },
+    path::Path,
+};
+
+use ed25519_dalek::{
+    Signature,
+    Signer,
+    SigningKey,
+    VerifyingKey,
+    PUBLIC_KEY_LENGTH,
+    SECRET_KEY_LENGTH,
+    SIGNATURE_LENGTH,
+};
+
+use crate::{
+    errors::LogError,
+    logger::Flags,
+    Header,
+    Result,
+};
+
+const SIGNATURE_FOOTER_MAGIC: &[u8; 4] = b"ezsg";
+
+/// Length of the footer appended to a sealed log file
+///
+/// `magic(4) + public key(32) + signature(64)`, the signature covers all the bytes before the footer.
+pub const SIGNATURE_FOOTER_LEN: usize =
+    SIGNATURE_FOOTER_MAGIC.len() + PUBLIC_KEY_LENGTH + SIGNATURE_LENGTH;
+
+fn signing_key(seed: &[u8]) -> Result<SigningKey> {
+    let seed: &[u8; SECRET_KEY_LENGTH] = seed.try_into().map_err(|_| {
+        LogError::Crypto(format!(
+            "signing key length must be {}, but is {}",
+            SECRET_KEY_LENGTH,
+            seed.len()
+        ))
+    })?;
+    Ok(SigningKey::from_bytes(seed))
+}
+
+/// The Ed25519 public key of the signing key seed, give it to the verifier
+pub fn public_key(signing_key_seed: &[u8]) -> Result<[u8; PUBLIC_KEY_LENGTH]> {
+    signing_key(signing_key_seed).map(|k| k.verifying_key().to_bytes())
+}
+
+/// Mark the finished log file as sealed, then append the signature footer
+///
+/// the sealed file is written to a hidden temp file and renamed over the log file, so the log file
+/// is either not sealed or sealed with the footer. the file already sealed is kept.
+pub(crate) fn seal_file(path: &Path, signing_key_seed: &[u8]) -> Result<()> {
+    let key = signing_key(signing_key_seed)?;
+    let mut content = fs::read(path)?;
+    let mut header = Header::decode(&mut Cursor::new(&content))?;
+    if header.is_sealed() && has_footer(&content) {
+        return Ok(());
+    }
+    header.flag |= Flags::SEALED;
+    header.encode(&mut Cursor::new(&mut content[..]))?;
+    let signature = key.sign(&content);
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| LogError::Illegal(format!("log file name is illegal {:?}", path)))?;
+    // not started with the logger name, it is never taken as a rotated file
+    let sealing = path.with_file_name(format!(".{}.sealing", file_name));
+    let mut file = File::create(&sealing)?;
+    file.write_all(&content)?;
+    file.write_all(SIGNATURE_FOOTER_MAGIC)?;
+    file.write_all(key.verifying_key().as_bytes())?;
+    file.write_all(&signature.to_bytes())?;
+    file.sync_all()?;
+    fs::rename(&sealing, path)?;
+    Ok(())
+}
+
+/// Whether the log file is sealed with the signature footer, the signature is not verified
+pub(crate) fn is_sealed(path: &Path) -> Result<bool> {
+    let mut file = File::open(path)?;
+    let header = Header::decode(&mut BufReader::new(&file))?;
+    if !header.is_sealed() || file.metadata()?.len() < SIGNATURE_FOOTER_LEN as u64 {
+        return Ok(false);
+    }
+    let mut footer = [0u8; SIGNATURE_FOOTER_LEN];
+    file.seek(SeekFrom::End(-(SIGNATURE_FOOTER_LEN as i64)))?;
+    file.read_exact(&mut footer)?;
+    Ok(has_footer(&footer))
+}
+
+fn has_footer(file: &[u8]) -> bool {
+    file.len()
+        .checked_sub(SIGNATURE_FOOTER_LEN)
+        .is_some_and(|split| file[split..].starts_with(SIGNATURE_FOOTER_MAGIC))
+}
+
+/// Verify the signature footer of the log file by the Ed25519 public key
+///
+/// it fails if the file is not sealed, sealed by another key, or modified after sealed.
+pub fn verify_signature(file: &[u8], public_key: &[u8]) -> Result<()> {
+    if !has_footer(file) {
+        return Err(LogError::Crypto("log file is not signed".to_string()));
+    }
+    let (content, footer) = file.split_at(file.len() - SIGNATURE_FOOTER_LEN);
+    let footer = &footer[SIGNATURE_FOOTER_MAGIC.len()..];
+    let (signed_by, signature) = footer.split_at(PUBLIC_KEY_LENGTH);
+    if signed_by != public_key {
+        return Err(LogError::Crypto(
+            "log file is signed by another key".to_string(),
+        ));
+    }
+    let public_key: &[u8; PUBLIC_KEY_LENGTH] = public_key
+        .try_into()
+        .map_err(|_| LogError::Crypto("public key length invalid".to_string()))?;
+    let verifying_key = VerifyingKey::from_bytes(public_key)
+        .map_err(|e| LogError::Crypto(format!("public key invalid {}", e)))?;
+    let signature = Signature::from_slice(signature)
+        .map_err(|e| LogError::Crypto(format!("signature invalid {}", e)))?;
+    verifying_key
+        .verify_strict(content, &signature)
+        .map_err(|_| LogError::Crypto("signature mismatch, log file is modified".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use time::OffsetDateTime;
+
+    use super::*;
+    use crate::{
+        EZLogConfigBuilder,
+        EZLogger,
+        EZRecord,
+    };
+
+    #[test]
+    fn test_seal_and_verify() {
+        let dir = test_compat::test_path().join("seal");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let seed = [7u8; SECRET_KEY_LENGTH];
+        let config = EZLogConfigBuilder::new()
+            .dir_path(&dir)
+            .name("seal")
+            .extra("signed")
+            .signing_key(seed.to_vec())
+            .build();
+        assert!(config.check_valid().is_ok());
+        assert!(EZLogConfigBuilder::new()
+            .signing_key(b"short".to_vec())
+            .build()
+            .check_valid()
+            .is_err());
+
+        let logger = EZLogger::new(config.clone()).unwrap();
+        for i in 0..3 {
+            logger
+                .append(EZRecord::builder().content(format!("signed {}", i)).build())
+                .unwrap();
+        }
+        logger.rotate_if_not_empty().unwrap();
+        drop(logger);
+
+        let rotated = config.query_log_files_for_date(OffsetDateTime::now_utc());
+        assert_eq!(rotated.len(), 1);
+        // sealed on the rotate thread
+        for _ in 0..500 {
+            if !crate::hook::is_pending(&rotated[0]) {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let file = fs::read(&rotated[0]).unwrap();
+        let header = Header::decode(&mut Cursor::new(&file)).unwrap();
+        assert!(header.is_sealed());
+        assert_eq!(
+            config.manifest_entries().unwrap()[0].size(),
+            file.len() as u64
+        );
+        assert!(header.has_extra());
+
+        let public_key = public_key(&seed).unwrap();
+        assert!(verify_signature(&file, &public_key).is_ok());
+        let other = crate::public_key(&[8u8; SECRET_KEY_LENGTH]).unwrap();
+        assert!(verify_signature(&file, &other).is_err());
+
+        #[cfg(feature = "decode")]
+        {
+            let mut contents = Vec::new();
+            let mut cursor = Cursor::new(file.clone());
+            Header::decode(&mut cursor).unwrap();
+            crate::decode::decode_with_fn(&mut cursor, &None, &None, &header, |data, is_end| {
+                if is_end {
+                    return None;
+                }
+                contents.push(String::from_utf8_lossy(data).to_string());
+                Some(0)
+            });
+            // the extra and the records, the footer is not decoded
+            assert_eq!(contents.len(), 4);
+            assert!(contents[3].contains("signed 2"));
+        }
+
+        let mut tampered = file.clone();
+        tampered[header.length() + 4] ^= 0x01;
+        assert!(verify_signature(&tampered, &public_key).is_err());
+        assert!(verify_signature(&file[..file.len() - SIGNATURE_FOOTER_LEN], &public_key).is_err());
+
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_reseal_on_start() {
+        let dir = test_compat::test_path().join("reseal");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let seed = [9u8; SECRET_KEY_LENGTH];
+        let builder = EZLogConfigBuilder::new().dir_path(&dir).name("reseal");
+        // the process exits before the file is sealed
+        let unsigned = builder.clone().build();
+        let logger = EZLogger::new(unsigned.clone()).unwrap();
+        logger
+            .append(EZRecord::builder().content("unsealed").build())
+            .unwrap();
+        logger.rotate_if_not_empty().unwrap();
+        drop(logger);
+        let rotated = unsigned.query_log_files_for_date(OffsetDateTime::now_utc());
+        assert_eq!(rotated.len(), 1);
+        assert!(!is_sealed(&rotated[0]).unwrap());
+
+        let config = builder.signing_key(seed.to_vec()).build();
+        drop(EZLogger::new(config.clone()).unwrap());
+        let public_key = public_key(&seed).unwrap();
+        let file = fs::read(&rotated[0]).unwrap();
+        assert!(verify_signature(&file, &public_key).is_ok());
+        assert_eq!(
+            config.manifest_entries().unwrap()[0].size(),
+            file.len() as u64
+        );
+
+        // the header is sealed but the footer is not written
+        fs::write(&rotated[0], &file[..file.len() - SIGNATURE_FOOTER_LEN]).unwrap();
+        assert!(!is_sealed(&rotated[0]).unwrap());
+        drop(EZLogger::new(config.clone()).unwrap());
+        assert_eq!(fs::read(&rotated[0]).unwrap(), file);
+
+        // the sealed file is kept, no temp file is left
+        seal_file(&rotated[0], &seed).unwrap();
+        assert_eq!(fs::read(&rotated[0]).unwrap(), file);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+}
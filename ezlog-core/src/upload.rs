@@ -0,0 +1,940 @@
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        Arc,
+        Weak,
+    },
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::{
+    RecvTimeoutError,
+    Sender,
+};
+use parking_lot::Mutex;
+use time::OffsetDateTime;
+
+use crate::{
+    events::event,
+    Event,
+    Header,
+    LogError,
+    Result,
+    RotateHook,
+};
+
+const UPLOAD_MAGIC: &str = "ezlog upload 1";
+/// The state of the queue is saved in this file of the state dir
+pub(crate) const UPLOAD_STATE_FILE: &str = "upload.state";
+/// The broken state file is moved to this file of the state dir
+pub(crate) const UPLOAD_CORRUPT_FILE: &str = "upload.state.corrupt";
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// Upload a sealed log file, it is called on the `ezlog_upload` thread
+///
+/// the file is retried with exponential backoff if an error is returned.
+pub trait Uploader: Send + Sync {
+    fn upload(&self, name: &str, path: &Path) -> std::result::Result<(), LogError>;
+}
+
+/// What to do with the file after it is uploaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadAction {
+    /// remove the file and forget it
+    Delete,
+    /// keep the file, it is marked as [UploadStatus::Uploaded] in the state file
+    Keep,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadStatus {
+    Pending,
+    Uploaded,
+    /// the max attempts are used up
+    Failed,
+}
+
+impl UploadStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            UploadStatus::Pending => "pending",
+            UploadStatus::Uploaded => "uploaded",
+            UploadStatus::Failed => "failed",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(UploadStatus::Pending),
+            "uploaded" => Some(UploadStatus::Uploaded),
+            "failed" => Some(UploadStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// A file in the upload queue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadItem {
+    /// the logger name
+    pub(crate) name: String,
+    pub(crate) path: PathBuf,
+    /// file size in bytes when it is queued
+    pub(crate) size: u64,
+    /// count of the failed attempts
+    pub(crate) attempts: u32,
+    /// the time of the next attempt
+    pub(crate) next_attempt: OffsetDateTime,
+    pub(crate) status: UploadStatus,
+}
+
+impl UploadItem {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub fn next_attempt(&self) -> OffsetDateTime {
+        self.next_attempt
+    }
+
+    pub fn status(&self) -> UploadStatus {
+        self.status
+    }
+
+    fn encode(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            escape(&self.name),
+            escape(&self.path.to_string_lossy()),
+            self.size,
+            self.attempts,
+            unix_millis(self.next_attempt),
+            self.status.as_str()
+        )
+    }
+
+    fn decode(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let item = UploadItem {
+            name: unescape(fields.next()?)?,
+            path: PathBuf::from(unescape(fields.next()?)?),
+            size: fields.next()?.parse().ok()?,
+            attempts: fields.next()?.parse().ok()?,
+            next_attempt: from_unix_millis(fields.next()?.parse().ok()?)?,
+            status: UploadStatus::parse(fields.next()?)?,
+        };
+        Some(item)
+    }
+}
+
+/// Build an [UploadQueue]
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use std::time::Duration;
+///
+/// use ezlog::{
+///     LogError,
+///     UploadAction,
+///     UploadQueueBuilder,
+///     Uploader,
+/// };
+///
+/// struct Server;
+///
+/// impl Uploader for Server {
+///     fn upload(&self, name: &str, path: &Path) -> Result<(), LogError> {
+///         println!("upload {} {:?}", name, path);
+///         Ok(())
+///     }
+/// }
+///
+/// let queue = UploadQueueBuilder::new(std::env::temp_dir().join("ezlog_upload_doc"))
+///     .initial_backoff(Duration::from_secs(10))
+///     .max_attempts(8)
+///     .after_success(UploadAction::Delete)
+///     .daily_limit(50 * 1024 * 1024)
+///     .build(Server)
+///     .unwrap();
+/// queue.start().unwrap();
+/// ezlog::InitBuilder::new()
+///     .with_rotate_hook(queue.clone())
+///     .init();
+/// ```
+#[derive(Debug, Clone)]
+pub struct UploadQueueBuilder {
+    state_dir: PathBuf,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_attempts: Option<u32>,
+    after_success: UploadAction,
+    max_queue_size: Option<u64>,
+    daily_limit: Option<u64>,
+}
+
+impl UploadQueueBuilder {
+    /// the state file is saved in `state_dir`
+    pub fn new(state_dir: impl AsRef<Path>) -> Self {
+        UploadQueueBuilder {
+            state_dir: state_dir.as_ref().to_path_buf(),
+            initial_backoff: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(6 * 60 * 60),
+            max_attempts: None,
+            after_success: UploadAction::Delete,
+            max_queue_size: None,
+            daily_limit: None,
+        }
+    }
+
+    /// the delay after the first failure, it is doubled after every failure
+    #[inline]
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    #[inline]
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// the file is marked as [UploadStatus::Failed] after the attempts, retry forever if not set
+    #[inline]
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    #[inline]
+    pub fn after_success(mut self, action: UploadAction) -> Self {
+        self.after_success = action;
+        self
+    }
+
+    /// the max size of the pending files, the oldest ones are dropped from the queue when it is
+    /// over, the files are not removed
+    #[inline]
+    pub fn max_queue_size(mut self, size: u64) -> Self {
+        self.max_queue_size = Some(size);
+        self
+    }
+
+    /// the max bytes uploaded in a UTC day, the rest wait for the next day
+    ///
+    /// the first file of a day is always uploaded, even if it is larger than the limit.
+    #[inline]
+    pub fn daily_limit(mut self, size: u64) -> Self {
+        self.daily_limit = Some(size);
+        self
+    }
+
+    /// Load the state file and create the queue, the uploads start after [UploadQueue::start]
+    pub fn build(self, uploader: impl Uploader + 'static) -> Result<UploadQueue> {
+        fs::create_dir_all(&self.state_dir)?;
+        let state = State::load(self.state_dir.join(UPLOAD_STATE_FILE))?;
+        Ok(UploadQueue {
+            inner: Arc::new(Inner {
+                config: self,
+                uploader: Box::new(uploader),
+                state: Mutex::new(state),
+                upload_lock: Mutex::new(()),
+                notify: Mutex::new(None),
+            }),
+        })
+    }
+}
+
+/// A persistent queue of the sealed files to upload
+///
+/// the files are uploaded by the [Uploader] one by one from the oldest, the progress is saved in
+/// `upload.state` of the state dir, so the queue is restored after restart.
+/// it can be set as the [RotateHook] to queue every rotated file.
+#[derive(Clone)]
+pub struct UploadQueue {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: UploadQueueBuilder,
+    uploader: Box<dyn Uploader>,
+    state: Mutex<State>,
+    /// only one thread uploads at a time
+    upload_lock: Mutex<()>,
+    /// wake up the upload thread, it stops when the sender is dropped
+    notify: Mutex<Option<Sender<()>>>,
+}
+
+impl UploadQueue {
+    /// Add the file to the queue, the file already in the queue is ignored
+    pub fn enqueue(&self, name: &str, path: &Path) -> Result<()> {
+        let size = fs::metadata(path)?.len();
+        {
+            let mut state = self.inner.state.lock();
+            if state.items.iter().any(|item| item.path == path) {
+                return Ok(());
+            }
+            event!(Event::Upload, "enqueue {:?}", path);
+            state.items.push(UploadItem {
+                name: name.to_string(),
+                path: path.to_path_buf(),
+                size,
+                attempts: 0,
+                next_attempt: now_millis(),
+                status: UploadStatus::Pending,
+            });
+            if let Some(max_size) = self.inner.config.max_queue_size {
+                state.drop_oldest(max_size);
+            }
+            state.save()?;
+        }
+        self.notify();
+        Ok(())
+    }
+
+    /// The files in the queue, include the uploaded files which are kept
+    pub fn items(&self) -> Vec<UploadItem> {
+        self.inner.state.lock().items.clone()
+    }
+
+    /// Upload the pending files which are due, every file is tried once at most, returns the count
+    /// of the uploaded files
+    pub fn upload_due(&self) -> usize {
+        let _guard = self.inner.upload_lock.lock();
+        let mut tried = Vec::new();
+        let mut uploaded = 0;
+        loop {
+            let now = OffsetDateTime::now_utc();
+            let next = {
+                let state = self.inner.state.lock();
+                let next = state
+                    .pending()
+                    .filter(|item| !tried.contains(&item.path))
+                    .find(|item| self.ready_at(&state, item, now) <= now)
+                    .cloned();
+                next
+            };
+            let Some(item) = next else {
+                return uploaded;
+            };
+            tried.push(item.path.clone());
+            let result = if item.path.is_file() {
+                self.inner.uploader.upload(&item.name, &item.path)
+            } else {
+                Err(LogError::Illegal(format!("{:?} is removed", item.path)))
+            };
+            if result.is_ok() {
+                uploaded += 1;
+            }
+            self.complete(&item, result)
+                .unwrap_or_else(|e| event!(!Event::UploadError, "save upload state"; &e));
+        }
+    }
+
+    /// Start the `ezlog_upload` thread, it uploads the files when they are due
+    ///
+    /// the thread stops after [UploadQueue::stop] or when every handle of the queue is dropped
+    pub fn start(&self) -> Result<()> {
+        let (sender, receiver) = crossbeam_channel::unbounded::<()>();
+        let inner: Weak<Inner> = Arc::downgrade(&self.inner);
+        thread::Builder::new()
+            .name("ezlog_upload".to_string())
+            .spawn(move || loop {
+                // the thread holds the queue only while uploading, so it never keeps the sender
+                let Some(inner) = inner.upgrade() else {
+                    break;
+                };
+                let queue = UploadQueue { inner };
+                queue.upload_due();
+                let wait = queue
+                    .next_due()
+                    .map(|due| (due - OffsetDateTime::now_utc()).max(time::Duration::ZERO))
+                    .and_then(|wait| Duration::try_from(wait).ok())
+                    .unwrap_or(Duration::MAX);
+                drop(queue);
+                match receiver.recv_timeout(wait) {
+                    Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            })?;
+        // the old thread stops when its sender is dropped
+        *self.inner.notify.lock() = Some(sender);
+        Ok(())
+    }
+
+    /// Stop the `ezlog_upload` thread after the current upload
+    pub fn stop(&self) {
+        self.inner.notify.lock().take();
+    }
+
+    fn notify(&self) {
+        if let Some(sender) = self.inner.notify.lock().as_ref() {
+            sender.try_send(()).unwrap_or_default();
+        }
+    }
+
+    /// The time the next pending file can be uploaded
+    fn next_due(&self) -> Option<OffsetDateTime> {
+        let now = OffsetDateTime::now_utc();
+        let state = self.inner.state.lock();
+        let due = state
+            .pending()
+            .map(|item| self.ready_at(&state, item, now))
+            .min();
+        due
+    }
+
+    /// The file waits for the backoff, and for the next day if it is over the daily limit
+    fn ready_at(&self, state: &State, item: &UploadItem, now: OffsetDateTime) -> OffsetDateTime {
+        let today = now.unix_timestamp().div_euclid(SECONDS_PER_DAY);
+        let used = if state.day == today { state.used } else { 0 };
+        match self.inner.config.daily_limit {
+            Some(limit) if used > 0 && used + item.size > limit => {
+                let tomorrow = OffsetDateTime::from_unix_timestamp((today + 1) * SECONDS_PER_DAY)
+                    .unwrap_or(now);
+                item.next_attempt.max(tomorrow)
+            }
+            _ => item.next_attempt,
+        }
+    }
+
+    fn complete(&self, item: &UploadItem, result: Result<()>) -> Result<()> {
+        let config = &self.inner.config;
+        let now = OffsetDateTime::now_utc();
+        let mut state = self.inner.state.lock();
+        let Some(index) = state.items.iter().position(|i| i.path == item.path) else {
+            return Ok(());
+        };
+        match result {
+            Ok(()) => {
+                event!(Event::UploadEnd, "{}", item.path.display());
+                let today = now.unix_timestamp().div_euclid(SECONDS_PER_DAY);
+                if state.day != today {
+                    state.day = today;
+                    state.used = 0;
+                }
+                state.used += item.size;
+                match config.after_success {
+                    UploadAction::Delete => {
+                        fs::remove_file(&item.path).unwrap_or_else(
+                            |e| event!(!Event::UploadError, "remove uploaded file"; &e.into()),
+                        );
+                        state.items.remove(index);
+                    }
+                    UploadAction::Keep => state.items[index].status = UploadStatus::Uploaded,
+                }
+            }
+            Err(e) => {
+                let entry = &mut state.items[index];
+                entry.attempts += 1;
+                if !entry.path.is_file()
+                    || config.max_attempts.is_some_and(|max| entry.attempts >= max)
+                {
+                    event!(!Event::UploadError, "give up {}", entry.path.display(); &e);
+                    entry.status = UploadStatus::Failed;
+                } else {
+                    event!(!Event::UploadError, "retry {}", entry.path.display(); &e);
+                    let backoff = config
+                        .initial_backoff
+                        .saturating_mul(1 << (entry.attempts - 1).min(31))
+                        .min(config.max_backoff);
+                    entry.next_attempt = now_millis() + backoff;
+                }
+            }
+        }
+        state.save()
+    }
+}
+
+impl RotateHook for UploadQueue {
+    fn on_rotate(&self, name: &str, path: &Path, _header: &Header) -> Result<()> {
+        self.enqueue(name, path)
+    }
+}
+
+struct State {
+    path: PathBuf,
+    items: Vec<UploadItem>,
+    /// the UTC day of `used`, in days since the unix epoch
+    day: i64,
+    /// bytes uploaded in the day
+    used: u64,
+}
+
+impl State {
+    /// Load the state file, the uploaded files which are removed by others are forgotten
+    ///
+    /// the broken lines are skipped, and the broken file is kept as [UPLOAD_CORRUPT_FILE].
+    /// it is saved only if it is missing or changed.
+    fn load(path: PathBuf) -> Result<Self> {
+        let mut state = State {
+            path,
+            items: Vec::new(),
+            day: 0,
+            used: 0,
+        };
+        let content = match fs::read(&state.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                state.save()?;
+                return Ok(state);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut changed = false;
+        match String::from_utf8(content)
+            .ok()
+            .and_then(|content| Self::decode(&content))
+        {
+            Some((day, used, items, broken)) => {
+                state.day = day;
+                state.used = used;
+                state.items = items;
+                if broken > 0 {
+                    event!(
+                        Event::UploadError,
+                        "skip {} broken lines of upload state",
+                        broken
+                    );
+                    state.quarantine();
+                    changed = true;
+                }
+            }
+            None => {
+                event!(Event::UploadError, "upload state is broken");
+                state.quarantine();
+                changed = true;
+            }
+        }
+        let count = state.items.len();
+        state
+            .items
+            .retain(|item| item.status != UploadStatus::Uploaded || item.path.is_file());
+        if changed || count != state.items.len() {
+            state.save()?;
+        }
+        Ok(state)
+    }
+
+    /// Keep a copy of the broken state file
+    fn quarantine(&self) {
+        let corrupt = self.path.with_file_name(UPLOAD_CORRUPT_FILE);
+        fs::copy(&self.path, corrupt)
+            .map(|_| ())
+            .unwrap_or_else(|e| event!(!Event::UploadError, "keep broken upload state"; &e.into()));
+    }
+
+    fn pending(&self) -> impl Iterator<Item = &UploadItem> {
+        self.items
+            .iter()
+            .filter(|item| item.status == UploadStatus::Pending)
+    }
+
+    /// Drop the oldest pending files until the pending size is not over `max_size`
+    fn drop_oldest(&mut self, max_size: u64) {
+        let mut size: u64 = self.pending().map(|item| item.size).sum();
+        while size > max_size && self.pending().count() > 1 {
+            let Some(index) = self
+                .items
+                .iter()
+                .position(|item| item.status == UploadStatus::Pending)
+            else {
+                break;
+            };
+            let item = self.items.remove(index);
+            event!(
+                Event::UploadError,
+                "queue is over {} bytes, drop {:?}",
+                max_size,
+                item.path
+            );
+            size -= item.size;
+        }
+    }
+
+    /// Decode the day, the used bytes, the items and the count of the broken lines
+    fn decode(content: &str) -> Option<(i64, u64, Vec<UploadItem>, usize)> {
+        let mut lines = content.lines();
+        if lines.next()? != UPLOAD_MAGIC {
+            return None;
+        }
+        let mut used = lines.next()?.split('\t');
+        let day = used.next()?.parse().ok()?;
+        let used = used.next()?.parse().ok()?;
+        let mut items = Vec::new();
+        let mut broken = 0;
+        for line in lines {
+            match UploadItem::decode(line) {
+                Some(item) => items.push(item),
+                None => broken += 1,
+            }
+        }
+        Some((day, used, items, broken))
+    }
+
+    /// Write to a temporary file and rename it, the state is never seen half written
+    fn save(&self) -> Result<()> {
+        let mut content = format!("{}\n{}\t{}", UPLOAD_MAGIC, self.day, self.used);
+        for item in &self.items {
+            content.push('\n');
+            content.push_str(&item.encode());
+        }
+        let tmp = self.path.with_extension("state.tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Escape the backslash, tab and line breaks in the name and path of the state file
+fn escape(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(field: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => unescaped.push('\\'),
+            't' => unescaped.push('\t'),
+            'n' => unescaped.push('\n'),
+            'r' => unescaped.push('\r'),
+            _ => return None,
+        }
+    }
+    Some(unescaped)
+}
+
+/// The time in millis, the same as it is saved in the state file
+fn now_millis() -> OffsetDateTime {
+    let now = OffsetDateTime::now_utc();
+    now.replace_millisecond(now.millisecond()).unwrap_or(now)
+}
+
+fn unix_millis(time: OffsetDateTime) -> i64 {
+    (time.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+fn from_unix_millis(millis: i64) -> Option<OffsetDateTime> {
+    OffsetDateTime::from_unix_timestamp_nanos(millis as i128 * 1_000_000).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{
+            BufRead,
+            BufReader,
+            Read,
+            Write,
+        },
+        net::{
+            TcpListener,
+            TcpStream,
+        },
+        sync::atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+        time::Instant,
+    };
+
+    use super::*;
+
+    /// Post the file to the stand-in server
+    struct HttpUploader {
+        addr: String,
+    }
+
+    impl Uploader for HttpUploader {
+        fn upload(&self, name: &str, path: &Path) -> Result<()> {
+            let body = fs::read(path)?;
+            let mut stream = TcpStream::connect(&self.addr)?;
+            write!(
+                stream,
+                "POST /upload/{} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                name,
+                self.addr,
+                body.len()
+            )?;
+            stream.write_all(&body)?;
+            let mut response = String::new();
+            stream.read_to_string(&mut response)?;
+            if response.starts_with("HTTP/1.1 200") {
+                Ok(())
+            } else {
+                Err(LogError::Illegal(response))
+            }
+        }
+    }
+
+    /// Read the url and body of a request to the stand-in server
+    fn read_request(stream: &TcpStream) -> (String, Vec<u8>) {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let url = line.split(' ').nth(1).unwrap().to_string();
+        let mut len = 0;
+        loop {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                len = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body).unwrap();
+        (url, body)
+    }
+
+    struct CountUploader(AtomicUsize);
+
+    impl Uploader for CountUploader {
+        fn upload(&self, _name: &str, _path: &Path) -> Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn create_files(dir: &Path, count: usize, size: usize) -> Vec<PathBuf> {
+        fs::create_dir_all(dir).unwrap();
+        (0..count)
+            .map(|i| {
+                let path = dir.join(format!("upload_{}.mmap", i));
+                fs::write(&path, vec![i as u8; size]).unwrap();
+                path
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_upload_retry() {
+        let dir = test_compat::test_path().join("upload_retry");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let files = create_files(&dir, 2, 100);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let server_received = received.clone();
+        thread::spawn(move || {
+            for (index, stream) in listener.incoming().enumerate() {
+                let (url, body) = read_request(stream.as_ref().unwrap());
+                // fail the first request
+                let status = if index == 0 {
+                    "500 Internal Server Error"
+                } else {
+                    server_received.lock().push((url, body));
+                    "200 OK"
+                };
+                write!(
+                    stream.unwrap(),
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+        });
+
+        let queue = UploadQueueBuilder::new(&dir)
+            .initial_backoff(Duration::from_millis(50))
+            .build(HttpUploader { addr })
+            .unwrap();
+        queue.start().unwrap();
+        for file in &files {
+            queue.enqueue("app", file).unwrap();
+        }
+
+        let begin = Instant::now();
+        while !queue.items().is_empty() && begin.elapsed() < Duration::from_secs(10) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        queue.stop();
+        assert!(queue.items().is_empty());
+        assert!(files.iter().all(|file| !file.exists()));
+        let received = received.lock();
+        assert_eq!(received.len(), 2);
+        assert!(received
+            .iter()
+            .all(|(url, body)| url == "/upload/app" && body.len() == 100));
+
+        let state = fs::read_to_string(dir.join(UPLOAD_STATE_FILE)).unwrap();
+        assert_eq!(state.lines().count(), 2);
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_upload_quota() {
+        let dir = test_compat::test_path().join("upload_quota");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let files = create_files(&dir, 4, 10);
+
+        let queue = UploadQueueBuilder::new(&dir)
+            .after_success(UploadAction::Keep)
+            .max_queue_size(30)
+            .daily_limit(25)
+            .build(CountUploader(AtomicUsize::new(0)))
+            .unwrap();
+        for file in &files {
+            queue.enqueue("app", file).unwrap();
+        }
+        // the oldest is dropped
+        let items = queue.items();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].path(), files[1]);
+
+        // the third waits for the next day
+        assert_eq!(queue.upload_due(), 2);
+        let items = queue.items();
+        assert!(files.iter().all(|file| file.exists()));
+        assert_eq!(items[0].status(), UploadStatus::Uploaded);
+        assert_eq!(items[1].status(), UploadStatus::Uploaded);
+        assert_eq!(items[2].status(), UploadStatus::Pending);
+        let today = OffsetDateTime::now_utc()
+            .unix_timestamp()
+            .div_euclid(SECONDS_PER_DAY);
+        assert_eq!(
+            queue.next_due().unwrap().unix_timestamp(),
+            (today + 1) * SECONDS_PER_DAY
+        );
+
+        // the state is restored
+        let restored = UploadQueueBuilder::new(&dir)
+            .build(CountUploader(AtomicUsize::new(0)))
+            .unwrap();
+        assert_eq!(restored.items(), items);
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_upload_state_recover() {
+        let dir = test_compat::test_path().join("upload_state");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        fs::create_dir_all(&dir).unwrap();
+        let files = vec![dir.join("tab\tname.mmap"), dir.join("line\nname.mmap")];
+        for file in &files {
+            fs::write(file, "upload").unwrap();
+        }
+
+        // the names and paths with tab and line break are escaped
+        let queue = UploadQueueBuilder::new(&dir)
+            .build(CountUploader(AtomicUsize::new(0)))
+            .unwrap();
+        for file in &files {
+            queue.enqueue("app\t1", file).unwrap();
+        }
+        let items = queue.items();
+        let state_path = dir.join(UPLOAD_STATE_FILE);
+        let state = fs::read_to_string(&state_path).unwrap();
+        assert_eq!(state.lines().count(), 4);
+        let restored = UploadQueueBuilder::new(&dir)
+            .build(CountUploader(AtomicUsize::new(0)))
+            .unwrap();
+        assert_eq!(restored.items(), items);
+        assert!(!dir.join(UPLOAD_CORRUPT_FILE).exists());
+
+        // a broken line is skipped, the other items are kept
+        fs::write(&state_path, format!("{}\nbroken line", state)).unwrap();
+        let restored = UploadQueueBuilder::new(&dir)
+            .build(CountUploader(AtomicUsize::new(0)))
+            .unwrap();
+        assert_eq!(restored.items(), items);
+        assert!(fs::read_to_string(dir.join(UPLOAD_CORRUPT_FILE))
+            .unwrap()
+            .ends_with("broken line"));
+        assert_eq!(fs::read_to_string(&state_path).unwrap(), state);
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_upload_max_attempts() {
+        let dir = test_compat::test_path().join("upload_attempts");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        let files = create_files(&dir, 1, 10);
+
+        let queue = UploadQueueBuilder::new(&dir)
+            .initial_backoff(Duration::ZERO)
+            .max_attempts(3)
+            .build(HttpUploader {
+                addr: "127.0.0.1:1".into(),
+            })
+            .unwrap();
+        queue.enqueue("app", &files[0]).unwrap();
+        for _ in 0..4 {
+            assert_eq!(queue.upload_due(), 0);
+        }
+        let items = queue.items();
+        assert_eq!(items[0].attempts(), 3);
+        assert_eq!(items[0].status(), UploadStatus::Failed);
+        assert!(queue.next_due().is_none());
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    /// Tell the test when the queue is dropped
+    struct DropUploader(Sender<()>);
+
+    impl Uploader for DropUploader {
+        fn upload(&self, _name: &str, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Drop for DropUploader {
+        fn drop(&mut self) {
+            self.0.send(()).unwrap_or_default();
+        }
+    }
+
+    #[test]
+    fn test_upload_thread_exit() {
+        let dir = test_compat::test_path().join("upload_exit");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        fs::create_dir_all(&dir).unwrap();
+
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let queue = UploadQueueBuilder::new(&dir)
+            .build(DropUploader(sender))
+            .unwrap();
+        queue.start().unwrap();
+        // drop every handle without stop, the thread must not keep the queue
+        drop(queue);
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+}
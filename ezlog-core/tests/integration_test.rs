@@ -1,12 +1,16 @@
 use std::fmt::Arguments;
 
 use ezlog::Event;
+use ezlog::FetchMode;
 use ezlog::LogError;
 use ezlog::{
     EZLogConfigBuilder,
     EZRecord,
 };
-use time::OffsetDateTime;
+use time::{
+    Duration,
+    OffsetDateTime,
+};
 
 #[test]
 fn test_ezlog_init() {
@@ -90,3 +94,44 @@ fn test_ezlog_request() {
     test_logger_create();
     ezlog::request_log_files_for_date("test", OffsetDateTime::now_utc(), OffsetDateTime::now_utc());
 }
+
+#[test]
+fn test_ezlog_fetch_per_request() {
+    ezlog::InitBuilder::new().debug(true).init();
+    let dir = test_compat::test_path().join("ezlog_fetch");
+    std::fs::remove_dir_all(&dir).unwrap_or_default();
+    let config = EZLogConfigBuilder::new()
+        .dir_path(&dir)
+        .name("fetch")
+        .build();
+    ezlog::create_log(config);
+    ezlog::log(
+        EZRecord::builder()
+            .log_name("fetch")
+            .content("fetch")
+            .build(),
+    );
+
+    let now = OffsetDateTime::now_utc();
+    let start = now - Duration::minutes(1);
+    let end = now + Duration::minutes(1);
+    let future = ezlog::fetch_log_files("fetch", start, end, FetchMode::Files);
+    let id = future.id();
+    let result = future
+        .wait_timeout(std::time::Duration::from_secs(10))
+        .unwrap();
+    assert_eq!(result.id(), id);
+    assert_eq!(result.name(), "fetch");
+    assert_eq!(result.into_files().unwrap().len(), 1);
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let id = ezlog::request_log_files("not_exist", start, end, FetchMode::Files, move |result| {
+        sender.send(result).unwrap();
+    });
+    let result = receiver
+        .recv_timeout(std::time::Duration::from_secs(10))
+        .unwrap();
+    assert_eq!(result.id(), id);
+    assert!(result.into_files().is_err());
+    std::fs::remove_dir_all(&dir).unwrap_or_default();
+}
@@ -149,6 +149,8 @@ enum CipherKind {
   AES256GCM,
   AES128GCMSIV,
   AES256GCMSIV,
+  CHACHA20POLY1305,
+  XCHACHA20POLY1305,
   NONE,
   UNKNOWN,
 }
@@ -166,6 +168,10 @@ extension CipherKindValue on CipherKind {
         return 3;
       case CipherKind.AES256GCMSIV:
         return 4;
+      case CipherKind.CHACHA20POLY1305:
+        return 5;
+      case CipherKind.XCHACHA20POLY1305:
+        return 6;
       case CipherKind.NONE:
         return 0;
       case CipherKind.UNKNOWN:
@@ -0,0 +1,604 @@
+//! EZLog command line tool
+//!
+//! The binary only knows the built-in ciphers and compressions. To decode files
+//! written with a custom [Cryptor] or [Compress], build your own binary which
+//! registers them before running the tool:
+//!
+//! ```no_run
+//! fn main() -> anyhow::Result<()> {
+//!     ezlogcli::run_with_plugin(|| {
+//!         // ezlogcli::register_cryptor(0x80, |key, nonce| { ... })?;
+//!         Ok(())
+//!     })
+//! }
+//! ```
+
+use std::{
+    fs::OpenOptions,
+    io::{
+        BufReader,
+        BufWriter,
+        Cursor,
+        ErrorKind,
+        Read,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    anyhow,
+    Context,
+};
+use base64::Engine;
+use clap::{
+    Parser,
+    Subcommand,
+};
+pub use ezlog::*;
+use serde::Deserialize;
+use time::{
+    format_description::well_known::Rfc3339,
+    OffsetDateTime,
+};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Commands>,
+
+    /// Origin ezlog file path
+    #[clap(short, long, value_parser, value_name = "FILE")]
+    input: Option<PathBuf>,
+
+    /// Decode log file path
+    #[clap(short, long, value_parser, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Sets a JSON config file contains the configuration of the logger.
+    ///
+    /// {
+    ///     "key": "an example very very secret key.",
+    ///     "nonce": "unique nonce",
+    ///     "passphrase": "optional, derive the key with the header's kdf params"
+    /// }
+    #[clap(short, long, value_parser, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Decrypt key, prefix with `hex:` or `base64:` to decode it, otherwise the UTF-8 bytes
+    #[clap(short, long, value_parser)]
+    key: Option<SecretBytes>,
+
+    /// Decrypt nonce
+    #[clap(short, long, value_parser)]
+    nonce: Option<SecretBytes>,
+
+    /// Derive the decrypt key from passphrase, the log file must be written with a passphrase
+    #[clap(short, long, value_parser)]
+    passphrase: Option<SecretBytes>,
+
+    /// Sets a JSON keyring file, the key matches the log file header is used.
+    ///
+    /// [
+    ///     { "key": "hex:00112233445566778899aabbccddeeff", "nonce": "unique nonce" },
+    ///     { "key": "base64:AAECAwQFBgcICQoLDA0ODw==", "nonce": "utf8:unique nonce" }
+    /// ]
+    #[clap(long, value_parser, value_name = "FILE")]
+    keyring: Option<PathBuf>,
+
+    /// Turn debugging information on
+    #[clap(short, long, action)]
+    debug: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Verify the hash chain or the signatures of the log files in a directory
+    Verify {
+        /// Log files directory
+        #[clap(long, value_parser, value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Chain key, prefix with `hex:` or `base64:` to decode it, otherwise the UTF-8 bytes
+        #[clap(long, value_parser, required_unless_present = "pubkey")]
+        chain_key: Option<SecretBytes>,
+
+        /// Ed25519 public key, every log file must be sealed by it, prefix with `hex:` or `base64:`
+        #[clap(long, value_parser)]
+        pubkey: Option<SecretBytes>,
+
+        /// Only verify the files of the logger name, otherwise the rotated files of all the loggers
+        /// in the manifests, the logging files are not sealed and skipped
+        #[clap(long, value_parser)]
+        name: Option<String>,
+    },
+    /// Bundle the log files of a logger in a time range to a tar file with a JSON manifest
+    Bundle {
+        /// Log files directory
+        #[clap(long, value_parser, value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Logger name
+        #[clap(long, value_parser, default_value = DEFAULT_LOG_NAME)]
+        name: String,
+
+        /// Start of the range in RFC 3339, like 2023-10-14T08:00:00Z
+        #[clap(long, value_parser = parse_time)]
+        start: OffsetDateTime,
+
+        /// End of the range in RFC 3339, exclusive, default now
+        #[clap(long, value_parser = parse_time)]
+        end: Option<OffsetDateTime>,
+
+        /// Output tar file path
+        #[clap(short, long, value_parser, value_name = "FILE")]
+        output: PathBuf,
+
+        /// Decode the records in the range, the key and nonce must be set if the logs are encrypted
+        #[clap(long, action)]
+        decode: bool,
+
+        /// Decrypt key, prefix with `hex:` or `base64:` to decode it, otherwise the UTF-8 bytes
+        #[clap(short, long, value_parser)]
+        key: Option<SecretBytes>,
+
+        /// Decrypt nonce
+        #[clap(short, long, value_parser)]
+        nonce: Option<SecretBytes>,
+
+        /// Derive the decrypt key from passphrase
+        #[clap(short, long, value_parser)]
+        passphrase: Option<SecretBytes>,
+
+        /// Redact the literal text in the decoded records, can be repeated
+        #[clap(long, value_parser, value_name = "TEXT", requires = "decode")]
+        redact: Vec<String>,
+
+        /// Redact the text matched by the regex in the decoded records, can be repeated
+        #[clap(long, value_parser, value_name = "PATTERN", requires = "decode")]
+        redact_regex: Vec<String>,
+
+        /// Replace the redacted text by its keyed hash instead of the mask, prefix with `hex:` or `base64:`
+        #[clap(long, value_parser, requires = "decode")]
+        redact_hash_key: Option<SecretBytes>,
+    },
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    key: SecretBytes,
+    #[serde(default)]
+    nonce: SecretBytes,
+    #[serde(default)]
+    passphrase: Option<SecretBytes>,
+}
+
+/// Run the command line tool with the built-in ciphers and compressions.
+pub fn run() -> anyhow::Result<()> {
+    run_with_plugin(|| Ok(()))
+}
+
+/// Run the command line tool after `plugin` registered the custom implementations.
+///
+/// see [register_cryptor] and [register_compress]
+pub fn run_with_plugin<F>(plugin: F) -> anyhow::Result<()>
+where
+    F: FnOnce() -> anyhow::Result<()>,
+{
+    plugin().with_context(|| "plugin register error".to_string())?;
+    let cli = Cli::parse();
+
+    if cli.debug {
+        println!("debug enable");
+        println!();
+        println!("{:?}", cli);
+    }
+
+    if let Some(Commands::Verify {
+        dir,
+        chain_key,
+        pubkey,
+        name,
+    }) = &cli.command
+    {
+        if let Some(pubkey) = pubkey {
+            check_signatures(dir, name.as_deref(), pubkey)?;
+        }
+        if let Some(chain_key) = chain_key {
+            check_chain(dir, name.as_deref(), chain_key)?;
+        }
+        return Ok(());
+    }
+
+    if let Some(Commands::Bundle {
+        dir,
+        name,
+        start,
+        end,
+        output,
+        decode,
+        key,
+        nonce,
+        passphrase,
+        redact,
+        redact_regex,
+        redact_hash_key,
+    }) = cli.command
+    {
+        let candidate = Config {
+            key: key.unwrap_or_default(),
+            nonce: nonce.unwrap_or_default(),
+            passphrase,
+        };
+        let mut builder = EZLogConfigBuilder::new().dir_path(&dir).name(&name);
+        for rule in redact_rules(&redact, &redact_regex, redact_hash_key.as_ref())? {
+            builder = builder.redact(rule);
+        }
+        let end = end.unwrap_or_else(OffsetDateTime::now_utc);
+        return bundle(&dir, builder, start, end, &output, decode, &candidate);
+    }
+
+    let input = cli
+        .input
+        .as_deref()
+        .with_context(|| "-i input file must be set".to_string())?;
+
+    let input_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(input)
+        .with_context(|| "input file must valid".to_string())?;
+
+    let output = cli
+        .output
+        .as_deref()
+        .with_context(|| "-o output file must be set".to_string())?;
+
+    let output_file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(output)
+        .with_context(|| "output file create error".to_string())?;
+
+    let mut buf = Vec::<u8>::new();
+    let mut reader = BufReader::new(input_file);
+    reader.read_to_end(&mut buf).unwrap();
+    let mut cursor = Cursor::new(buf);
+    let header =
+        ezlog::Header::decode(&mut cursor).with_context(|| "header decode error".to_string())?;
+
+    if cli.debug {
+        println!();
+        println!("header parse {:?}", &header);
+    }
+
+    if let CipherKind::CUSTOM(id) = header.cipher() {
+        if !is_cryptor_registered(id) {
+            return Err(anyhow!(
+                "cipher {} is not registered, decode it with a plugin build",
+                header.cipher()
+            ));
+        }
+    }
+
+    if let CompressKind::CUSTOM(id) = header.compress() {
+        if !is_compress_registered(id) {
+            return Err(anyhow!(
+                "compress {:?} is not registered, decode it with a plugin build",
+                header.compress()
+            ));
+        }
+    }
+
+    let mut key = SecretBytes::default();
+    let mut nonce = SecretBytes::default();
+    let mut passphrase: Option<SecretBytes> = None;
+
+    if let Some(config_path) = cli.config.as_deref() {
+        if let Ok(config_file) = OpenOptions::new().read(true).open(config_path) {
+            let mut json = String::new();
+            if BufReader::new(config_file)
+                .read_to_string(&mut json)
+                .is_ok()
+            {
+                let config: Config = serde_json::from_str(&json)
+                    .with_context(|| "config file is not valid".to_string())?;
+
+                if cli.debug {
+                    println!(
+                        "config read \n    key={:?} \n    nonce={:?}",
+                        &config.key, &config.nonce
+                    );
+                }
+                key = config.key;
+                nonce = config.nonce;
+                passphrase = config.passphrase;
+            }
+        } else {
+            println!("config file read error")
+        }
+    }
+
+    if key.is_empty() {
+        key = cli.key.unwrap_or_default()
+    }
+
+    if nonce.is_empty() {
+        nonce = cli.nonce.unwrap_or_default()
+    }
+
+    if passphrase.is_none() {
+        passphrase = cli.passphrase
+    }
+
+    let mut candidates = Vec::new();
+    let keyring = match cli.keyring.as_deref() {
+        Some(keyring_path) => {
+            let json = std::fs::read_to_string(keyring_path)
+                .with_context(|| "keyring file read error".to_string())?;
+            serde_json::from_str::<Vec<Config>>(&json)
+                .with_context(|| "keyring file is not valid".to_string())?
+        }
+        None => Vec::new(),
+    };
+    if keyring.is_empty() || !key.is_empty() || passphrase.is_some() {
+        candidates.push(Config {
+            key,
+            nonce,
+            passphrase,
+        });
+    }
+    candidates.extend(keyring);
+
+    let config = select_config(&header, &mut cursor, &candidates, cli.debug)?;
+
+    let compression = ezlog::create_compress(&config);
+    let decryptor =
+        ezlog::create_cryptor(&config).with_context(|| "create cryptor error".to_string())?;
+
+    let mut plain_text_write = BufWriter::new(output_file);
+
+    ezlog::decode::decode_with_writer(
+        &mut cursor,
+        &mut plain_text_write,
+        compression,
+        decryptor,
+        &header,
+    )
+    .map_err(|e| anyhow!(format!("{}", e)))
+}
+
+fn check_signatures(dir: &Path, name: Option<&str>, pubkey: &SecretBytes) -> anyhow::Result<()> {
+    let pubkey = parse_bytes(pubkey)?;
+    let paths = sealed_files(dir, name)?;
+    let mut count = 0;
+    for path in paths {
+        let file = std::fs::read(&path).with_context(|| "log file read error".to_string())?;
+        ezlog::verify_signature(&file, pubkey.expose())
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        println!("{}", path.display());
+        count += 1;
+    }
+    if count == 0 {
+        return Err(anyhow!("no log file in {}", dir.display()));
+    }
+    println!("signature verified, {} files", count);
+    Ok(())
+}
+
+/// The rotated files in the manifests of the loggers, the logging files are not sealed yet
+fn sealed_files(dir: &Path, name: Option<&str>) -> anyhow::Result<Vec<PathBuf>> {
+    let names = match name {
+        Some(name) => vec![name.to_string()],
+        None => {
+            let mut names = std::fs::read_dir(dir)
+                .with_context(|| "log directory read error".to_string())?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "manifest"))
+                .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        }
+    };
+    let mut paths = Vec::new();
+    for name in names {
+        let config = EZLogConfigBuilder::new().dir_path(dir).name(&name).build();
+        let entries = config
+            .manifest_entries()
+            .with_context(|| format!("manifest of {} read error", name))?;
+        paths.extend(entries.iter().map(|entry| dir.join(entry.file_name())));
+    }
+    Ok(paths)
+}
+
+fn check_chain(dir: &PathBuf, name: Option<&str>, chain_key: &SecretBytes) -> anyhow::Result<()> {
+    let key = parse_bytes(chain_key)?;
+    let summary = ezlog::verify_chain(dir, name, key.expose())
+        .with_context(|| "verify chain error".to_string())?;
+    if let Some(first_break) = summary.first_break {
+        return Err(anyhow!(
+            "chain broken after {} files and {} records: {}",
+            summary.files.len(),
+            summary.records,
+            first_break
+        ));
+    }
+    if summary.files.is_empty() {
+        return Err(anyhow!("no chained log file in {}", dir.display()));
+    }
+    for file in summary.files.iter() {
+        println!("{}", file.display());
+    }
+    println!(
+        "chain verified, {} files and {} records",
+        summary.files.len(),
+        summary.records
+    );
+    Ok(())
+}
+
+/// Parse the key, nonce or passphrase, `hex:` and `base64:` prefix are decoded,
+/// `utf8:` prefix or no prefix means the raw bytes.
+fn parse_bytes(secret: &SecretBytes) -> anyhow::Result<SecretBytes> {
+    let Ok(value) = std::str::from_utf8(secret.expose()) else {
+        return Ok(secret.clone());
+    };
+    if let Some(hex) = value.strip_prefix("hex:") {
+        hex::decode(hex)
+            .map(SecretBytes::from)
+            .map_err(|_| anyhow!("invalid hex value"))
+    } else if let Some(base64) = value.strip_prefix("base64:") {
+        base64::engine::general_purpose::STANDARD
+            .decode(base64)
+            .map(SecretBytes::from)
+            .map_err(|_| anyhow!("invalid base64 value"))
+    } else {
+        Ok(SecretBytes::from(
+            value.strip_prefix("utf8:").unwrap_or(value),
+        ))
+    }
+}
+
+/// The redaction rules of the decoded records in the bundle
+fn redact_rules(
+    literals: &[String],
+    patterns: &[String],
+    hash_key: Option<&SecretBytes>,
+) -> anyhow::Result<Vec<RedactRule>> {
+    let mut rules = literals.iter().map(RedactRule::literal).collect::<Vec<_>>();
+    for pattern in patterns {
+        rules.push(RedactRule::regex(pattern).with_context(|| "redact regex error".to_string())?);
+    }
+    if let Some(key) = hash_key {
+        let key = parse_bytes(key)?;
+        rules = rules
+            .into_iter()
+            .map(|rule| rule.hash(key.expose().to_vec()))
+            .collect();
+    }
+    Ok(rules)
+}
+
+/// Bundle the logs of the logger, `builder` has the dir, the name and the redaction rules
+fn bundle(
+    dir: &Path,
+    builder: EZLogConfigBuilder,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    output: &Path,
+    decode: bool,
+    candidate: &Config,
+) -> anyhow::Result<()> {
+    let base = builder.clone().build();
+    // the cipher and compression are read from the current file, or the first rotated file
+    let header = std::fs::read(dir.join(base.file_name()?))
+        .ok()
+        .or_else(|| {
+            let path = base
+                .query_log_files_in_range(start, end)
+                .into_iter()
+                .next()?;
+            std::fs::read(path).ok()
+        })
+        .and_then(|file| Header::decode(&mut Cursor::new(file)).ok());
+    let config = match &header {
+        Some(header) => create_config_with(builder, header, candidate)?,
+        None => base,
+    };
+    let manifest = ezlog::export_with_config(&config, start, end, decode, output)
+        .with_context(|| "bundle error".to_string())?;
+    for file in manifest.files() {
+        println!("{}", file.entry());
+    }
+    println!(
+        "bundled {} files to {}",
+        manifest.files().len(),
+        output.display()
+    );
+    Ok(())
+}
+
+fn parse_time(value: &str) -> Result<OffsetDateTime, String> {
+    OffsetDateTime::parse(value, &Rfc3339).map_err(|e| e.to_string())
+}
+
+fn create_config(header: &Header, candidate: &Config) -> anyhow::Result<EZLogConfig> {
+    create_config_with(EZLogConfigBuilder::new(), header, candidate)
+}
+
+fn create_config_with(
+    builder: EZLogConfigBuilder,
+    header: &Header,
+    candidate: &Config,
+) -> anyhow::Result<EZLogConfig> {
+    let mut builder = builder
+        .from_header(header)
+        .cipher_key(parse_bytes(&candidate.key)?)
+        .cipher_nonce(parse_bytes(&candidate.nonce)?);
+    if let Some(passphrase) = &candidate.passphrase {
+        if header.kdf().is_none() {
+            return Err(anyhow!("log file is not written with a passphrase"));
+        }
+        builder = builder.cipher_passphrase(parse_bytes(passphrase)?);
+    }
+    let mut config = builder.build();
+    config
+        .derive_cipher_key()
+        .with_context(|| "derive key from passphrase error".to_string())?;
+    Ok(config)
+}
+
+/// Pick the config whose key fingerprint matches the header.
+///
+/// files written by older versions have an unstable fingerprint,
+/// so fall back to the first key which can decrypt the first record.
+fn select_config(
+    header: &Header,
+    cursor: &mut Cursor<Vec<u8>>,
+    candidates: &[Config],
+    debug: bool,
+) -> anyhow::Result<EZLogConfig> {
+    if candidates.len() == 1 {
+        return create_config(header, &candidates[0]);
+    }
+    let mut configs = Vec::new();
+    for (index, candidate) in candidates.iter().enumerate() {
+        match create_config(header, candidate) {
+            Ok(config) if header.is_match(&config) => {
+                if debug {
+                    println!("key {} fingerprint matched", index);
+                }
+                return Ok(config);
+            }
+            Ok(config) => configs.push((index, config)),
+            Err(e) => {
+                if debug {
+                    println!("key {} skipped: {:#}", index, e);
+                }
+            }
+        }
+    }
+
+    for (index, config) in configs {
+        let compression = ezlog::create_compress(&config);
+        let Ok(decryptor) = ezlog::create_cryptor(&config) else {
+            continue;
+        };
+        match ezlog::decode::decode_first_record(cursor, &compression, &decryptor, header) {
+            Ok(_) => {}
+            Err(LogError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => {}
+            Err(_) => continue,
+        }
+        if debug {
+            println!("key {} decrypted the first record", index);
+        }
+        return Ok(config);
+    }
+    Err(anyhow!("no key in the keyring can decrypt the log file"))
+}
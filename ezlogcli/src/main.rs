@@ -1,163 +1,5 @@
-use std::{
-    fs::OpenOptions,
-    io::{
-        BufReader,
-        BufWriter,
-        Cursor,
-        Read,
-    },
-    path::PathBuf,
-};
-
-use anyhow::{
-    anyhow,
-    Context,
-};
-use clap::Parser;
-pub use ezlog::*;
-use serde::{
-    Deserialize,
-    Serialize,
-};
-
-#[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
-struct Cli {
-    /// Origin ezlog file path
-    #[clap(short, long, value_parser, value_name = "FILE")]
-    input: Option<PathBuf>,
-
-    /// Decode log file path
-    #[clap(short, long, value_parser, value_name = "FILE")]
-    output: Option<PathBuf>,
-
-    /// Sets a JSON config file contains the configuration of the logger.
-    ///
-    /// {
-    ///     "key": "an example very very secret key.",
-    ///     "nonce": "unique nonce"
-    /// }
-    #[clap(short, long, value_parser, value_name = "FILE")]
-    config: Option<PathBuf>,
-
-    /// Decrypt key
-    #[clap(short, long, value_parser)]
-    key: Option<String>,
-
-    /// Decrypt nonce
-    #[clap(short, long, value_parser)]
-    nonce: Option<String>,
-
-    /// Turn debugging information on
-    #[clap(short, long, action)]
-    debug: bool,
-}
-
-#[derive(Serialize, Deserialize)]
-struct Config {
-    key: String,
-    nonce: String,
-}
-
-pub fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
-
-    if cli.debug {
-        println!("debug enable");
-        println!();
-        println!("{:?}", cli);
-    }
-
-    let input = cli
-        .input
-        .as_deref()
-        .with_context(|| "-i input file must be set".to_string())?;
-
-    let input_file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(input)
-        .with_context(|| "input file must valid".to_string())?;
-
-    let output = cli
-        .output
-        .as_deref()
-        .with_context(|| "-o output file must be set".to_string())?;
-
-    let output_file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(false)
-        .open(output)
-        .with_context(|| "output file create error".to_string())?;
-
-    let mut buf = Vec::<u8>::new();
-    let mut reader = BufReader::new(input_file);
-    reader.read_to_end(&mut buf).unwrap();
-    let mut cursor = Cursor::new(buf);
-    let header =
-        ezlog::Header::decode(&mut cursor).with_context(|| "header decode error".to_string())?;
-
-    if cli.debug {
-        println!();
-        println!("header parse {:?}", &header);
-    }
-
-    let mut key: Vec<u8> = Vec::new();
-    let mut nonce: Vec<u8> = Vec::new();
-
-    if let Some(config_path) = cli.config.as_deref() {
-        if let Ok(config_file) = OpenOptions::new().read(true).open(config_path) {
-            let mut json = String::new();
-            if BufReader::new(config_file)
-                .read_to_string(&mut json)
-                .is_ok()
-            {
-                let config: Config = serde_json::from_str(&json)
-                    .with_context(|| "config file is not valid".to_string())?;
-                key = config.key.as_bytes().to_vec();
-                nonce = config.nonce.as_bytes().to_vec();
-
-                if cli.debug {
-                    println!(
-                        "config read \n    key={} \n    nonce={}",
-                        &config.key, &config.nonce
-                    );
-                }
-            }
-        } else {
-            println!("config file read error")
-        }
-    }
-
-    if key.is_empty() {
-        key = cli.key.map_or(vec![], |k| k.as_bytes().to_vec())
-    }
-
-    if nonce.is_empty() {
-        nonce = cli.nonce.map_or(vec![], |n| n.as_bytes().to_vec())
-    }
-
-    let config = EZLogConfigBuilder::new()
-        .from_header(&header)
-        .cipher_key(key)
-        .cipher_nonce(nonce)
-        .build();
-
-    let compression = ezlog::create_compress(&config);
-    let decryptor =
-        ezlog::create_cryptor(&config).with_context(|| "create cryptor error".to_string())?;
-
-    let mut plain_text_write = BufWriter::new(output_file);
-
-    ezlog::decode::decode_with_writer(
-        &mut cursor,
-        &mut plain_text_write,
-        compression,
-        decryptor,
-        &header,
-    )
-    .map_err(|e| anyhow!(format!("{}", e)))
+fn main() -> anyhow::Result<()> {
+    ezlogcli::run()
 }
 
 #[cfg(test)]
@@ -212,4 +54,281 @@ mod tests {
             .unwrap();
         cmd.assert().success();
     }
+
+    #[test]
+    fn test_decode_keyring() {
+        let bin_under_test = escargot::CargoBuild::new()
+            .bin("ezlogcli")
+            .current_release()
+            .current_target()
+            .run()
+            .unwrap();
+
+        let mut input_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        input_file.push("resources/test/test.mmap");
+
+        let cache_dir = dirs::cache_dir().unwrap();
+        let keyring_file = cache_dir.join("keyring.json");
+        std::fs::write(
+            &keyring_file,
+            format!(
+                r#"[
+                    {{ "key": "an example very very wrong key..", "nonce": "unique nonce" }},
+                    {{ "key": "hex:{}", "nonce": "base64:dW5pcXVlIG5vbmNl" }}
+                ]"#,
+                hex::encode("an example very very secret key.")
+            ),
+        )
+        .unwrap();
+        let output_file = cache_dir.join("2.log");
+        std::fs::remove_file(&output_file).ok();
+
+        let cmd = bin_under_test
+            .command()
+            .arg("-i")
+            .arg(input_file.into_os_string())
+            .arg("-o")
+            .arg(&output_file)
+            .arg("--keyring")
+            .arg(&keyring_file)
+            .unwrap();
+        cmd.assert().success();
+        let output = std::fs::read_to_string(&output_file).unwrap();
+        assert!(output.contains("create default log"));
+    }
+
+    #[test]
+    fn test_verify() {
+        let bin_under_test = escargot::CargoBuild::new()
+            .bin("ezlogcli")
+            .current_release()
+            .current_target()
+            .run()
+            .unwrap();
+
+        let dir = dirs::cache_dir().unwrap().join("verify");
+        std::fs::remove_dir_all(&dir).ok();
+        ezlogcli::init();
+        ezlogcli::create_log(
+            ezlogcli::EZLogConfigBuilder::new()
+                .dir_path(dir.to_str().unwrap())
+                .name("audit")
+                .chain_key("hex:00112233")
+                .build(),
+        );
+        for i in 0..3 {
+            ezlogcli::log(
+                ezlogcli::EZRecord::builder()
+                    .log_name("audit")
+                    .content(format!("audit {}", i))
+                    .build(),
+            );
+        }
+        ezlogcli::flush("audit");
+        for _ in 0..50 {
+            let records = ezlogcli::verify_chain(&dir, Some("audit"), b"hex:00112233")
+                .map(|summary| summary.records)
+                .unwrap_or_default();
+            if records == 3 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+
+        let output = bin_under_test
+            .command()
+            .arg("verify")
+            .arg("--dir")
+            .arg(&dir)
+            .arg("--chain-key")
+            .arg("utf8:hex:00112233")
+            .arg("--name")
+            .arg("audit")
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains("1 files and 3 records"));
+
+        bin_under_test
+            .command()
+            .arg("verify")
+            .arg("--dir")
+            .arg(&dir)
+            .arg("--chain-key")
+            .arg("wrong key")
+            .assert()
+            .failure();
+    }
+
+    #[test]
+    fn test_bundle() {
+        let bin_under_test = escargot::CargoBuild::new()
+            .bin("ezlogcli")
+            .current_release()
+            .current_target()
+            .run()
+            .unwrap();
+
+        let dir = dirs::cache_dir().unwrap().join("bundle");
+        std::fs::remove_dir_all(&dir).ok();
+        ezlogcli::init();
+        ezlogcli::create_log(
+            ezlogcli::EZLogConfigBuilder::new()
+                .dir_path(dir.to_str().unwrap())
+                .name("support")
+                .build(),
+        );
+        ezlogcli::log(
+            ezlogcli::EZRecord::builder()
+                .log_name("support")
+                .content("support record")
+                .build(),
+        );
+        ezlogcli::flush("support");
+        let logging = dir.join("support.mmap");
+        for _ in 0..50 {
+            let flushed = std::fs::read(&logging)
+                .map(|file| String::from_utf8_lossy(&file).contains("support record"))
+                .unwrap_or(false);
+            if flushed {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+
+        let output_file = dir.join("support.tar");
+        let output = bin_under_test
+            .command()
+            .arg("bundle")
+            .arg("--dir")
+            .arg(&dir)
+            .arg("--name")
+            .arg("support")
+            .arg("--start")
+            .arg("2020-01-01T00:00:00Z")
+            .arg("--output")
+            .arg(&output_file)
+            .arg("--decode")
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("logs/support.mmap.log"));
+        assert!(stdout.contains("bundled 1 files"));
+        let bundle = std::fs::read(&output_file).unwrap();
+        assert!(String::from_utf8_lossy(&bundle).contains("support record"));
+
+        bin_under_test
+            .command()
+            .arg("bundle")
+            .arg("--dir")
+            .arg(&dir)
+            .arg("--name")
+            .arg("support")
+            .arg("--start")
+            .arg("2020-01-01T00:00:00Z")
+            .arg("--output")
+            .arg(&output_file)
+            .arg("--decode")
+            .arg("--redact")
+            .arg("support")
+            .arg("--redact-regex")
+            .arg("rec[a-z]+")
+            .unwrap();
+        let bundle = std::fs::read(&output_file).unwrap();
+        let bundle = String::from_utf8_lossy(&bundle);
+        assert!(!bundle.contains("support record"));
+        assert!(bundle.contains("] *** ***"));
+
+        // the redaction applies to the decoded records only
+        bin_under_test
+            .command()
+            .arg("bundle")
+            .arg("--dir")
+            .arg(&dir)
+            .arg("--start")
+            .arg("2020-01-01T00:00:00Z")
+            .arg("--output")
+            .arg(&output_file)
+            .arg("--redact")
+            .arg("support")
+            .assert()
+            .failure();
+
+        bin_under_test
+            .command()
+            .arg("bundle")
+            .arg("--dir")
+            .arg(&dir)
+            .arg("--start")
+            .arg("yesterday")
+            .arg("--output")
+            .arg(&output_file)
+            .assert()
+            .failure();
+    }
+
+    #[test]
+    fn test_verify_pubkey() {
+        let bin_under_test = escargot::CargoBuild::new()
+            .bin("ezlogcli")
+            .current_release()
+            .current_target()
+            .run()
+            .unwrap();
+
+        let dir = dirs::cache_dir().unwrap().join("verify_pubkey");
+        std::fs::remove_dir_all(&dir).ok();
+        let seed = b"an example very very secret key.";
+        ezlogcli::init();
+        ezlogcli::create_log(
+            ezlogcli::EZLogConfigBuilder::new()
+                .dir_path(dir.to_str().unwrap())
+                .name("signed")
+                .signing_key(seed.to_vec())
+                .build(),
+        );
+        ezlogcli::log(
+            ezlogcli::EZRecord::builder()
+                .log_name("signed")
+                .content("signed record")
+                .build(),
+        );
+        // rotate the file to seal it
+        ezlogcli::rotate("signed");
+        let public_key = ezlogcli::public_key(seed).unwrap();
+        let is_sealed = |path: &PathBuf| {
+            std::fs::read(path)
+                .map(|file| ezlogcli::verify_signature(&file, &public_key).is_ok())
+                .unwrap_or(false)
+        };
+        for _ in 0..50 {
+            let sealed = std::fs::read_dir(&dir)
+                .map(|dir| dir.flatten().any(|entry| is_sealed(&entry.path())))
+                .unwrap_or(false);
+            if sealed {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+
+        let output = bin_under_test
+            .command()
+            .arg("verify")
+            .arg("--dir")
+            .arg(&dir)
+            .arg("--pubkey")
+            .arg(format!("hex:{}", hex::encode(public_key)))
+            .arg("--name")
+            .arg("signed")
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains("signature verified, 1 files"));
+
+        // the logging file is not sealed and skipped
+        let output = bin_under_test
+            .command()
+            .arg("verify")
+            .arg("--dir")
+            .arg(&dir)
+            .arg("--pubkey")
+            .arg(format!("hex:{}", hex::encode(public_key)))
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains("signature verified, 1 files"));
+    }
 }
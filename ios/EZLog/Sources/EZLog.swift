@@ -184,6 +184,8 @@ public enum Cipher: Int {
     case AES256GCM
     case AES128GCMSIV
     case AES256GCMSIV
+    case CHACHA20POLY1305
+    case XCHACHA20POLY1305
 }
 
 public enum CompressLevel: Int, Codable {
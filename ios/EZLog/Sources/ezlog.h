@@ -20,6 +20,57 @@ typedef struct Callback {
                                             const char* _Nonnull);
 } Callback;
 
+/**
+ * map to c EZFetchedFile struct, the times are unix timestamp in milliseconds
+ */
+typedef struct EZFetchedFile {
+    const char* _Nonnull path;
+    uint64_t size;
+    unsigned char version;
+    unsigned char compress;
+    unsigned char cipher;
+    bool is_active;
+    unsigned int key_fingerprint;
+    int64_t created_time;
+    int64_t rotated_time;
+    uint64_t records;
+} EZFetchedFile;
+
+/**
+ * map to c FilesCallback struct
+ */
+typedef struct FilesCallback {
+    const void* _Nonnull successPoint;
+    const void(* _Nonnull onFilesFetchSuccess)(void* _Nonnull,
+                                               const char* _Nonnull,
+                                               const char* _Nonnull,
+                                               const struct EZFetchedFile* _Nonnull,
+                                               int32_t);
+    const void* _Nonnull failPoint;
+    const void (* _Nonnull onLogsFetchFail)(void* _Nonnull,
+                                            const char* _Nonnull,
+                                            const char* _Nonnull,
+                                            const char* _Nonnull);
+} FilesCallback;
+
+/**
+ * map to c FetchCallback struct, it is called once with the result of the request
+ */
+typedef struct FetchCallback {
+    const void* _Nonnull point;
+    const void(* _Nonnull onFetchSuccess)(void* _Nonnull,
+                                          uint64_t,
+                                          const char* _Nonnull,
+                                          const char* _Nonnull,
+                                          const struct EZFetchedFile* _Nonnull,
+                                          int32_t);
+    const void (* _Nonnull onFetchFail)(void* _Nonnull,
+                                        uint64_t,
+                                        const char* _Nonnull,
+                                        const char* _Nonnull,
+                                        const char* _Nonnull);
+} FetchCallback;
+
 /**
  * Init ezlog, must call before any other function
  */
@@ -72,6 +123,13 @@ void ezlog_trim(void);
  */
 void ezlog_register_callback(struct Callback callback);
 
+/**
+ * Register callback function for get logger's files with metadata asynchronously
+ *
+ * only the first registered callback takes effect, register either this or `ezlog_register_callback`
+ */
+void ezlog_register_files_callback(struct FilesCallback callback);
+
 /**
  * Request logger's files path array by specified date
  * before call this function, you should register a callback
@@ -84,3 +142,16 @@ void ezlog_register_callback(struct Callback callback);
 void ezlog_request_log_files_for_date(const char * _Nonnull c_log_name,
                                       int64_t c_start_time,
                                       int64_t c_end_time);
+
+/**
+ * Request logger's files in the time range `[start, end)` with a callback of this request
+ *
+ * the times are unix timestamp in milliseconds, `c_mode` is 0 for files, 1 for decoded records and
+ * 2 for encoded records. returns the request id which is passed to the callback, or 0 if the
+ * request is rejected, like the time range is illegal, `onFetchFail` is called with the id 0.
+ */
+uint64_t ezlog_request_log_files(const char * _Nonnull c_log_name,
+                                 int64_t c_start_time,
+                                 int64_t c_end_time,
+                                 unsigned char c_mode,
+                                 struct FetchCallback callback);